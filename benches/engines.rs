@@ -0,0 +1,86 @@
+//! Times the tree walker and the VM on the same programs, at the top level
+//! and inside a function. Run with `cargo bench --bench engines`.
+
+use std::time::{Duration, Instant};
+
+use pluto_lang::{Engine, Interpreter};
+
+const RUNS: usize = 5;
+
+const WHILE: &str = "
+    let i = 0;
+    let total = 0;
+    while (i < 1000000) {
+        total = total + i % 7;
+        i = i + 1;
+    }
+    total;";
+
+const FOR: &str = "
+    let total = 0;
+    for (let i = 0; i < 1000000; i++) {
+        total += i % 7;
+    }
+    total;";
+
+const FIB: &str = "
+    fn fib(n) {
+        if (n < 2) { return n; }
+        return fib(n - 1) + fib(n - 2);
+    }
+    fib(22);";
+
+const SIEVE: &str = "
+    let n = 5000;
+    let flags = (0..=n).map((x) -> true);
+    let count = 0;
+    for (let i = 2; i <= n; i++) {
+        if (flags[i]) {
+            count += 1;
+            let j = i * i;
+            while (j <= n) {
+                flags[j] = false;
+                j += i;
+            }
+        }
+    }
+    count;";
+
+/// The same program in the body of a function, returning the value of its last line.
+fn in_function(source: &str) -> String {
+    let (statements, result) = source.rsplit_once('\n').unwrap();
+    format!("fn main() {{ {}\n return {} }} main();", statements, result.trim())
+}
+
+/// The fastest of `RUNS` runs, with the value the program ended with.
+fn time(engine: Engine, source: &str) -> (Duration, String) {
+    let mut best = Duration::MAX;
+    let mut value = String::new();
+    for _ in 0..RUNS {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_engine(engine);
+        let start = Instant::now();
+        let result = interpreter.run_source(source).unwrap_or_else(|e| panic!("{}", e.message));
+        best = best.min(start.elapsed());
+        value = result.to_string();
+    }
+    (best, value)
+}
+
+fn main() {
+    println!("{:<12} {:>12} {:>12} {:>8}", "program", "tree walker", "vm", "speedup");
+    for (name, source) in [("while", WHILE), ("for", FOR), ("fib", FIB), ("sieve", SIEVE)] {
+        for (name, source) in [(name.to_string(), source.to_string()), (format!("fn {}", name), in_function(source))] {
+            let (tree, tree_value) = time(Engine::TreeWalker, &source);
+            let (vm, vm_value) = time(Engine::Vm, &source);
+            assert_eq!(tree_value, vm_value, "the engines disagree on {}", name);
+            println!(
+                "{:<12} {:>10.1}ms {:>10.1}ms {:>7.1}x",
+                name,
+                tree.as_secs_f64() * 1000.0,
+                vm.as_secs_f64() * 1000.0,
+                tree.as_secs_f64() / vm.as_secs_f64()
+            );
+        }
+    }
+}
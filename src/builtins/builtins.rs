@@ -1,74 +1,89 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use crate::{evaluator::evaluator::Value};
 use std::io::Write;
+use std::rc::Rc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::thread;
-use crate::evaluator::evaluator::{Evaluator, EvalResult};
-
-pub type MethodFn = fn(&Value, Vec<Value>) -> Result<Value, String>;
+use crate::evaluator::evaluator::{Evaluator, NativeFunction};
+use crate::evaluator::bigint::BigInt;
+use crate::evaluator::ordered_map::OrderedMap;
+use crate::evaluator::range::IntRange;
+use crate::error::error::PlutoError;
+use crate::parser::ast::BinaryOp;
+use crate::utils::unicode;
+
+pub type MethodFn = fn(&mut Evaluator, &Value, Vec<Value>) -> Result<Value, PlutoError>;
+
+fn native(f: impl Fn(&mut Evaluator, Vec<Value>) -> Result<Value, PlutoError> + 'static) -> Value {
+    Value::BuiltInFunction(NativeFunction::new(f))
+}
 
-fn string_len(v: &Value, _: Vec<Value>) -> Result<Value, String> {
+fn string_len(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::String(s) = v {
-        Ok(Value::Number(s.len() as i64))
+        Ok(Value::Number(s.chars().count() as i64))
     } else {
-        Err("Not a string".into())
+        Err(PlutoError::type_error("Not a string"))
     }
 }
 
-fn string_to_number(v: &Value, _: Vec<Value>) -> Result<Value, String> {
+fn string_to_number(evaluator: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::String(s) = v {
         if let Ok(n) = s.parse::<i64>() {
             Ok(Value::Number(n))
+        } else if let Some(n) = BigInt::parse(s) {
+            evaluator.integer_result(None, || n)
         } else {
-            Err("Not a number".into())
+            Err(PlutoError::value(format!("Cannot convert '{}' to Number", s)))
         }
     } else {
-        Err("Not a string".into())
+        Err(PlutoError::type_error("Not a string"))
     }
 }
 
-fn string_to_float(v: &Value, _: Vec<Value>) -> Result<Value, String> {
+fn string_to_float(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::String(s) = v {
         if let Ok(n) = s.parse::<f64>() {
             Ok(Value::Float(n))
         } else {
-            Err("Not a float".into())
+            Err(PlutoError::value(format!("Cannot convert '{}' to Float", s)))
         }
     } else {
-        Err("Not a string".into())
+        Err(PlutoError::type_error("Not a string"))
     }
 }
 
-fn string_to_uppercase(v: &Value, _: Vec<Value>) -> Result<Value, String> {
+fn string_to_uppercase(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::String(s) = v {
         Ok(Value::String(s.to_uppercase()))
     } else {
-        Err("Not a string".into())
+        Err(PlutoError::type_error("Not a string"))
     }
 }
 
-fn string_to_lowercase(v: &Value, _: Vec<Value>) -> Result<Value, String> {
+fn string_to_lowercase(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::String(s) = v {
         Ok(Value::String(s.to_lowercase()))
     } else {
-        Err("Not a string".into())
+        Err(PlutoError::type_error("Not a string"))
     }
 }
 
-fn string_char_at(v: &Value, args: Vec<Value>) -> Result<Value, String> {
+fn string_char_at(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::String(s) = v {
-        if let Some(Value::Number(n)) = args.get(0) {
-            if *n >= 0 && *n < s.len() as i64 {
-                return Ok(Value::String(s.chars().nth(*n as usize).unwrap().to_string()));
-            }
+        if let Some(Value::Number(n)) = args.first()
+            && let Some(c) = usize::try_from(*n).ok().and_then(|n| s.chars().nth(n))
+        {
+            return Ok(Value::String(c.to_string()));
         }
-        Err("Index out of bounds".into())
+        Err(PlutoError::index("Index out of bounds"))
     } else {
-        Err("Not a string".into())
+        Err(PlutoError::type_error("Not a string"))
     }
 }
 
-fn string_concat(v: &Value, args: Vec<Value>) -> Result<Value, String> {
+fn string_concat(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::String(s) = v {
         let mut result = s.clone();
         for arg in args {
@@ -76,69 +91,202 @@ fn string_concat(v: &Value, args: Vec<Value>) -> Result<Value, String> {
         }
         Ok(Value::String(result))
     } else {
-        Err("Not a string".into())
+        Err(PlutoError::type_error("Not a string"))
     }
 }
 
-fn string_is_a_number(v: &Value, _: Vec<Value>) -> Result<Value, String> {
+fn string_is_a_number(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::String(s) = v {
-        if let Ok(_) = s.parse::<i64>() {
+        if s.parse::<i64>().is_ok() {
             Ok(Value::Bool(true))
         } else {
             Ok(Value::Bool(false))
         }
     } else {
-        Err("Not a string".into())
+        Err(PlutoError::type_error("Not a string"))
     }
 }
 
-fn string_is_a_float(v: &Value, _: Vec<Value>) -> Result<Value, String> {
+fn string_is_a_float(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::String(s) = v {
-        if let Ok(_) = s.parse::<f64>() {
+        if s.parse::<f64>().is_ok() {
             Ok(Value::Bool(true))
         } else {
             Ok(Value::Bool(false))
         }
     } else {
-        Err("Not a string".into())
+        Err(PlutoError::type_error("Not a string"))
+    }
+}
+
+fn as_string(v: &Value) -> Result<&str, PlutoError> {
+    match v {
+        Value::String(s) => Ok(s),
+        _ => Err(PlutoError::type_error("Not a string")),
+    }
+}
+
+fn string_arg<'a>(args: &'a [Value], index: usize, method: &str) -> Result<&'a str, PlutoError> {
+    match args.get(index) {
+        Some(Value::String(s)) => Ok(s),
+        _ => Err(PlutoError::argument(format!("{} expects a String", method))),
+    }
+}
+
+fn string_split(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let s = as_string(v)?;
+    let parts: Vec<Value> = match args.first() {
+        None => s.split_whitespace().map(|part| Value::String(part.to_string())).collect(),
+        Some(Value::String(sep)) if sep.is_empty() => s.chars().map(|c| Value::String(c.to_string())).collect(),
+        Some(Value::String(sep)) => s.split(sep.as_str()).map(|part| Value::String(part.to_string())).collect(),
+        Some(_) => return Err(PlutoError::argument("split expects a String separator")),
+    };
+    Ok(Value::Array(parts))
+}
+
+fn string_trim(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::String(as_string(v)?.trim().to_string()))
+}
+
+fn string_trim_start(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::String(as_string(v)?.trim_start().to_string()))
+}
+
+fn string_trim_end(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::String(as_string(v)?.trim_end().to_string()))
+}
+
+fn string_replace(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let from = string_arg(&args, 0, "replace")?;
+    let to = string_arg(&args, 1, "replace")?;
+    Ok(Value::String(as_string(v)?.replace(from, to)))
+}
+
+fn string_contains(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::Bool(as_string(v)?.contains(string_arg(&args, 0, "contains")?)))
+}
+
+fn string_starts_with(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::Bool(as_string(v)?.starts_with(string_arg(&args, 0, "starts_with")?)))
+}
+
+fn string_ends_with(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::Bool(as_string(v)?.ends_with(string_arg(&args, 0, "ends_with")?)))
+}
+
+fn string_index_of(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let s = as_string(v)?;
+    let index = s.find(string_arg(&args, 0, "index_of")?).map(|byte| s[..byte].chars().count() as i64);
+    Ok(Value::Number(index.unwrap_or(-1)))
+}
+
+fn string_substring(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let chars: Vec<char> = as_string(v)?.chars().collect();
+    let start = array_index(&args, 0, chars.len(), 0, "substring")?;
+    let end = array_index(&args, 1, chars.len(), chars.len(), "substring")?;
+    Ok(Value::String(chars[start..end.max(start)].iter().collect()))
+}
+
+fn string_repeat(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    match args.first() {
+        Some(Value::Number(n)) if *n >= 0 => Ok(Value::String(as_string(v)?.repeat(*n as usize))),
+        Some(Value::Number(_)) => Err(PlutoError::value("repeat count cannot be negative")),
+        _ => Err(PlutoError::argument("repeat expects a Number")),
+    }
+}
+
+fn string_lines(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::Array(as_string(v)?.lines().map(|line| Value::String(line.to_string())).collect()))
+}
+
+fn string_chars(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::Array(as_string(v)?.chars().map(|c| Value::String(c.to_string())).collect()))
+}
+
+/// The fill that pads `s` to `width` characters: `fill` repeated and cut to length.
+fn padding(s: &str, args: &[Value], method: &str) -> Result<String, PlutoError> {
+    let width = match args.first() {
+        Some(Value::Number(n)) => (*n).max(0) as usize,
+        _ => return Err(PlutoError::argument(format!("{} expects a Number width", method))),
+    };
+    let fill = match args.get(1) {
+        None => " ",
+        Some(Value::String(fill)) if !fill.is_empty() => fill,
+        Some(_) => return Err(PlutoError::argument(format!("{} expects a non-empty String fill", method))),
+    };
+    Ok(fill.chars().cycle().take(width.saturating_sub(s.chars().count())).collect())
+}
+
+fn string_pad_start(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let s = as_string(v)?;
+    Ok(Value::String(padding(s, &args, "pad_start")? + s))
+}
+
+fn string_pad_end(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let s = as_string(v)?;
+    Ok(Value::String(s.to_string() + &padding(s, &args, "pad_end")?))
+}
+
+fn string_bytes(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::Array(as_string(v)?.bytes().map(|b| Value::Number(b as i64)).collect()))
+}
+
+fn string_graphemes(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::Array(unicode::graphemes(as_string(v)?).into_iter().map(Value::String).collect()))
+}
+
+fn string_reverse(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::String(as_string(v)?.chars().rev().collect()))
+}
+
+fn string_is_empty(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::Bool(as_string(v)?.is_empty()))
+}
+
+fn string_count(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    match string_arg(&args, 0, "count")? {
+        "" => Err(PlutoError::value("count expects a non-empty String")),
+        needle => Ok(Value::Number(as_string(v)?.matches(needle).count() as i64)),
     }
 }
 
 // ------------------------------------------------------
 
-fn number_and_float_to_string(v: &Value, _: Vec<Value>) -> Result<Value, String> {
+fn number_and_float_to_string(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
     match v {
         Value::Number(n) => Ok(Value::String(n.to_string())),
+        Value::BigInt(n) => Ok(Value::String(n.to_string())),
         Value::Float(f) => Ok(Value::String(f.to_string())),
-        _ => Err("Not a number".into()),
+        _ => Err(PlutoError::type_error("Not a number")),
     }
 }
 
-fn number_to_float(v: &Value, _: Vec<Value>) -> Result<Value, String> {
+fn number_to_float(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
     match v {
         Value::Number(n) => Ok(Value::Float(*n as f64)),
-        _ => Err("Not a number".into()),
+        Value::BigInt(n) => Ok(Value::Float(n.to_f64())),
+        _ => Err(PlutoError::type_error("Not a number")),
     }
 }
 
-fn float_to_number(v: &Value, _: Vec<Value>) -> Result<Value, String> {
+fn float_to_number(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
     match v {
         Value::Float(f) => Ok(Value::Number(*f as i64)),
-        _ => Err("Not a float".into()),
+        _ => Err(PlutoError::type_error("Not a float")),
     }
 }
 
 // ------------------------------------------------------
 
-fn array_len(v: &Value, _: Vec<Value>) -> Result<Value, String> {
+fn array_len(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::Array(arr) = v {
         Ok(Value::Number(arr.len() as i64))
     } else {
-        Err("Not an array".into())
+        Err(PlutoError::type_error("Not an array"))
     }
 }
 
-fn array_push(v: &Value, args: Vec<Value>) -> Result<Value, String> {
+fn array_push(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::Array(arr) = v {
         let mut new_arr = arr.clone();
         for arg in args {
@@ -146,39 +294,39 @@ fn array_push(v: &Value, args: Vec<Value>) -> Result<Value, String> {
         }
         Ok(Value::Array(new_arr))
     } else {
-        Err("Not an array".into())
+        Err(PlutoError::type_error("Not an array"))
     }
 }
 
-fn array_pop(v: &Value, _: Vec<Value>) -> Result<Value, String> {
+fn array_pop(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::Array(arr) = v {
         if arr.is_empty() {
-            return Err("Array is empty".into());
+            return Err(PlutoError::index("Array is empty"));
         }
         let mut new_arr = arr.clone();
         new_arr.pop();
         Ok(Value::Array(new_arr))
     } else {
-        Err("Not an array".into())
+        Err(PlutoError::type_error("Not an array"))
     }
 }
 
-fn array_remove(v: &Value, args: Vec<Value>) -> Result<Value, String> {
+fn array_remove(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::Array(arr) = v {
-        if let Some(Value::Number(n)) = args.get(0) {
-            if *n >= 0 && *n < arr.len() as i64 {
-                let mut new_arr = arr.clone();
-                new_arr.remove(*n as usize);
-                return Ok(Value::Array(new_arr));
-            }
+        if let Some(Value::Number(n)) = args.first()
+            && *n >= 0 && *n < arr.len() as i64
+        {
+            let mut new_arr = arr.clone();
+            new_arr.remove(*n as usize);
+            return Ok(Value::Array(new_arr));
         }
-        Err("Index out of bounds".into())
+        Err(PlutoError::index("Index out of bounds"))
     } else {
-        Err("Not an array".into())
+        Err(PlutoError::type_error("Not an array"))
     }
 }
 
-fn array_sum(v: &Value, _: Vec<Value>) -> Result<Value, String> {
+fn array_sum(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::Array(arr) = v {
         let sum: f64 = arr.iter().filter_map(|x| {
             if let Value::Number(n) = x {
@@ -191,136 +339,589 @@ fn array_sum(v: &Value, _: Vec<Value>) -> Result<Value, String> {
         }).sum();
         Ok(Value::Float(sum))
     } else {
-        Err("Not an array".into())
+        Err(PlutoError::type_error("Not an array"))
     }
 }
 
-fn array_map(v: &Value, args: Vec<Value>) -> Result<Value, String> {
+fn array_map(evaluator: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::Array(arr) = v {
-        if args.is_empty() {
-            return Err("No function provided for map".into());
-        }
-        let func = &args[0];
+        let Some(func) = args.first() else {
+            return Err(PlutoError::argument("No function provided for map"));
+        };
         let mut new_arr = Vec::new();
         for item in arr {
-            let result = match func {
-                Value::BuiltInFunction(f) => Ok(f(vec![item.clone()])),
-                Value::UserFunction { params, body, env } => {
-                    if params.len() != 1 {
-                        return Err("User function for map must take exactly one argument".into());
-                    }
-                    let mut func_env = env.clone();
-                    let mut frame = HashMap::<String, (Value, bool)>::new();
-                    frame.insert(params[0].0.clone(), (item.clone(), false));
-                    func_env.push(frame);
-            
-                    let mut evaluator = Evaluator {
-                        env_stack: func_env,
-                    };
-                    match evaluator.eval(body) {
-                        Ok(EvalResult::Value(val)) => Ok(val),
-                        Ok(EvalResult::Return(val)) => Ok(val),
-                        Ok(_) => Err("Unexpected control flow in user function".into()),
-                        Err(e) => Err(e),
-                    }
-                }
-                _ => return Err("First argument must be a function".into()),
-            };
-            new_arr.push(result?);
+            new_arr.push(evaluator.call_value(func, vec![item.clone()])?);
         }
         Ok(Value::Array(new_arr))
     } else {
-        Err("Not an array".into())
+        Err(PlutoError::type_error("Not an array"))
     }
 }
 
+fn array_enumerate(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    if let Value::Array(arr) = v {
+        let pairs = arr.iter()
+            .enumerate()
+            .map(|(i, item)| Value::Array(vec![Value::Number(i as i64), item.clone()]))
+            .collect();
+        Ok(Value::Array(pairs))
+    } else {
+        Err(PlutoError::type_error("Not an array"))
+    }
+}
+
+fn as_array(v: &Value) -> Result<&Vec<Value>, PlutoError> {
+    match v {
+        Value::Array(arr) => Ok(arr),
+        _ => Err(PlutoError::type_error("Not an array")),
+    }
+}
+
+/// The function argument of a higher-order method.
+fn callback<'a>(args: &'a [Value], index: usize, method: &str) -> Result<&'a Value, PlutoError> {
+    match args.get(index) {
+        Some(f @ (Value::UserFunction { .. } | Value::BuiltInFunction(_))) => Ok(f),
+        _ => Err(PlutoError::argument(format!("{} expects a function", method))),
+    }
+}
+
+/// Equality as `==` sees it; values it cannot compare are unequal.
+fn values_equal(evaluator: &Evaluator, a: &Value, b: &Value) -> bool {
+    matches!(evaluator.eval_binary(a.clone(), BinaryOp::Eq, b.clone()), Ok(Value::Bool(true)))
+}
+
+/// Natural ordering for `sort`, `min` and `max`: numbers by value and
+/// strings lexicographically.
+fn compare(a: &Value, b: &Value) -> Result<Ordering, PlutoError> {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => Ok(x.cmp(y)),
+        (Value::String(x), Value::String(y)) => Ok(x.cmp(y)),
+        (Value::Number(_) | Value::BigInt(_), Value::Number(_) | Value::BigInt(_)) => {
+            Ok(BigInt::from_value(a).unwrap().cmp(&BigInt::from_value(b).unwrap()))
+        }
+        (Value::Number(_) | Value::BigInt(_) | Value::Float(_), Value::Number(_) | Value::BigInt(_) | Value::Float(_)) => {
+            let float = |v: &Value| match v {
+                Value::Number(n) => *n as f64,
+                Value::BigInt(n) => n.to_f64(),
+                Value::Float(f) => *f,
+                _ => unreachable!(),
+            };
+            Ok(float(a).total_cmp(&float(b)))
+        }
+        _ => Err(PlutoError::type_error(format!("Cannot compare {} with {}", a.type_name(), b.type_name()))),
+    }
+}
+
+/// A stable sort of `items` by `keys`, failing on keys that cannot be compared.
+fn sort_by_keys(items: &[Value], keys: Vec<Value>) -> Result<Value, PlutoError> {
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    let mut error = None;
+    order.sort_by(|&i, &j| {
+        compare(&keys[i], &keys[j]).unwrap_or_else(|e| {
+            error.get_or_insert(e);
+            Ordering::Equal
+        })
+    });
+    match error {
+        Some(error) => Err(error),
+        None => Ok(Value::Array(order.into_iter().map(|i| items[i].clone()).collect())),
+    }
+}
+
+/// An index argument; negative values count from the end, as in `slice(-2)`.
+fn array_index(args: &[Value], index: usize, len: usize, default: usize, method: &str) -> Result<usize, PlutoError> {
+    match args.get(index) {
+        None | Some(Value::Null) => Ok(default),
+        Some(Value::Number(n)) if *n < 0 => Ok(len.saturating_sub(n.unsigned_abs() as usize)),
+        Some(Value::Number(n)) => Ok((*n as usize).min(len)),
+        _ => Err(PlutoError::argument(format!("{} expects Number indices", method))),
+    }
+}
+
+fn array_filter(evaluator: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let f = callback(&args, 0, "filter")?;
+    let mut kept = Vec::new();
+    for item in as_array(v)? {
+        let keep = evaluator.call_value(f, vec![item.clone()])?;
+        if evaluator.is_truthy(&keep) {
+            kept.push(item.clone());
+        }
+    }
+    Ok(Value::Array(kept))
+}
+
+fn array_reduce(evaluator: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let f = callback(&args, 0, "reduce")?;
+    let mut items = as_array(v)?.iter();
+    let Some(mut acc) = items.next().cloned() else {
+        return Ok(Value::Null);
+    };
+    for item in items {
+        acc = evaluator.call_value(f, vec![acc, item.clone()])?;
+    }
+    Ok(acc)
+}
+
+fn array_fold(evaluator: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let f = callback(&args, 1, "fold")?;
+    let mut acc = args[0].clone();
+    for item in as_array(v)? {
+        acc = evaluator.call_value(f, vec![acc, item.clone()])?;
+    }
+    Ok(acc)
+}
+
+fn array_find(evaluator: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let f = callback(&args, 0, "find")?;
+    for item in as_array(v)? {
+        let found = evaluator.call_value(f, vec![item.clone()])?;
+        if evaluator.is_truthy(&found) {
+            return Ok(item.clone());
+        }
+    }
+    Ok(Value::Null)
+}
+
+fn array_find_index(evaluator: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let f = callback(&args, 0, "find_index")?;
+    for (i, item) in as_array(v)?.iter().enumerate() {
+        let found = evaluator.call_value(f, vec![item.clone()])?;
+        if evaluator.is_truthy(&found) {
+            return Ok(Value::Number(i as i64));
+        }
+    }
+    Ok(Value::Number(-1))
+}
+
+fn array_any(evaluator: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let f = callback(&args, 0, "any")?;
+    for item in as_array(v)? {
+        let result = evaluator.call_value(f, vec![item.clone()])?;
+        if evaluator.is_truthy(&result) {
+            return Ok(Value::Bool(true));
+        }
+    }
+    Ok(Value::Bool(false))
+}
+
+fn array_all(evaluator: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let f = callback(&args, 0, "all")?;
+    for item in as_array(v)? {
+        let result = evaluator.call_value(f, vec![item.clone()])?;
+        if !evaluator.is_truthy(&result) {
+            return Ok(Value::Bool(false));
+        }
+    }
+    Ok(Value::Bool(true))
+}
+
+fn array_sort(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    let arr = as_array(v)?;
+    sort_by_keys(arr, arr.clone())
+}
+
+fn array_sort_by(evaluator: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let f = callback(&args, 0, "sort_by")?;
+    let arr = as_array(v)?;
+    let mut keys = Vec::with_capacity(arr.len());
+    for item in arr {
+        keys.push(evaluator.call_value(f, vec![item.clone()])?);
+    }
+    sort_by_keys(arr, keys)
+}
+
+fn array_reverse(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::Array(as_array(v)?.iter().rev().cloned().collect()))
+}
+
+fn array_slice(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let arr = as_array(v)?;
+    let start = array_index(&args, 0, arr.len(), 0, "slice")?;
+    let end = array_index(&args, 1, arr.len(), arr.len(), "slice")?;
+    Ok(Value::Array(arr[start..end.max(start)].to_vec()))
+}
+
+fn array_concat(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let mut result = as_array(v)?.clone();
+    for arg in args {
+        match arg {
+            Value::Array(items) => result.extend(items),
+            other => return Err(PlutoError::argument(format!("concat expects arrays, got {}", other.type_name()))),
+        }
+    }
+    Ok(Value::Array(result))
+}
+
+fn array_join(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let separator = match args.first() {
+        None => "",
+        Some(Value::String(s)) => s,
+        Some(_) => return Err(PlutoError::argument("join expects a String separator")),
+    };
+    let parts: Vec<String> = as_array(v)?.iter().map(|item| item.to_string()).collect();
+    Ok(Value::String(parts.join(separator)))
+}
+
+fn array_flat_map(evaluator: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let f = callback(&args, 0, "flat_map")?;
+    let mut result = Vec::new();
+    for item in as_array(v)? {
+        match evaluator.call_value(f, vec![item.clone()])? {
+            Value::Array(items) => result.extend(items),
+            other => result.push(other),
+        }
+    }
+    Ok(Value::Array(result))
+}
+
+fn array_zip(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let Some(Value::Array(other)) = args.first() else {
+        return Err(PlutoError::argument("zip expects an array"));
+    };
+    let pairs = as_array(v)?
+        .iter()
+        .zip(other)
+        .map(|(a, b)| Value::Array(vec![a.clone(), b.clone()]))
+        .collect();
+    Ok(Value::Array(pairs))
+}
+
+fn array_unique(evaluator: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    let mut unique: Vec<Value> = Vec::new();
+    for item in as_array(v)? {
+        if !unique.iter().any(|seen| values_equal(evaluator, seen, item)) {
+            unique.push(item.clone());
+        }
+    }
+    Ok(Value::Array(unique))
+}
+
+fn array_chunk(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let size = match args.first() {
+        Some(Value::Number(n)) if *n > 0 => *n as usize,
+        Some(Value::Number(_)) => return Err(PlutoError::value("chunk size must be positive")),
+        _ => return Err(PlutoError::argument("chunk expects a Number size")),
+    };
+    let chunks = as_array(v)?.chunks(size).map(|chunk| Value::Array(chunk.to_vec())).collect();
+    Ok(Value::Array(chunks))
+}
+
+fn array_index_of(evaluator: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let target = args.first().cloned().unwrap_or(Value::Null);
+    let index = as_array(v)?.iter().position(|item| values_equal(evaluator, item, &target));
+    Ok(Value::Number(index.map_or(-1, |i| i as i64)))
+}
+
+fn array_contains(evaluator: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let target = args.first().cloned().unwrap_or(Value::Null);
+    Ok(Value::Bool(as_array(v)?.iter().any(|item| values_equal(evaluator, item, &target))))
+}
+
+/// The first item no other item orders `wanted` of, or null for an empty array.
+fn extreme(v: &Value, wanted: Ordering) -> Result<Value, PlutoError> {
+    let mut items = as_array(v)?.iter();
+    let Some(mut best) = items.next() else {
+        return Ok(Value::Null);
+    };
+    for item in items {
+        if compare(item, best)? == wanted {
+            best = item;
+        }
+    }
+    Ok(best.clone())
+}
+
+fn array_min(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    extreme(v, Ordering::Less)
+}
+
+fn array_max(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    extreme(v, Ordering::Greater)
+}
+
+fn array_first(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(as_array(v)?.first().cloned().unwrap_or(Value::Null))
+}
+
+fn array_last(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(as_array(v)?.last().cloned().unwrap_or(Value::Null))
+}
+
 // ------------------------------------------------------
 
-fn hashmap_len(v: &Value, _: Vec<Value>) -> Result<Value, String> {
+fn hashmap_len(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::HashMapV(map) = v {
         Ok(Value::Number(map.len() as i64))
     } else {
-        Err("Not a hashmap".into())
+        Err(PlutoError::type_error("Not a hashmap"))
     }
 }
 
-fn hashmap_get(v: &Value, args: Vec<Value>) -> Result<Value, String> {
+fn hashmap_get(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::HashMapV(map) = v {
-        if let Some(Value::String(key)) = args.get(0) {
-            if let Some(value) = map.get(key) {
-                return Ok(value.clone());
-            }
+        if let Some(Value::String(key)) = args.first()
+            && let Some(value) = map.get(key)
+        {
+            return Ok(value.clone());
         }
-        Err("Key not found".into())
+        Err(PlutoError::key("Key not found"))
     } else {
-        Err("Not a hashmap".into())
+        Err(PlutoError::type_error("Not a hashmap"))
     }
 }
 
-fn hashmap_set(v: &Value, args: Vec<Value>) -> Result<Value, String> {
+fn hashmap_set(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
     if let Value::HashMapV(map) = v {
         if args.len() < 2 {
-            return Err("Not enough arguments".into());
+            return Err(PlutoError::argument("Not enough arguments"));
         }
-        if let Some(Value::String(key)) = args.get(0) {
+        if let Some(Value::String(key)) = args.first() {
             let value = args[1].clone();
             let mut new_map = map.clone();
             new_map.insert(key.clone(), value);
             return Ok(Value::HashMapV(new_map));
         }
-        Err("First argument must be a string key".into())
+        Err(PlutoError::type_error("First argument must be a string key"))
     } else {
-        Err("Not a hashmap".into())
+        Err(PlutoError::type_error("Not a hashmap"))
+    }
+}
+
+fn as_map(v: &Value) -> Result<&OrderedMap, PlutoError> {
+    match v {
+        Value::HashMapV(map) => Ok(map),
+        _ => Err(PlutoError::type_error("Not a hashmap")),
+    }
+}
+
+fn key_arg<'a>(args: &'a [Value], method: &str) -> Result<&'a str, PlutoError> {
+    match args.first() {
+        Some(Value::String(key)) => Ok(key),
+        _ => Err(PlutoError::argument(format!("{} expects a String key", method))),
+    }
+}
+
+fn hashmap_keys(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::Array(as_map(v)?.keys().map(|k| Value::String(k.clone())).collect()))
+}
+
+fn hashmap_values(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::Array(as_map(v)?.values().cloned().collect()))
+}
+
+fn hashmap_entries(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    let entries = as_map(v)?
+        .iter()
+        .map(|(k, v)| Value::Array(vec![Value::String(k.clone()), v.clone()]))
+        .collect();
+    Ok(Value::Array(entries))
+}
+
+fn hashmap_has(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::Bool(as_map(v)?.contains_key(key_arg(&args, "has")?)))
+}
+
+fn hashmap_get_or(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let key = key_arg(&args, "get_or")?;
+    let default = args.get(1).cloned().unwrap_or(Value::Null);
+    Ok(as_map(v)?.get(key).cloned().unwrap_or(default))
+}
+
+fn hashmap_delete(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let mut map = as_map(v)?.clone();
+    map.remove(key_arg(&args, "delete")?);
+    Ok(Value::HashMapV(map))
+}
+
+fn hashmap_merge(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let Some(Value::HashMapV(other)) = args.first() else {
+        return Err(PlutoError::argument("merge expects a hashmap"));
+    };
+    let mut map = as_map(v)?.clone();
+    for (key, value) in other.iter() {
+        map.insert(key.clone(), value.clone());
+    }
+    Ok(Value::HashMapV(map))
+}
+
+fn hashmap_map_values(evaluator: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let f = callback(&args, 0, "map_values")?;
+    let mut mapped = OrderedMap::new();
+    for (key, value) in as_map(v)?.iter() {
+        mapped.insert(key.clone(), evaluator.call_value(f, vec![value.clone()])?);
+    }
+    Ok(Value::HashMapV(mapped))
+}
+
+fn hashmap_filter(evaluator: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let f = callback(&args, 0, "filter")?;
+    let mut kept = OrderedMap::new();
+    for (key, value) in as_map(v)?.iter() {
+        let keep = evaluator.call_value(f, vec![Value::String(key.clone()), value.clone()])?;
+        if evaluator.is_truthy(&keep) {
+            kept.insert(key.clone(), value.clone());
+        }
+    }
+    Ok(Value::HashMapV(kept))
+}
+
+// ------------------------------------------------------
+
+fn as_range(v: &Value) -> Result<IntRange, PlutoError> {
+    IntRange::from_value(v).ok_or_else(|| PlutoError::type_error("Not a range"))
+}
+
+fn range_len(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(BigInt::from_i128(as_range(v)?.len()).to_value())
+}
+
+fn range_to_array(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(Value::Array(as_range(v)?.iter().map(Value::Number).collect()))
+}
+
+fn range_step(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let range = as_range(v)?;
+    match args.first() {
+        Some(Value::Number(0)) => Err(PlutoError::value("Range step cannot be zero")),
+        Some(Value::Number(n)) => Ok(IntRange { step: *n, ..range }.to_value()),
+        _ => Err(PlutoError::argument("step expects a Number")),
+    }
+}
+
+fn range_rev(_: &mut Evaluator, v: &Value, _: Vec<Value>) -> Result<Value, PlutoError> {
+    Ok(as_range(v)?.rev().to_value())
+}
+
+fn range_contains(_: &mut Evaluator, v: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+    let range = as_range(v)?;
+    match args.first() {
+        Some(Value::Number(n)) => Ok(Value::Bool(range.contains(*n))),
+        _ => Ok(Value::Bool(false)),
     }
 }
 
 // ------------------------------------------------------
 
-pub fn string_methods() -> HashMap<&'static str, MethodFn> {
-    let mut map = HashMap::new();
-    map.insert("len", string_len as MethodFn);
-    map.insert("to_int", string_to_number as MethodFn);
-    map.insert("to_float", string_to_float as MethodFn);
-    map.insert("to_upper", string_to_uppercase as MethodFn);
-    map.insert("to_lower", string_to_lowercase as MethodFn);
-    map.insert("char_at", string_char_at as MethodFn);
-    map.insert("concat", string_concat as MethodFn);
-    map.insert("is_number", string_is_a_number as MethodFn);
-    map.insert("is_float", string_is_a_float as MethodFn);
-    map
-}
-
-pub fn number_methods() -> HashMap<&'static str, MethodFn> {
-    let mut map = HashMap::new();
-    map.insert("to_string", number_and_float_to_string as MethodFn);
-    map.insert("to_float", number_to_float as MethodFn);
-    map
-}
-
-pub fn float_methods() -> HashMap<&'static str, MethodFn> {
-    let mut map = HashMap::new();
-    map.insert("to_string", number_and_float_to_string as MethodFn);
-    map.insert("to_int", float_to_number as MethodFn);
-    map
-}
-
-pub fn array_methods() -> HashMap<&'static str, MethodFn> {
-    let mut map = HashMap::new();
-    map.insert("len", array_len as MethodFn);
-    map.insert("push", array_push as MethodFn);
-    map.insert("pop", array_pop as MethodFn);
-    map.insert("remove", array_remove as MethodFn);
-    map.insert("sum", array_sum as MethodFn);
-    map.insert("map", array_map as MethodFn);
-    map
-}
-
-pub fn hashmap_methods() -> HashMap<&'static str, MethodFn> {
-    let mut map = HashMap::new();
-    map.insert("len", hashmap_len as MethodFn);
-    map.insert("get", hashmap_get as MethodFn);
-    map.insert("set", hashmap_set as MethodFn);
-    map
+/// The `String` method called `name`, if there is one.
+pub fn string_method(name: &str) -> Option<MethodFn> {
+    Some(match name {
+        "len" => string_len,
+        "to_int" => string_to_number,
+        "to_float" => string_to_float,
+        "to_upper" => string_to_uppercase,
+        "to_lower" => string_to_lowercase,
+        "char_at" => string_char_at,
+        "concat" => string_concat,
+        "is_number" => string_is_a_number,
+        "is_float" => string_is_a_float,
+        "split" => string_split,
+        "trim" => string_trim,
+        "trim_start" => string_trim_start,
+        "trim_end" => string_trim_end,
+        "replace" => string_replace,
+        "contains" => string_contains,
+        "starts_with" => string_starts_with,
+        "ends_with" => string_ends_with,
+        "index_of" => string_index_of,
+        "substring" => string_substring,
+        "repeat" => string_repeat,
+        "lines" => string_lines,
+        "chars" => string_chars,
+        "bytes" => string_bytes,
+        "graphemes" => string_graphemes,
+        "pad_start" => string_pad_start,
+        "pad_end" => string_pad_end,
+        "reverse" => string_reverse,
+        "is_empty" => string_is_empty,
+        "count" => string_count,
+        _ => return None,
+    })
+}
+
+/// The `Number` method called `name`, if there is one.
+pub fn number_method(name: &str) -> Option<MethodFn> {
+    Some(match name {
+        "to_string" => number_and_float_to_string,
+        "to_float" => number_to_float,
+        _ => return None,
+    })
+}
+
+/// The `Float` method called `name`, if there is one.
+pub fn float_method(name: &str) -> Option<MethodFn> {
+    Some(match name {
+        "to_string" => number_and_float_to_string,
+        "to_int" => float_to_number,
+        _ => return None,
+    })
+}
+
+/// The `Array` method called `name`, if there is one.
+pub fn array_method(name: &str) -> Option<MethodFn> {
+    Some(match name {
+        "len" => array_len,
+        "push" => array_push,
+        "pop" => array_pop,
+        "remove" => array_remove,
+        "sum" => array_sum,
+        "map" => array_map,
+        "enumerate" => array_enumerate,
+        "filter" => array_filter,
+        "reduce" => array_reduce,
+        "fold" => array_fold,
+        "find" => array_find,
+        "find_index" => array_find_index,
+        "any" => array_any,
+        "all" => array_all,
+        "sort" => array_sort,
+        "sort_by" => array_sort_by,
+        "reverse" => array_reverse,
+        "slice" => array_slice,
+        "concat" => array_concat,
+        "join" => array_join,
+        "flat_map" => array_flat_map,
+        "zip" => array_zip,
+        "unique" => array_unique,
+        "chunk" => array_chunk,
+        "index_of" => array_index_of,
+        "contains" => array_contains,
+        "min" => array_min,
+        "max" => array_max,
+        "first" => array_first,
+        "last" => array_last,
+        _ => return None,
+    })
+}
+
+/// The `Range` method called `name`, if there is one.
+pub fn range_method(name: &str) -> Option<MethodFn> {
+    Some(match name {
+        "len" => range_len,
+        "to_array" => range_to_array,
+        "step" => range_step,
+        "rev" => range_rev,
+        "contains" => range_contains,
+        _ => return None,
+    })
+}
+
+/// The `HashMap` method called `name`, if there is one.
+pub fn hashmap_method(name: &str) -> Option<MethodFn> {
+    Some(match name {
+        "len" => hashmap_len,
+        "get" => hashmap_get,
+        "set" => hashmap_set,
+        "keys" => hashmap_keys,
+        "values" => hashmap_values,
+        "entries" => hashmap_entries,
+        "has" => hashmap_has,
+        "get_or" => hashmap_get_or,
+        "delete" => hashmap_delete,
+        "merge" => hashmap_merge,
+        "map_values" => hashmap_map_values,
+        "filter" => hashmap_filter,
+        _ => return None,
+    })
 }
 
 // Xoshiro256** PRNG implementation
@@ -333,12 +934,12 @@ impl Xoshiro256StarStar {
         let mut state = [0u64; 4];
         let mut splitmix = seed;
         
-        for i in 0..4 {
+        for word in state.iter_mut() {
             splitmix = splitmix.wrapping_add(0x9e3779b97f4a7c15);
             let mut z = splitmix;
             z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
             z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
-            state[i] = z ^ (z >> 31);
+            *word = z ^ (z >> 31);
         }
         
         Self { s: state }
@@ -364,11 +965,19 @@ impl Xoshiro256StarStar {
     }
     
     fn next_range(&mut self, min: i64, max: i64) -> i64 {
-        let range = (max - min + 1) as u64;
-        min + (self.next() % range) as i64
+        match u64::try_from(max as i128 - min as i128 + 1) {
+            Ok(range) => min.wrapping_add((self.next() % range) as i64),
+            // the full i64 range: every output is already in bounds
+            Err(_) => self.next() as i64,
+        }
     }
 }
 
+/// A builtin module whose members cannot be reassigned.
+fn module(members: HashMap<String, Value>) -> Value {
+    Value::Module(Rc::new(RefCell::new(members.into_iter().map(|(name, value)| (name, (value, true))).collect())))
+}
+
 pub fn default_env() -> HashMap<String, (Value, bool)> {
     let mut env = HashMap::new();
 
@@ -380,163 +989,163 @@ pub fn default_env() -> HashMap<String, (Value, bool)> {
 
     math.insert("pi".to_string(), Value::Float(std::f64::consts::PI));
 
-    math.insert("pow".to_string(), Value::BuiltInFunction(|args| {
-        let a = match args.get(0) {
+    math.insert("pow".to_string(), native(|evaluator, args| {
+        let a = match args.first() {
             Some(Value::Float(f)) => Value::Float(*f),
             Some(Value::Number(n)) => Value::Number(*n),
-            _ => return Value::Number(0),
+            Some(Value::BigInt(n)) => Value::BigInt(n.clone()),
+            _ => return Err(PlutoError::type_error("Math.pow expects a number as base")),
         };
         let b = match args.get(1) {
             Some(Value::Float(f)) => Value::Float(*f),
             Some(Value::Number(n)) => Value::Number(*n),
-            _ => return Value::Number(0),
+            _ => return Err(PlutoError::type_error("Math.pow expects a number as exponent")),
         };
         match (a, b) {
-            (Value::Float(x), Value::Float(y)) => Value::Float(x.powf(y)),
-            (Value::Float(x), Value::Number(y)) => Value::Float(x.powf(y as f64)),
-            (Value::Number(x), Value::Float(y)) => Value::Float((x as f64).powf(y)),
-            (Value::Number(x), Value::Number(y)) => {
-                if y >= 0 {
-                    Value::Number(x.pow(y as u32))
+            (Value::Float(x), Value::Float(y)) => Ok(Value::Float(x.powf(y))),
+            (Value::Float(x), Value::Number(y)) => Ok(Value::Float(x.powf(y as f64))),
+            (Value::Number(x), Value::Float(y)) => Ok(Value::Float((x as f64).powf(y))),
+            (Value::BigInt(x), Value::Float(y)) => Ok(Value::Float(x.to_f64().powf(y))),
+            (x, Value::Number(y)) => {
+                let x = BigInt::from_value(&x).unwrap();
+                if let Ok(y) = u32::try_from(y) {
+                    let checked = x.to_i64().and_then(|x| x.checked_pow(y));
+                    evaluator.integer_result(checked, || x.pow(y))
+                } else if y >= 0 {
+                    Err(PlutoError::value("Math.pow exponent is too large"))
                 } else {
-                    Value::Float((x as f64).powf(y as f64))
+                    Ok(Value::Float(x.to_f64().powf(y as f64)))
                 }
             },
-            _ => Value::Number(0),
+            _ => unreachable!(),
         }
     }));
 
-    math.insert("sqrt".to_string(), Value::BuiltInFunction(|args| {
-        let a = match args.get(0) {
-            Some(Value::Float(f)) => Value::Float(f.sqrt()),
-            Some(Value::Number(n)) => Value::Number((*n as f64).sqrt() as i64),
-            _ => return Value::Number(0),
-        };
-        a
+    math.insert("sqrt".to_string(), native(|_, args| {
+        match args.first() {
+            Some(Value::Float(f)) if *f < 0.0 => Err(PlutoError::value("Math.sqrt of a negative number")),
+            Some(Value::Number(n)) if *n < 0 => Err(PlutoError::value("Math.sqrt of a negative number")),
+            Some(Value::Float(f)) => Ok(Value::Float(f.sqrt())),
+            Some(Value::Number(n)) => Ok(Value::Number((*n as f64).sqrt() as i64)),
+            _ => Err(PlutoError::type_error("Math.sqrt expects a number")),
+        }
     }));
 
-    env.insert("Math".to_string(), (Value::Module(math), true));
+    env.insert("Math".to_string(), (module(math), true));
 
     // -----------------------------------------------------
 
     let mut time = HashMap::new();
 
-    time.insert("now".to_string(), Value::BuiltInFunction(|_args| {
+    time.insert("now".to_string(), native(|_, _args| {
         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-        Value::Number(now.as_secs() as i64)
+        Ok(Value::Number(now.as_secs() as i64))
     }));
 
-    time.insert("now_ms".to_string(), Value::BuiltInFunction(|_args| {
+    time.insert("now_ms".to_string(), native(|_, _args| {
         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-        Value::Number(now.as_millis() as i64)
+        Ok(Value::Number(now.as_millis() as i64))
     }));
 
-    time.insert("sleep".to_string(), Value::BuiltInFunction(|args| {
-        if let Some(Value::Number(ms)) = args.get(0) {
-            thread::sleep(std::time::Duration::from_millis(*ms as u64));
+    time.insert("sleep".to_string(), native(|_, args| {
+        match args.first() {
+            Some(Value::Number(ms)) => match u64::try_from(*ms) {
+                Ok(ms) => thread::sleep(std::time::Duration::from_millis(ms)),
+                Err(_) => return Err(PlutoError::value("Time.sleep expects a non-negative duration")),
+            },
+            _ => return Err(PlutoError::type_error("Time.sleep expects a Number of milliseconds")),
         }
-        Value::Number(0)
+        Ok(Value::Number(0))
     }));
 
-    env.insert("Time".to_string(), (Value::Module(time), true));
+    env.insert("Time".to_string(), (module(time), true));
 
     let mut random = HashMap::new();
 
-    random.insert("int".to_string(), Value::BuiltInFunction(|args| {
-        let seed = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos() as u64;
-        
-        let min = match args.get(0) {
+    // one generator shared by every Random function, seeded once per environment
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64;
+    let shared_rng = Rc::new(RefCell::new(Xoshiro256StarStar::new(seed)));
+
+    let rng = Rc::clone(&shared_rng);
+    random.insert("int".to_string(), native(move |_, args| {
+        let min = match args.first() {
             Some(Value::Number(n)) => *n,
-            _ => 0,
+            None => 0,
+            Some(_) => return Err(PlutoError::type_error("Random.int expects Number bounds")),
         };
-        
+
         let max = match args.get(1) {
             Some(Value::Number(n)) => *n,
-            _ => 100,
+            None => 100,
+            Some(_) => return Err(PlutoError::type_error("Random.int expects Number bounds")),
         };
 
         if min > max {
-            return Value::Number(min);
+            return Err(PlutoError::value(format!("Random.int bounds are reversed: {} > {}", min, max)));
         }
 
-        let mut rng = Xoshiro256StarStar::new(seed);
-        Value::Number(rng.next_range(min, max))
+        let mut rng = rng.borrow_mut();
+        Ok(Value::Number(rng.next_range(min, max)))
     }));
 
-    random.insert("float".to_string(), Value::BuiltInFunction(|_args| {
-        let seed = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos() as u64;
-        
-        let mut rng = Xoshiro256StarStar::new(seed);
-        Value::Float(rng.next_f64())
+    let rng = Rc::clone(&shared_rng);
+    random.insert("float".to_string(), native(move |_, _args| {
+        let mut rng = rng.borrow_mut();
+        Ok(Value::Float(rng.next_f64()))
     }));
 
-    random.insert("bool".to_string(), Value::BuiltInFunction(|args| {
-        let probability = match args.get(0) {
+    let rng = Rc::clone(&shared_rng);
+    random.insert("bool".to_string(), native(move |_, args| {
+        let probability = match args.first() {
             Some(Value::Float(f)) => *f,
             Some(Value::Number(n)) => *n as f64,
-            _ => 0.5,
+            None => 0.5,
+            Some(_) => return Err(PlutoError::type_error("Random.bool expects a Number probability")),
         };
 
-        let seed = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos() as u64;
-        
-        let mut rng = Xoshiro256StarStar::new(seed);
-        Value::Bool(rng.next_f64() < probability)
-    }));
+        if !(0.0..=1.0).contains(&probability) {
+            return Err(PlutoError::value("Random.bool expects a probability between 0 and 1"));
+        }
 
-    random.insert("choice".to_string(), Value::BuiltInFunction(|args| {
-        if let Some(Value::Array(arr)) = args.get(0) {
-            if arr.is_empty() {
-                return Value::Null;
-            }
+        let mut rng = rng.borrow_mut();
+        Ok(Value::Bool(rng.next_f64() < probability))
+    }));
 
-            let seed = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_nanos() as u64;
-            
-            let mut rng = Xoshiro256StarStar::new(seed);
-            let idx = (rng.next() as usize) % arr.len();
-            arr[idx].clone()
-        } else {
-            Value::Null
+    let rng = Rc::clone(&shared_rng);
+    random.insert("choice".to_string(), native(move |_, args| {
+        let Some(Value::Array(arr)) = args.first() else {
+            return Err(PlutoError::type_error("Random.choice expects an Array"));
+        };
+        if arr.is_empty() {
+            return Err(PlutoError::value("Random.choice of an empty array"));
         }
+
+        let mut rng = rng.borrow_mut();
+        let idx = (rng.next() as usize) % arr.len();
+        Ok(arr[idx].clone())
     }));
 
-    random.insert("shuffle".to_string(), Value::BuiltInFunction(|args| {
-        if let Some(Value::Array(arr)) = args.get(0) {
-            if arr.is_empty() {
-                return Value::Array(vec![]);
-            }
+    let rng = Rc::clone(&shared_rng);
+    random.insert("shuffle".to_string(), native(move |_, args| {
+        let Some(Value::Array(arr)) = args.first() else {
+            return Err(PlutoError::type_error("Random.shuffle expects an Array"));
+        };
 
-            let mut new_arr = arr.clone();
-            let seed = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_nanos() as u64;
-            
-            let mut rng = Xoshiro256StarStar::new(seed);
-
-            for i in (1..new_arr.len()).rev() {
-                let j = (rng.next() as usize) % (i + 1);
-                new_arr.swap(i, j);
-            }
+        let mut new_arr = arr.clone();
+        let mut rng = rng.borrow_mut();
 
-            Value::Array(new_arr)
-        } else {
-            Value::Array(vec![])
+        for i in (1..new_arr.len()).rev() {
+            let j = (rng.next() as usize) % (i + 1);
+            new_arr.swap(i, j);
         }
+
+        Ok(Value::Array(new_arr))
     }));
 
-    env.insert("Random".to_string(), (Value::Module(random), true));
+    env.insert("Random".to_string(), (module(random), true));
 
     // -----------------------------------------------------
     // -------------------- GENERAL ------------------------
@@ -545,22 +1154,20 @@ pub fn default_env() -> HashMap<String, (Value, bool)> {
     env.insert(
         "print".to_string(),
         (
-            Value::BuiltInFunction(|args| {
+            native(|_, args| {
                 let mut end = "\n";
                 let mut values = Vec::new();
                 
                 let mut i = 0;
                 while i < args.len() {
-                    if i + 1 < args.len() {
-                        if let Value::String(s) = &args[i] {
-                            if s == "end" {
-                                if let Value::String(e) = &args[i + 1] {
-                                    end = e;
-                                    i += 2;
-                                    continue;
-                                }
-                            }
-                        }
+                    if i + 1 < args.len()
+                        && let Value::String(s) = &args[i]
+                        && s == "end"
+                        && let Value::String(e) = &args[i + 1]
+                    {
+                        end = e;
+                        i += 2;
+                        continue;
                     }
                     values.push(&args[i]);
                     i += 1;
@@ -570,11 +1177,11 @@ pub fn default_env() -> HashMap<String, (Value, bool)> {
                     if i > 0 {
                         print!(" ");
                     }
-                    print!("{}", value.to_string());
+                    print!("{}", value);
                 }
                 print!("{}", end);
                 let _ = std::io::stdout().flush();
-                Value::Null
+                Ok(Value::Null)
             }),
             true,
         ),
@@ -583,22 +1190,20 @@ pub fn default_env() -> HashMap<String, (Value, bool)> {
     env.insert(
         "print_raw".to_string(),
         (
-            Value::BuiltInFunction(|args| {
+            native(|_, args| {
                 let mut end = "\n";
                 let mut values = Vec::new();
                 
                 let mut i = 0;
                 while i < args.len() {
-                    if i + 1 < args.len() {
-                        if let Value::String(s) = &args[i] {
-                            if s == "end" {
-                                if let Value::String(e) = &args[i + 1] {
-                                    end = e;
-                                    i += 2;
-                                    continue;
-                                }
-                            }
-                        }
+                    if i + 1 < args.len()
+                        && let Value::String(s) = &args[i]
+                        && s == "end"
+                        && let Value::String(e) = &args[i + 1]
+                    {
+                        end = e;
+                        i += 2;
+                        continue;
                     }
                     values.push(&args[i]);
                     i += 1;
@@ -612,7 +1217,7 @@ pub fn default_env() -> HashMap<String, (Value, bool)> {
                 }
                 print!("{}", end);
                 let _ = std::io::stdout().flush();
-                Value::Null
+                Ok(Value::Null)
             }),
             true,
         ),
@@ -621,23 +1226,20 @@ pub fn default_env() -> HashMap<String, (Value, bool)> {
     env.insert(
         "type".to_string(),
         (
-            Value::BuiltInFunction(|args| {
-                if let Some(arg) = args.get(0) {
-                    match arg {
-                        Value::Null => Value::String("Null".to_string()),
-                        Value::Bool(_) => Value::String("Bool".to_string()),
-                        Value::Number(_) => Value::String("Number".to_string()),
-                        Value::Float(_) => Value::String("Float".to_string()),
-                        Value::String(_) => Value::String("String".to_string()),
-                        Value::Array(_) => Value::String("Array".to_string()),
-                        Value::HashMapV(_) => Value::String("HashMap".to_string()),
-                        Value::Module(_) => Value::String("Module".to_string()),
-                        Value::BuiltInFunction(_) => Value::String("BuiltInFunction".to_string()),
-                        Value::UserFunction { .. } => Value::String("UserFunction".to_string()),
-                    }
-                } else {
-                    Value::String("UNKNOWN".to_string())
-                }
+            native(|_, args| {
+                let name = args.first().map_or("UNKNOWN", Value::type_name);
+                Ok(Value::String(name.to_string()))
+            }),
+            true,
+        ),
+    );
+
+    env.insert(
+        "doc".to_string(),
+        (
+            native(|_, args| match args.first() {
+                Some(Value::UserFunction { doc: Some(doc), .. }) => Ok(Value::String(doc.to_string())),
+                _ => Ok(Value::Null),
             }),
             true,
         ),
@@ -646,27 +1248,16 @@ pub fn default_env() -> HashMap<String, (Value, bool)> {
     env.insert(
         "input".to_string(), 
         (
-            Value::BuiltInFunction(|args| {
-                let r;
-                if let Some(Value::String(prompt)) = args.get(0) {
-                    let mut input = String::new();
+            native(|_, args| {
+                if let Some(Value::String(prompt)) = args.first() {
                     print!("{}", prompt);
-                    let _ = std::io::stdout().flush();
-                    std::io::stdin()
-                        .read_line(&mut input)
-                        .expect("Failed to read line");
-                
-                    r = input.trim().to_string();
-                } else {
-                    let mut input = String::new();
-                    let _ = std::io::stdout().flush();
-                    std::io::stdin()
-                        .read_line(&mut input)
-                        .expect("Failed to read line");
-                
-                    r = input.trim().to_string();
                 }
-                Value::String(r)
+                let _ = std::io::stdout().flush();
+                let mut input = String::new();
+                std::io::stdin()
+                    .read_line(&mut input)
+                    .map_err(|e| PlutoError::io(format!("Failed to read line: {}", e)))?;
+                Ok(Value::String(input.trim().to_string()))
             }),
             true
         )
@@ -675,8 +1266,8 @@ pub fn default_env() -> HashMap<String, (Value, bool)> {
     env.insert(
         "exit".to_string(),
         (
-            Value::BuiltInFunction(|args| {
-                if let Some(Value::Number(n)) = args.get(0) {
+            native(|_, args| {
+                if let Some(Value::Number(n)) = args.first() {
                     std::process::exit(*n as i32);
                 } else {
                     std::process::exit(0);
@@ -689,13 +1280,13 @@ pub fn default_env() -> HashMap<String, (Value, bool)> {
     env.insert(
         "format".to_string(),
         (
-            Value::BuiltInFunction(|args| {
+            native(|_, args| {
                 if args.is_empty() {
-                    return Value::String("".to_string());
+                    return Ok(Value::String("".to_string()));
                 }
                 let template = match &args[0] {
                     Value::String(s) => s,
-                    _ => return Value::String("".to_string()),
+                    _ => return Err(PlutoError::type_error("format expects a template string")),
                 };
 
                 let mut result = String::new();
@@ -703,22 +1294,21 @@ pub fn default_env() -> HashMap<String, (Value, bool)> {
                 let mut chars = template.chars().peekable();
 
                 while let Some(c) = chars.next() {
-                    if c == '{' {
-                        if let Some(&next) = chars.peek() {
-                            if next == '}' {
-                                chars.next(); // '}'
-                                if let Some(val) = arg_iter.next() {
-                                    result.push_str(&val.to_string());
-                                } else {
-                                    result.push_str("{}");
-                                }
-                                continue;
-                            }
+                    if c == '{'
+                        && let Some(&next) = chars.peek()
+                        && next == '}'
+                    {
+                        chars.next(); // '}'
+                        if let Some(val) = arg_iter.next() {
+                            result.push_str(&val.to_string());
+                        } else {
+                            result.push_str("{}");
                         }
+                        continue;
                     }
                     result.push(c);
                 }
-                Value::String(result)
+                Ok(Value::String(result))
             }),
             true,
         )
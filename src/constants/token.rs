@@ -5,6 +5,11 @@ pub enum TokenKind {
     Number(i64),
     Float(f64),
     StringLiteral(String),
+    /// A string literal containing `${}`.
+    InterpolatedString(Vec<StringPart>),
+    /// The text of a `///` or `/** */` comment. The parser attaches it to the
+    /// declaration that follows and drops it everywhere else.
+    DocComment(String),
     Null,
     Dot,
     DotDot,
@@ -37,6 +42,17 @@ pub enum TokenKind {
     And,
     Or,
     Not,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    ShiftLeft,
+    ShiftRight,
+    AmpersandEqual,
+    PipeEqual,
+    CaretEqual,
+    ShiftLeftEqual,
+    ShiftRightEqual,
     Semicolon,
     Comma,
     Let,
@@ -44,12 +60,20 @@ pub enum TokenKind {
     Fn,
     While,
     For,
+    In,
     Break,
     Continue,
     Return,
     If,
     Else,
     Match,
+    Try,
+    Catch,
+    Finally,
+    Throw,
+    Import,
+    Struct,
+    Impl,
     QuestionMark,
     Elvis,
     EOF,
@@ -58,6 +82,14 @@ pub enum TokenKind {
     Underscore,
 }
 
+/// A piece of an interpolated string: literal text, or the tokens of one
+/// `${}` expression followed by an `EOF` at its closing brace.
+#[derive(Debug, PartialEq, Clone)]
+pub enum StringPart {
+    Text(String),
+    Code(Vec<Token>),
+}
+
 pub trait TokenKindTrait {
     fn to_string(&self) -> String;
 }
@@ -67,6 +99,13 @@ impl TokenKindTrait for TokenKind {
         match self {
             TokenKind::Elvis => "?:".to_string(),
             TokenKind::Match => "match".to_string(),
+            TokenKind::Try => "try".to_string(),
+            TokenKind::Catch => "catch".to_string(),
+            TokenKind::Finally => "finally".to_string(),
+            TokenKind::Throw => "throw".to_string(),
+            TokenKind::Import => "import".to_string(),
+            TokenKind::Struct => "struct".to_string(),
+            TokenKind::Impl => "impl".to_string(),
             TokenKind::QuestionMark => "?".to_string(),
             TokenKind::Else => "else".to_string(),
             TokenKind::Const => "const".to_string(),
@@ -79,6 +118,7 @@ impl TokenKindTrait for TokenKind {
             TokenKind::StarEqual => "*=".to_string(),
             TokenKind::SlashEqual => "/=".to_string(),
             TokenKind::For => "for".to_string(),
+            TokenKind::In => "in".to_string(),
             TokenKind::Break => "break".to_string(),
             TokenKind::Continue => "continue".to_string(),
             TokenKind::While => "while".to_string(),
@@ -93,6 +133,17 @@ impl TokenKindTrait for TokenKind {
             TokenKind::And => "&&".to_string(),
             TokenKind::Or => "||".to_string(),
             TokenKind::Not => "!".to_string(),
+            TokenKind::Ampersand => "&".to_string(),
+            TokenKind::Pipe => "|".to_string(),
+            TokenKind::Caret => "^".to_string(),
+            TokenKind::Tilde => "~".to_string(),
+            TokenKind::ShiftLeft => "<<".to_string(),
+            TokenKind::ShiftRight => ">>".to_string(),
+            TokenKind::AmpersandEqual => "&=".to_string(),
+            TokenKind::PipeEqual => "|=".to_string(),
+            TokenKind::CaretEqual => "^=".to_string(),
+            TokenKind::ShiftLeftEqual => "<<=".to_string(),
+            TokenKind::ShiftRightEqual => ">>=".to_string(),
             TokenKind::Identifier(name) => name.clone(),
             TokenKind::Number(value) => value.to_string(),
             TokenKind::Float(value) => value.to_string(),
@@ -117,6 +168,17 @@ impl TokenKindTrait for TokenKind {
             TokenKind::Let => "let".to_string(),
             TokenKind::If => "if".to_string(),
             TokenKind::StringLiteral(value) => format!("\"{}\"", value),
+            TokenKind::InterpolatedString(parts) => {
+                let parts: String = parts.iter().map(|part| match part {
+                    StringPart::Text(text) => text.clone(),
+                    StringPart::Code(tokens) => {
+                        let code: Vec<String> = tokens.iter().filter(|t| t.kind != TokenKind::EOF).map(|t| t.kind.to_string()).collect();
+                        format!("${{{}}}", code.join(" "))
+                    }
+                }).collect();
+                format!("\"{}\"", parts)
+            }
+            TokenKind::DocComment(text) => format!("/** {} */", text),
             TokenKind::EOF => "EOF".to_string(),
             TokenKind::Unknown(c) => c.to_string(),
             TokenKind::Colon => ":".to_string(),
@@ -129,11 +191,14 @@ impl TokenKindTrait for TokenKind {
 pub struct Token {
     pub kind: TokenKind,
     pub position: usize,
+    pub end: usize,
+    pub line: usize,
 }
 
 impl Token {
-    pub fn new(kind: TokenKind, position: usize) -> Self {
-        Token { kind, position }
+    /// `line` is filled in by the tokenizer once all tokens are known.
+    pub fn new(kind: TokenKind, position: usize, end: usize) -> Self {
+        Token { kind, position, end, line: 0 }
     }
 
     // pub fn is_identifier(&self) -> bool {
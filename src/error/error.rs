@@ -0,0 +1,188 @@
+use std::fmt;
+use std::fs;
+
+use crate::parser::ast::Span;
+use crate::utils::diagnostics::{format_diagnostic, format_message, line_col};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Syntax,
+    Name,
+    Type,
+    Value,
+    Index,
+    Key,
+    Attribute,
+    Argument,
+    Io,
+    Import,
+    /// The call depth limit was reached.
+    StackOverflow,
+    Runtime,
+    /// Raised by a script's own `throw`.
+    User,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorKind::Syntax => "SyntaxError",
+            ErrorKind::Name => "NameError",
+            ErrorKind::Type => "TypeError",
+            ErrorKind::Value => "ValueError",
+            ErrorKind::Index => "IndexError",
+            ErrorKind::Key => "KeyError",
+            ErrorKind::Attribute => "AttributeError",
+            ErrorKind::Argument => "ArgumentError",
+            ErrorKind::Io => "IoError",
+            ErrorKind::Import => "ImportError",
+            ErrorKind::StackOverflow => "StackOverflowError",
+            ErrorKind::Runtime => "RuntimeError",
+            ErrorKind::User => "Error",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A function call that was active when the error was raised.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub function: String,
+    pub call_site: Span,
+    /// Module the call site is in; `None` for the main program.
+    pub file: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlutoError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub span: Option<Span>,
+    /// Module the span points into; `None` for the main program.
+    pub file: Option<String>,
+    /// Innermost call first; `None` when raised outside any function.
+    pub call_stack: Option<Vec<Frame>>,
+}
+
+impl PlutoError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        PlutoError {
+            kind,
+            message: message.into(),
+            span: None,
+            file: None,
+            call_stack: None,
+        }
+    }
+
+    pub fn syntax(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Syntax, message)
+    }
+
+    pub fn name(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Name, message)
+    }
+
+    pub fn type_error(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Type, message)
+    }
+
+    pub fn value(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Value, message)
+    }
+
+    pub fn index(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Index, message)
+    }
+
+    pub fn key(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Key, message)
+    }
+
+    pub fn attribute(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Attribute, message)
+    }
+
+    pub fn argument(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Argument, message)
+    }
+
+    pub fn import(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Import, message)
+    }
+
+    pub fn stack_overflow(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::StackOverflow, message)
+    }
+
+    pub fn io(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Io, message)
+    }
+
+    pub fn runtime(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Runtime, message)
+    }
+
+    /// Sets the span unless a more precise one was already recorded.
+    pub fn with_span(mut self, span: Span) -> Self {
+        if self.span.is_none() {
+            self.span = Some(span);
+        }
+        self
+    }
+
+    pub fn with_frame(mut self, function: &str, call_site: Span) -> Self {
+        self.call_stack.get_or_insert_with(Vec::new).push(Frame {
+            function: function.to_string(),
+            call_site,
+            file: None,
+        });
+        self
+    }
+
+    /// Pretty output with the offending line and the call stack, for terminals.
+    /// `source` is the main program; module sources are read from their files.
+    pub fn render(&self, source: &str) -> String {
+        let label = self.kind.to_string();
+        let mut out = match self.span {
+            Some(span) => {
+                let text = module_source(&self.file);
+                let text = text.as_deref().unwrap_or(source);
+                format_diagnostic(text, self.file.as_deref(), &label, &self.message, span)
+            }
+            None => format_message(&label, &self.message),
+        };
+        if let Some(stack) = &self.call_stack {
+            let mut i = 0;
+            while i < stack.len() {
+                let frame = &stack[i];
+                let text = module_source(&frame.file);
+                let (line, column) = line_col(text.as_deref().unwrap_or(source), frame.call_site.start);
+                match &frame.file {
+                    Some(file) => out.push_str(&format!("\n  at {} ({}:{}:{})", frame.function, file, line, column)),
+                    None => out.push_str(&format!("\n  at {} ({}:{})", frame.function, line, column)),
+                }
+                // deep recursion repeats the same frame; show it once
+                let repeats = stack[i + 1..].iter().take_while(|other| *other == frame).count();
+                if repeats > 1 {
+                    out.push_str(&format!("\n  ... repeated {} more times", repeats));
+                    i += repeats;
+                }
+                i += 1;
+            }
+        }
+        out
+    }
+}
+
+fn module_source(file: &Option<String>) -> Option<String> {
+    fs::read_to_string(file.as_ref()?).ok()
+}
+
+impl fmt::Display for PlutoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+impl std::error::Error for PlutoError {}
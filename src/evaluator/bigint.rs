@@ -0,0 +1,260 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::evaluator::evaluator::Value;
+
+/// Each limb holds nine decimal digits, which keeps printing trivial.
+const BASE: u64 = 1_000_000_000;
+
+/// Arbitrary-precision integer behind `Value::BigInt`, which integer
+/// arithmetic switches to when a result no longer fits in an `i64`.
+/// Division and remainder truncate toward zero, like `i64`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    /// Magnitude in base `BASE`, least significant limb first, with no
+    /// trailing zero limbs; zero has no limbs and is never negative.
+    limbs: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut limbs: Vec<u32>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        BigInt { negative: negative && !limbs.is_empty(), limbs }
+    }
+
+    pub fn from_i128(n: i128) -> Self {
+        let mut magnitude = n.unsigned_abs();
+        let mut limbs = Vec::new();
+        while magnitude > 0 {
+            limbs.push((magnitude % BASE as u128) as u32);
+            magnitude /= BASE as u128;
+        }
+        BigInt::new(n < 0, limbs)
+    }
+
+    pub fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(n) => Some(BigInt::from(*n)),
+            Value::BigInt(n) => Some(n.clone()),
+            _ => None,
+        }
+    }
+
+    /// A `Number` when the value fits in one, so a `Value::BigInt` is
+    /// always outside the `i64` range.
+    pub fn to_value(self) -> Value {
+        match self.to_i64() {
+            Some(n) => Value::Number(n),
+            None => Value::BigInt(self),
+        }
+    }
+
+    /// Parses an optionally signed string of decimal digits.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let limbs = digits
+            .as_bytes()
+            .rchunks(9)
+            .map(|chunk| chunk.iter().fold(0, |limb, digit| limb * 10 + (digit - b'0') as u32))
+            .collect();
+        Some(BigInt::new(negative, limbs))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.limbs.len() > 3 {
+            return None;
+        }
+        let magnitude = self.limbs.iter().rev().fold(0i128, |acc, &limb| acc * BASE as i128 + limb as i128);
+        i64::try_from(if self.negative { -magnitude } else { magnitude }).ok()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self.limbs.iter().rev().fold(0.0, |acc, &limb| acc * BASE as f64 + limb as f64);
+        if self.negative { -magnitude } else { magnitude }
+    }
+
+    pub fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_magnitudes(&self.limbs, &other.limbs));
+        }
+        match compare_magnitudes(&self.limbs, &other.limbs) {
+            Ordering::Less => BigInt::new(other.negative, sub_magnitudes(&other.limbs, &self.limbs)),
+            _ => BigInt::new(self.negative, sub_magnitudes(&self.limbs, &other.limbs)),
+        }
+    }
+
+    pub fn sub(&self, other: &BigInt) -> BigInt {
+        self.add(&BigInt::new(!other.negative, other.limbs.clone()))
+    }
+
+    pub fn mul(&self, other: &BigInt) -> BigInt {
+        let mut limbs = vec![0u64; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let total = limbs[i + j] + a as u64 * b as u64 + carry;
+                limbs[i + j] = total % BASE;
+                carry = total / BASE;
+            }
+            limbs[i + other.limbs.len()] += carry;
+        }
+        BigInt::new(self.negative != other.negative, limbs.into_iter().map(|l| l as u32).collect())
+    }
+
+    /// Quotient and remainder, or `None` when dividing by zero.
+    pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = div_rem_magnitudes(&self.limbs, &other.limbs);
+        Some((
+            BigInt::new(self.negative != other.negative, quotient),
+            BigInt::new(self.negative, remainder),
+        ))
+    }
+
+    pub fn pow(&self, mut exponent: u32) -> BigInt {
+        let mut result = BigInt::from(1);
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul(&base);
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = base.mul(&base);
+            }
+        }
+        result
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(n: i64) -> Self {
+        BigInt::from_i128(n as i128)
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitudes(&self.limbs, &other.limbs),
+            (true, true) => compare_magnitudes(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some((most, rest)) = self.limbs.split_last() else {
+            return write!(f, "0");
+        };
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", most)?;
+        for limb in rest.iter().rev() {
+            write!(f, "{:09}", limb)?;
+        }
+        Ok(())
+    }
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut limbs = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let total = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        limbs.push((total % BASE) as u32);
+        carry = total / BASE;
+    }
+    limbs.push(carry as u32);
+    limbs
+}
+
+/// `a - b`, where `a` is at least `b`.
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut limbs = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &limb) in a.iter().enumerate() {
+        let subtrahend = *b.get(i).unwrap_or(&0) as i64 + borrow;
+        let mut diff = limb as i64 - subtrahend;
+        borrow = 0;
+        if diff < 0 {
+            diff += BASE as i64;
+            borrow = 1;
+        }
+        limbs.push(diff as u32);
+    }
+    limbs
+}
+
+fn mul_small(a: &[u32], factor: u32) -> Vec<u32> {
+    let mut limbs = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0;
+    for &limb in a {
+        let total = limb as u64 * factor as u64 + carry;
+        limbs.push((total % BASE) as u32);
+        carry = total / BASE;
+    }
+    limbs.push(carry as u32);
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+    limbs
+}
+
+/// Schoolbook long division, one limb of the quotient at a time; each limb
+/// is found by binary search over its possible values.
+fn div_rem_magnitudes(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let mut quotient = vec![0; a.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+    for (i, &limb) in a.iter().enumerate().rev() {
+        remainder.insert(0, limb);
+        while remainder.last() == Some(&0) {
+            remainder.pop();
+        }
+        let (mut low, mut high) = (0, BASE as u32 - 1);
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            if compare_magnitudes(&mul_small(b, mid), &remainder) == Ordering::Greater {
+                high = mid - 1;
+            } else {
+                low = mid;
+            }
+        }
+        if low > 0 {
+            remainder = sub_magnitudes(&remainder, &mul_small(b, low));
+            while remainder.last() == Some(&0) {
+                remainder.pop();
+            }
+        }
+        quotient[i] = low;
+    }
+    (quotient, remainder)
+}
@@ -0,0 +1,125 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::evaluator::evaluator::{Scope, Value};
+use crate::evaluator::scope::Frame;
+
+/// Registered scopes at which the first collection runs.
+const FIRST_COLLECTION: usize = 256;
+
+/// Frees scopes that only reference cycles keep alive.
+///
+/// A closure holds the scopes it was created in, so a closure stored in one
+/// of them (a local `fn`, `let f = () -> ...`) makes a cycle that reference
+/// counting never frees. Every scope a closure captures is registered here.
+/// Once enough have piled up, the ones nothing outside the registered scopes
+/// refers to, directly or through another scope, are emptied, which breaks
+/// their cycles. This is the trial deletion CPython's `gc` uses.
+pub(crate) struct Cycles {
+    captured: RefCell<Vec<Weak<RefCell<Frame>>>>,
+    /// Registry size that triggers the next look at it.
+    limit: Cell<usize>,
+}
+
+impl Default for Cycles {
+    fn default() -> Self {
+        Cycles {
+            captured: RefCell::new(Vec::new()),
+            limit: Cell::new(FIRST_COLLECTION),
+        }
+    }
+}
+
+impl Cycles {
+    /// Registers the scopes a new closure captures.
+    pub fn capture(&self, env: &[Scope]) {
+        let mut captured = self.captured.borrow_mut();
+        for scope in env {
+            if let Ok(mut frame) = scope.try_borrow_mut()
+                && frame.mark_captured()
+            {
+                captured.push(Rc::downgrade(scope));
+            }
+        }
+        let full = captured.len() >= self.limit.get();
+        drop(captured);
+        if full {
+            self.collect(false);
+        }
+    }
+
+    /// Drops registrations of freed scopes and, when most of the registered
+    /// scopes are still alive (or `always`), frees the ones kept by cycles.
+    pub fn collect(&self, always: bool) {
+        let scopes: Vec<Scope> = self.captured.take().iter().filter_map(Weak::upgrade).collect();
+        let keep = if always || scopes.len() >= self.limit.get() / 2 {
+            unreachable_emptied(&scopes)
+        } else {
+            vec![true; scopes.len()]
+        };
+        let live: Vec<_> = scopes.iter().zip(&keep).filter(|(_, keep)| **keep).map(|(s, _)| Rc::downgrade(s)).collect();
+        self.limit.set((live.len() * 2).max(FIRST_COLLECTION));
+        *self.captured.borrow_mut() = live;
+    }
+}
+
+/// Empties the scopes of `scopes` that are only reachable through each
+/// other and returns which ones are left.
+fn unreachable_emptied(scopes: &[Scope]) -> Vec<bool> {
+    let index: HashMap<_, _> = scopes.iter().enumerate().map(|(i, s)| (Rc::as_ptr(s), i)).collect();
+
+    // references from outside the registered scopes; `scopes` holds one of each
+    let mut outside: Vec<usize> = scopes.iter().map(|s| Rc::strong_count(s) - 1).collect();
+    for scope in scopes {
+        // a scope in use cannot be inspected, so whatever it refers to stays
+        let Ok(frame) = scope.try_borrow() else { continue };
+        for (_, (value, _)) in frame.iter() {
+            scopes_in(value, &mut |s| {
+                if let Some(&j) = index.get(&Rc::as_ptr(s)) {
+                    outside[j] = outside[j].saturating_sub(1);
+                }
+            });
+        }
+    }
+
+    let mut reachable = vec![false; scopes.len()];
+    let mut pending: Vec<usize> = (0..scopes.len())
+        .filter(|&i| outside[i] > 0 || scopes[i].try_borrow().is_err())
+        .collect();
+    while let Some(i) = pending.pop() {
+        if std::mem::replace(&mut reachable[i], true) {
+            continue;
+        }
+        let Ok(frame) = scopes[i].try_borrow() else { continue };
+        for (_, (value, _)) in frame.iter() {
+            scopes_in(value, &mut |s| {
+                if let Some(&j) = index.get(&Rc::as_ptr(s)) {
+                    pending.push(j);
+                }
+            });
+        }
+    }
+
+    // emptied first and dropped afterwards, so no scope is borrowed while
+    // its variables are freed
+    let garbage: Vec<Frame> = scopes
+        .iter()
+        .zip(&reachable)
+        .filter(|(_, reachable)| !**reachable)
+        .map(|(scope, _)| scope.take())
+        .collect();
+    drop(garbage);
+    reachable
+}
+
+/// Calls `found` with every scope a closure inside `value` holds.
+fn scopes_in(value: &Value, found: &mut impl FnMut(&Scope)) {
+    match value {
+        Value::UserFunction { env, .. } => env.iter().for_each(found),
+        Value::Array(items) | Value::Struct { fields: items, .. } => items.iter().for_each(|v| scopes_in(v, found)),
+        Value::HashMapV(map) => map.values().for_each(|v| scopes_in(v, found)),
+        Value::Module(frame) => found(frame),
+        _ => {}
+    }
+}
@@ -1,8 +1,22 @@
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell, RefMut};
 use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
 
-use crate::builtins::builtins::{default_env, float_methods, number_methods, string_methods, array_methods, hashmap_methods};
-use crate::parser::ast::{ASTNode, ASTNodeTrait};
+use crate::builtins::builtins::{default_env, float_method, number_method, string_method, array_method, hashmap_method, range_method};
+use crate::error::error::{ErrorKind, PlutoError};
+use crate::evaluator::bigint::BigInt;
+use crate::evaluator::cycles::Cycles;
+use crate::evaluator::module_loader::{import_binding, ModuleLoader};
+use crate::evaluator::ordered_map::OrderedMap;
+use crate::evaluator::range::IntRange;
+use crate::evaluator::scope::Frame;
+use crate::evaluator::structs::StructDef;
+use crate::lexer::tokenizer::tokenize;
+use crate::parser::ast::{ASTNode, ASTNodeKind, ASTNodeTrait, BinaryOp, MatchArm, Params, Pattern, PostfixOp, Slot, Span, UnaryOp};
+use crate::parser::parser::Parser;
+use crate::resolver::resolver::resolve;
 
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
@@ -13,74 +27,166 @@ pub enum EvalResult {
     Continue,
 }
 
+type NativeFnBody = dyn Fn(&mut Evaluator, Vec<Value>) -> Result<Value, PlutoError>;
+
+/// A function implemented in Rust. It can capture state, fail with a
+/// `PlutoError`, and call back into Pluto through the evaluator it is given.
+#[derive(Clone)]
+pub struct NativeFunction(Rc<NativeFnBody>);
+
+impl NativeFunction {
+    pub fn new(f: impl Fn(&mut Evaluator, Vec<Value>) -> Result<Value, PlutoError> + 'static) -> Self {
+        NativeFunction(Rc::new(f))
+    }
+
+    pub fn call(&self, evaluator: &mut Evaluator, args: Vec<Value>) -> Result<Value, PlutoError> {
+        (self.0)(evaluator, args)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<built-in function>")
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Value {
     Bool(bool),
     Number(i64),
+    /// An integer outside the `i64` range; see `Overflow`.
+    BigInt(BigInt),
     Float(f64),
-    BuiltInFunction(fn(Vec<Value>) -> Value),
+    BuiltInFunction(NativeFunction),
     UserFunction {
         params: Vec<(String, Option<Box<ASTNode>>)>,
-        body: Box<ASTNode>,
-        env: Vec<HashMap<String, (Value, bool)>>,
+        body: Rc<ASTNode>,
+        env: Vec<Scope>,
+        /// From the doc comment of its declaration; see `doc()`.
+        doc: Option<Rc<str>>,
     },
     String(String),
-    Module(HashMap<String, Value>),
+    /// The global frame of an imported file or a builtin module, shared so
+    /// that its exports stay live as its functions update them.
+    Module(Scope),
     Array(Vec<Value>),
-    HashMapV(HashMap<String, Value>),
+    HashMapV(OrderedMap),
+    Range {
+        start: i64,
+        end: i64,
+        step: i64,
+        inclusive: bool,
+    },
+    Error(Box<PlutoError>),
+    StructType(Rc<StructDef>),
+    /// Field values in the order of `def.fields`.
+    Struct {
+        def: Rc<StructDef>,
+        fields: Vec<Value>,
+    },
     Null
 }
 
 pub trait PlutoMethod {
-    fn call_method(&self, method: &str, args: Vec<Value>) -> Result<Value, String>;
+    fn call_method(&self, evaluator: &mut Evaluator, method: &str, args: Vec<Value>) -> Result<Value, PlutoError>;
 }
 
 impl PlutoMethod for Value {
-    fn call_method(&self, method: &str, args: Vec<Value>) -> Result<Value, String> {
+    fn call_method(&self, evaluator: &mut Evaluator, method: &str, args: Vec<Value>) -> Result<Value, PlutoError> {
         match self {
-            Value::Module(map) => {
-                if let Some(Value::BuiltInFunction(f)) = map.get(method) {
-                    return Ok(f(args));
+            Value::Struct { def, .. } | Value::StructType(def) => {
+                let Some(func) = def.method(method) else {
+                    return Err(PlutoError::attribute(format!("No such method '{}' for '{}'", method, def.name)));
+                };
+                // methods whose first parameter is `self` receive the instance
+                let takes_self = matches!(&func, Value::UserFunction { params, .. } if params.first().is_some_and(|(p, _)| p == "self"));
+                let mut call_args = Vec::new();
+                if let Value::Struct { .. } = self
+                    && takes_self
+                {
+                    call_args.push(self.clone());
+                } else if takes_self {
+                    return Err(PlutoError::type_error(format!("'{}.{}' must be called on an instance", def.name, method)));
+                }
+                call_args.extend(args);
+                evaluator.call_value(&func, call_args)
+            }
+            Value::Module(frame) => {
+                let export = frame.borrow().value(method);
+                if let Some(Value::BuiltInFunction(f)) = export {
+                    f.call(evaluator, args)
                 } else {
-                    return Err(format!("No such method '{}' in module", method));
+                    Err(PlutoError::attribute(format!("No such method '{}' in module", method)))
                 }
             }
             Value::String(_) => {
-                if let Some(f) = string_methods().get(method) {
-                    f(self, args)
+                if let Some(f) = string_method(method) {
+                    f(evaluator, self, args)
                 } else {
-                    Err(format!("No such method '{}' for String", method))
+                    Err(PlutoError::attribute(format!("No such method '{}' for String", method)))
                 }
             }
-            Value::Number(_) => {
-                if let Some(f) = number_methods().get(method) {
-                    f(self, args)
+            Value::Number(_) | Value::BigInt(_) => {
+                if let Some(f) = number_method(method) {
+                    f(evaluator, self, args)
                 } else {
-                    Err(format!("No such method '{}' for Number", method))
+                    Err(PlutoError::attribute(format!("No such method '{}' for Number", method)))
                 }
             }
             Value::Float(_) => {
-                if let Some(f) = float_methods().get(method) {
-                    f(self, args)
+                if let Some(f) = float_method(method) {
+                    f(evaluator, self, args)
                 } else {
-                    Err(format!("No such method '{}' for Float", method))
+                    Err(PlutoError::attribute(format!("No such method '{}' for Float", method)))
                 }
             }
             Value::Array(_) => {
-                if let Some(f) = array_methods().get(method) {
-                    f(self, args)
+                if let Some(f) = array_method(method) {
+                    f(evaluator, self, args)
                 } else {
-                    Err(format!("No such method '{}' for Array", method))
+                    Err(PlutoError::attribute(format!("No such method '{}' for Array", method)))
                 }
             }
             Value::HashMapV(_) => {
-                if let Some(f) = hashmap_methods().get(method) {
-                    f(self, args)
+                if let Some(f) = hashmap_method(method) {
+                    f(evaluator, self, args)
                 } else {
-                    Err(format!("No such method '{}' for HashMap", method))
+                    Err(PlutoError::attribute(format!("No such method '{}' for HashMap", method)))
                 }
             }
-            _ => Err(format!("No such method '{}' for this type", method)),
+            Value::Range { .. } => {
+                if let Some(f) = range_method(method) {
+                    f(evaluator, self, args)
+                } else if let Some(f) = array_method(method) {
+                    // anything else behaves as on the materialized array
+                    let range = IntRange::from_value(self).unwrap();
+                    f(evaluator, &Value::Array(range.iter().map(Value::Number).collect()), args)
+                } else {
+                    Err(PlutoError::attribute(format!("No such method '{}' for Range", method)))
+                }
+            }
+            _ => Err(PlutoError::attribute(format!("No such method '{}' for this type", method))),
+        }
+    }
+}
+
+impl Value {
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::Null => "Null",
+            Value::Bool(_) => "Bool",
+            Value::Number(_) | Value::BigInt(_) => "Number",
+            Value::Float(_) => "Float",
+            Value::String(_) => "String",
+            Value::Array(_) => "Array",
+            Value::HashMapV(_) => "HashMap",
+            Value::Range { .. } => "Range",
+            Value::Module(_) => "Module",
+            Value::BuiltInFunction(_) => "BuiltInFunction",
+            Value::UserFunction { .. } => "UserFunction",
+            Value::Error(_) => "Error",
+            Value::StructType(_) => "Struct",
+            Value::Struct { def, .. } => &def.name,
         }
     }
 }
@@ -90,51 +196,268 @@ impl fmt::Display for Value {
         match self {
             Value::Bool(b) => write!(f, "{}", b),
             Value::Number(n) => write!(f, "{}", n),
+            Value::BigInt(n) => write!(f, "{}", n),
             Value::Float(fl) => write!(f, "{}", fl),
             Value::String(s) => write!(f, "{}", s),
             Value::Null => write!(f, "null"),
             Value::BuiltInFunction(_) => write!(f, "<built-in function>"),
             Value::Module(_) => write!(f, "<module>"),
-            Value::UserFunction { params, body, env } => {
+            Value::UserFunction { params, body, env, .. } => {
                 let params_str = params.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>().join(", ");
                 write!(f, "<function: params=[{}], body={:?}, env_size={} >", params_str, body, env.len())
             }
             Value::Array(arr) => write!(f, "[{}]", arr.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")),
             Value::HashMapV(map) => {
                 let pairs: Vec<String> = map.iter()
-                    .map(|(k, v)| format!("{}: {}", k, v.to_string()))
+                    .map(|(k, v)| format!("{}: {}", k, v))
                     .collect();
                 write!(f, "{{{}}}", pairs.join(", "))
             }
+            Value::Range { start, end, step, inclusive } => {
+                let dots = if *inclusive { "..=" } else { ".." };
+                if *step == 1 {
+                    write!(f, "{}{}{}", start, dots, end)
+                } else {
+                    write!(f, "({}{}{}).step({})", start, dots, end, step)
+                }
+            }
+            Value::Error(err) => write!(f, "{}", err),
+            Value::StructType(def) => write!(f, "<struct {}>", def.name),
+            Value::Struct { def, fields } => {
+                let pairs: Vec<String> = def.fields.iter()
+                    .zip(fields)
+                    .map(|((name, _), v)| format!("{}: {}", name, v))
+                    .collect();
+                write!(f, "{} {{ {} }}", def.name, pairs.join(", "))
+            }
         }
     }
 }
 
+/// One level of variables. Scopes are shared, so a closure sees later
+/// changes to the variables it captured, and its own changes are seen outside.
+pub type Scope = Rc<RefCell<Frame>>;
+
+/// How many nested calls a program may make before failing with a
+/// `StackOverflow` error, unless changed with `set_max_depth`.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+/// Bytes of native stack nested calls may use before failing with a
+/// `StackOverflow` error, unless changed with `set_max_stack`. Half of the
+/// 2 MiB a Rust thread gets by default, leaving the rest to the host.
+pub const DEFAULT_MAX_STACK: usize = 1024 * 1024;
+
+/// What integer arithmetic does when a result does not fit in a `Number`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Continues with an arbitrary-precision `Value::BigInt`.
+    #[default]
+    Promote,
+    /// Raises a `RuntimeError`.
+    Error,
+}
+
+/// Active calls, shared by every evaluator of one program.
+#[derive(Default)]
+struct CallDepth {
+    current: Cell<usize>,
+    max: Cell<usize>,
+    /// Native stack position of the outermost active call.
+    stack_base: Cell<usize>,
+    max_stack: Cell<usize>,
+}
+
+/// A `return f(x)` whose call is left to the caller's `apply`, which makes it
+/// in place of the returning function, so tail recursion runs in constant stack.
+pub(crate) struct TailCall {
+    pub func: Value,
+    pub args: Vec<(Option<String>, Value)>,
+    pub name: String,
+    pub call_site: Span,
+}
+
 pub struct Evaluator {
-    pub env_stack: Vec<HashMap<String, (Value, bool)>>,
+    pub env_stack: Vec<Scope>,
+    modules: Rc<RefCell<ModuleLoader>>,
+    calls: Rc<CallDepth>,
+    overflow: Rc<Cell<Overflow>>,
+    cycles: Rc<Cycles>,
+    /// Set while running a function body, the only place a tail call can happen.
+    pub(crate) in_function: bool,
+    pub(crate) tail_call: Option<TailCall>,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Evaluator {
     pub fn new() -> Self {
+        Evaluator::with_env(vec![Rc::new(RefCell::new(default_env().into_iter().collect()))]) // default
+    }
+
+    pub fn with_env(env_stack: Vec<Scope>) -> Self {
+        Evaluator {
+            env_stack,
+            modules: Rc::new(RefCell::new(ModuleLoader::new())),
+            calls: Rc::new(CallDepth {
+                current: Cell::new(0),
+                max: Cell::new(DEFAULT_MAX_DEPTH),
+                stack_base: Cell::new(0),
+                max_stack: Cell::new(DEFAULT_MAX_STACK),
+            }),
+            overflow: Rc::new(Cell::new(Overflow::default())),
+            cycles: Rc::new(Cycles::default()),
+            in_function: false,
+            tail_call: None,
+        }
+    }
+
+    /// An evaluator for a module that shares this one's loaded modules, call
+    /// depth and captured scopes.
+    fn child(&self, env_stack: Vec<Scope>) -> Self {
         Evaluator {
-            env_stack: vec![default_env()], // default
+            env_stack,
+            modules: Rc::clone(&self.modules),
+            calls: Rc::clone(&self.calls),
+            overflow: Rc::clone(&self.overflow),
+            cycles: Rc::clone(&self.cycles),
+            in_function: false,
+            tail_call: None,
+        }
+    }
+
+    /// Limits how deeply calls may nest. Each level also needs native stack,
+    /// so a large limit may require running on a thread with a bigger stack.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.calls.max.set(max_depth);
+    }
+
+    /// Limits how many bytes of native stack nested calls may use. The
+    /// default suits a thread with Rust's default stack of 2 MiB.
+    pub fn set_max_stack(&mut self, max_stack: usize) {
+        self.calls.max_stack.set(max_stack);
+    }
+
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow.set(overflow);
+    }
+
+    /// The result of integer arithmetic: `checked` if it did not overflow,
+    /// otherwise `big()` or an error, depending on the `Overflow` setting.
+    pub(crate) fn integer_result(&self, checked: Option<i64>, big: impl FnOnce() -> BigInt) -> Result<Value, PlutoError> {
+        match (checked, self.overflow.get()) {
+            (Some(n), _) => Ok(Value::Number(n)),
+            (None, Overflow::Promote) => Ok(big().to_value()),
+            (None, Overflow::Error) => Err(PlutoError::runtime("Integer overflow")),
+        }
+    }
+
+    pub(crate) fn enter_call(&self) -> Result<(), PlutoError> {
+        let depth = self.calls.current.get();
+        if depth >= self.calls.max.get() {
+            return Err(PlutoError::stack_overflow(format!(
+                "Stack overflow: maximum call depth of {} exceeded",
+                self.calls.max.get()
+            )));
+        }
+        // the depth alone cannot tell whether the next call still fits on
+        // the native stack, which depends on the thread and the build
+        let position = stack_position();
+        if depth == 0 {
+            self.calls.stack_base.set(position);
+        } else if self.calls.stack_base.get().abs_diff(position) > self.calls.max_stack.get() {
+            return Err(PlutoError::stack_overflow(format!(
+                "Stack overflow: native stack exhausted after {} nested calls",
+                depth
+            )));
         }
+        self.calls.current.set(depth + 1);
+        Ok(())
     }
 
-    fn current_env_mut(&mut self) -> &mut HashMap<String, (Value, bool)> {
-        self.env_stack.last_mut().unwrap()
+    pub(crate) fn leave_call(&self) {
+        self.calls.current.set(self.calls.current.get() - 1);
     }
 
-    fn lookup(&self, name: &str) -> Option<Value> {
+    /// Path of the script being run; imports resolve relative to its directory.
+    pub fn set_script_path(&mut self, path: &Path) {
+        self.modules.borrow_mut().set_main(path);
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.env_stack.first()?.borrow().get(name).map(|(val, _)| val.clone())
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.env_stack[0].borrow_mut().insert(name.to_string(), (value, false));
+    }
+
+    /// Drops every variable and loaded module, including those that closures
+    /// referring to each other would keep alive. The evaluator is unusable afterwards.
+    pub fn release(&mut self) {
+        self.env_stack.clear();
+        *self.modules.borrow_mut() = ModuleLoader::new();
+        self.cycles.collect(true);
+    }
+
+    /// A closure over the current scopes.
+    fn closure(&self, params: &Params, body: &Rc<ASTNode>, doc: Option<Rc<str>>) -> Value {
+        self.closure_in(self.env_stack.clone(), params, body, doc)
+    }
+
+    /// A closure over `env`, which must hold a scope for every scope the
+    /// resolver saw around the function.
+    pub(crate) fn closure_in(&self, env: Vec<Scope>, params: &Params, body: &Rc<ASTNode>, doc: Option<Rc<str>>) -> Value {
+        self.cycles.capture(&env);
+        Value::UserFunction {
+            params: params.clone(),
+            body: Rc::clone(body),
+            env,
+            doc,
+        }
+    }
+
+    pub(crate) fn current_env_mut(&self) -> RefMut<'_, Frame> {
+        self.env_stack.last().unwrap().borrow_mut()
+    }
+
+    pub(crate) fn push_scope(&mut self, scope: Frame) {
+        self.env_stack.push(Rc::new(RefCell::new(scope)));
+    }
+
+    pub(crate) fn lookup(&self, name: &str) -> Option<Value> {
         for env in self.env_stack.iter().rev() {
-            if let Some((val, _)) = env.get(name) {
+            if let Some((val, _)) = env.borrow().get(name) {
                 return Some(val.clone());
             }
         }
         None
     }
 
-    fn is_truthy(&self, value: &Value) -> bool {
+    /// Runs `f` on the variable `name`, reaching it through `slot` when the
+    /// resolver supplied one and searching the scopes by name otherwise.
+    pub(crate) fn with_variable<R>(&self, name: &str, slot: Option<Slot>, f: impl FnOnce(&mut (Value, bool)) -> R) -> Option<R> {
+        if let Some(slot) = slot
+            && let Some(scope) = self.env_stack.len().checked_sub(slot.depth + 1).map(|i| &self.env_stack[i])
+            && let Some(entry) = scope.borrow_mut().get_mut_at(name, slot.index)
+        {
+            return Some(f(entry));
+        }
+        for env in self.env_stack.iter().rev() {
+            if let Some(entry) = env.borrow_mut().get_mut(name) {
+                return Some(f(entry));
+            }
+        }
+        None
+    }
+
+    /// The one truthiness rule, used by every condition, `!`, `&&`, `||`,
+    /// `?:` and match guards: `false`, `null`, zero, and empty strings,
+    /// arrays, maps and ranges are falsy; everything else is truthy.
+    pub(crate) fn is_truthy(&self, value: &Value) -> bool {
         match value {
             Value::Bool(b) => *b,
             Value::Number(n) => *n != 0,
@@ -143,999 +466,1533 @@ impl Evaluator {
             Value::Array(arr) => !arr.is_empty(),
             Value::Null => false,
             Value::HashMapV(map) => !map.is_empty(),
-            _ => false
+            Value::Range { .. } => !IntRange::from_value(value).unwrap().is_empty(),
+            _ => true,
+        }
+    }
+
+    /// The result of `&&`, `||` or `?:` when its left operand alone decides
+    /// it, in which case the right operand must not be evaluated.
+    pub(crate) fn short_circuit(&self, op: BinaryOp, left: &Value) -> Option<Value> {
+        match op {
+            BinaryOp::And if !self.is_truthy(left) => Some(Value::Bool(false)),
+            BinaryOp::Or if self.is_truthy(left) => Some(Value::Bool(true)),
+            BinaryOp::Elvis if self.is_truthy(left) => Some(left.clone()),
+            _ => None,
         }
     }
 
-    pub fn evaluate(&mut self, ast: &ASTNode) -> Result<Value, String> {
-        match self.eval(ast)? {
+    pub fn evaluate(&mut self, ast: &ASTNode) -> Result<Value, PlutoError> {
+        match self.eval(ast).map_err(|e| self.locate(e))? {
             EvalResult::Value(val) => Ok(val),
-            EvalResult::Return(_) => Err("Unexpected 'return' outside of function".to_string()),
-            EvalResult::Break => Err("Unexpected 'break' outside of loop".to_string()),
-            EvalResult::Continue => Err("Unexpected 'continue' outside of loop".to_string()),
+            EvalResult::Return(_) => Err(PlutoError::syntax("Unexpected 'return' outside of function")),
+            EvalResult::Break => Err(PlutoError::syntax("Unexpected 'break' outside of loop")),
+            EvalResult::Continue => Err(PlutoError::syntax("Unexpected 'continue' outside of loop")),
         }
     }
 
-    pub fn evaluate_ast(&mut self, ast: ASTNode) -> Result<Value, String> {
-        match self.eval(&ast)? {
+    pub fn evaluate_ast(&mut self, ast: ASTNode) -> Result<Value, PlutoError> {
+        match self.eval(&ast).map_err(|e| self.locate(e))? {
             EvalResult::Value(val) => Ok(val),
             EvalResult::Return(val) => Ok(val),
-            EvalResult::Break => Err("Unexpected 'break' outside of loop".to_string()),
-            EvalResult::Continue => Err("Unexpected 'continue' outside of loop".to_string()),
+            EvalResult::Break => Err(PlutoError::syntax("Unexpected 'break' outside of loop")),
+            EvalResult::Continue => Err(PlutoError::syntax("Unexpected 'continue' outside of loop")),
+        }
+    }
+
+    /// Fills in the module paths behind the file ids of an error's spans.
+    pub(crate) fn locate(&self, mut error: PlutoError) -> PlutoError {
+        let modules = self.modules.borrow();
+        if let Some(span) = error.span
+            && error.file.is_none()
+        {
+            error.file = modules.display_name(span.file);
+        }
+        if let Some(stack) = error.call_stack.as_mut() {
+            for frame in stack {
+                if frame.file.is_none() {
+                    frame.file = modules.display_name(frame.call_site.file);
+                }
+            }
         }
+        error
     }
 
     // -----------------------------------------------------
     // ------------------   CORE EVAL    -------------------
     // -----------------------------------------------------
 
-    pub fn eval(&mut self, node: &ASTNode) -> Result<EvalResult, String> {
-        match node {
-            ASTNode::Program(statements) => {
-                let mut last = Value::Null;
-                for stmt in statements {
-                    match self.eval(stmt)? {
-                        EvalResult::Value(val) => last = val,
-                        EvalResult::Return(val) => return Ok(EvalResult::Return(val)),
-                        EvalResult::Break => return Ok(EvalResult::Break),
-                        EvalResult::Continue => return Ok(EvalResult::Continue),
-                    }
-                }
-                Ok(EvalResult::Value(last))
-            }
-
-            ASTNode::Block(statements) => {
-                self.env_stack.push(HashMap::new());
-                let mut last = Value::Null;
-                
-                for stmt in statements {
-                    match self.eval(stmt)? {
-                        EvalResult::Value(_) => last = Value::Null,
-                        EvalResult::Return(val) => {
-                            self.env_stack.pop();
-                            return Ok(EvalResult::Return(val));
-                        }
-                        EvalResult::Break => {
-                            self.env_stack.pop();
-                            return Ok(EvalResult::Break);
-                        }
-                        EvalResult::Continue => {
-                            self.env_stack.pop();
-                            return Ok(EvalResult::Continue);
-                        }
-                    }
-                }
-                self.env_stack.pop();
-                Ok(EvalResult::Value(last))
-            }
+    pub fn eval(&mut self, node: &ASTNode) -> Result<EvalResult, PlutoError> {
+        self.eval_node(node).map_err(|e| e.with_span(node.span))
+    }
 
-            ASTNode::VariableDeclaration(name, maybe_expr) => {
-                let val = if let Some(expr) = maybe_expr {
-                    match self.eval(expr)? {
-                        EvalResult::Value(v) => v,
-                        EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                        EvalResult::Break => return Ok(EvalResult::Break),
-                        EvalResult::Continue => return Ok(EvalResult::Continue),
-                    }
+    /// Dispatches on the node kind. Anything that needs locals lives in its own
+    /// method, kept out of line, so the frame of this function, which every
+    /// nested expression and call recurses through, stays small.
+    fn eval_node(&mut self, node: &ASTNode) -> Result<EvalResult, PlutoError> {
+        match &node.kind {
+            ASTNodeKind::Program(statements) => self.eval_program(statements),
+            ASTNodeKind::Block(statements) => self.eval_block(statements),
+            ASTNodeKind::VariableDeclaration(name, maybe_expr) => self.eval_declaration(name, maybe_expr.as_deref(), false),
+            ASTNodeKind::ConstDeclaration(name, maybe_expr) => self.eval_declaration(name, maybe_expr.as_deref(), true),
+            ASTNodeKind::Assignment(name, expr) => self.eval_assignment(name, node.slot, expr),
+            ASTNodeKind::AssignmentMember(object, field, value_expr) => {
+                self.eval_assignment_member(object, field, value_expr)
+            }
+            ASTNodeKind::StructDeclaration(name, fields) => {
+                let def = StructDef::new(name.clone(), fields.clone());
+                self.current_env_mut().insert(name.clone(), (Value::StructType(Rc::new(def)), false));
+                Ok(EvalResult::Value(Value::Null))
+            }
+            ASTNodeKind::ImplBlock(name, methods) => self.eval_impl(name, methods),
+            ASTNodeKind::BinaryExpression(left, op, right) => self.eval_binary_expression(left, *op, right),
+            ASTNodeKind::NumberLiteral(n) => Ok(EvalResult::Value(Value::Number(*n))),
+            ASTNodeKind::FloatLiteral(f) => Ok(EvalResult::Value(Value::Float(*f))),
+            ASTNodeKind::StringLiteral(s) => Ok(EvalResult::Value(Value::String(s.clone()))),
+            ASTNodeKind::InterpolatedString(parts) => self.eval_interpolated_string(parts),
+            ASTNodeKind::NullLiteral => Ok(EvalResult::Value(Value::Null)),
+            ASTNodeKind::Identifier(name) => match self.with_variable(name, node.slot, |(val, _)| val.clone()) {
+                Some(val) => Ok(EvalResult::Value(val)),
+                None => Err(PlutoError::name(format!("Undefined variable '{}'", name))),
+            },
+            ASTNodeKind::AnonymousFunction(params, body) => {
+                Ok(EvalResult::Value(self.closure(params, body, node.doc.clone())))
+            }
+            ASTNodeKind::ImmediateInvocation(func, args) => self.eval_immediate_invocation(func, args),
+            ASTNodeKind::FunctionCall(name, args) => self.eval_function_call(name, args, node),
+            ASTNodeKind::MethodCall(obj, method, args) => self.eval_method_call(obj, method, args, node.span),
+            ASTNodeKind::MemberAccess(object, member) => self.eval_member_access(object, member),
+            ASTNodeKind::BooleanLiteral(b) => Ok(EvalResult::Value(Value::Bool(*b))),
+            ASTNodeKind::TernaryExpression(condition, then_branch, else_branch) => {
+                self.eval_conditional(condition, then_branch, Some(else_branch))
+            }
+            ASTNodeKind::IfStatement(condition, then_branch, else_branch) => {
+                self.eval_conditional(condition, then_branch, else_branch.as_deref())
+            }
+            ASTNodeKind::UnaryExpression(op, expr) => self.eval_unary_expression(*op, expr),
+            ASTNodeKind::ArrayLiteral(elements) => self.eval_array_literal(elements),
+            ASTNodeKind::IndexAccess(array_expr, index_expr) => self.eval_index_access(array_expr, index_expr),
+            ASTNodeKind::AssignmentIndex(target, index_expr, value_expr) => {
+                self.eval_assignment_index(target, index_expr, value_expr)
+            }
+            ASTNodeKind::FunctionDeclaration(name, params, body) => {
+                let func = self.closure(params, body, node.doc.clone());
+                self.current_env_mut().insert(name.clone(), (func, false));
+                Ok(EvalResult::Value(Value::Null))
+            }
+            ASTNodeKind::WhileStatement(condition, body) => self.eval_while(condition, body),
+            ASTNodeKind::ReturnStatement(value) => self.eval_return(value.as_deref()),
+            ASTNodeKind::ForStatement(init, cond, incr, body) => {
+                self.eval_for(init.as_deref(), cond.as_deref(), incr.as_deref(), body)
+            }
+            ASTNodeKind::ImportStatement(path, alias) => {
+                let module = self.import_module(path, node.span)?;
+                self.current_env_mut().insert(import_binding(path, alias.as_ref()), (module, false));
+                Ok(EvalResult::Value(Value::Null))
+            }
+            ASTNodeKind::FromImport(path, names) => self.eval_from_import(path, names, node.span),
+            ASTNodeKind::ForInStatement(bindings, iterable, body) => self.eval_for_in(bindings, iterable, body),
+            ASTNodeKind::PostfixUnaryExpression(op, expr) => {
+                if let ASTNodeKind::Identifier(ref name) = expr.kind {
+                    Ok(EvalResult::Value(self.postfix_update(*op, name, expr.slot)?))
                 } else {
-                    Value::Null
-                };
-                self.current_env_mut().insert(name.clone(), (val.clone(), false));
-                Ok(EvalResult::Value(val))
+                    Err(PlutoError::type_error("Postfix unary operation only supported on variables"))
+                }
             }
+            ASTNodeKind::AssignmentOp(op, left, right) => self.eval_assignment_op(*op, left, right),
+            ASTNodeKind::HashMapLiteral(pairs) => self.eval_hashmap_literal(pairs),
+            ASTNodeKind::Break => Ok(EvalResult::Break),
+            ASTNodeKind::Continue => Ok(EvalResult::Continue),
+            ASTNodeKind::MatchExpression(expr, arms) => self.eval_match(expr, arms),
+            ASTNodeKind::ThrowStatement(expr) => self.eval_throw(expr),
+            ASTNodeKind::TryStatement(body, catch, finally) => self.eval_try(body, catch.as_ref(), finally.as_deref()),
+            ASTNodeKind::Range(start, end, inclusive) => self.eval_range(start, end, *inclusive),
+        }
+    }
 
-            ASTNode::ConstDeclaration(name, maybe_expr) => {
-                if self.current_env_mut().contains_key(name) {
-                    return Err(format!("Variable '{}' already declared", name));
-                }
-                let val = if let Some(expr) = maybe_expr {
-                    match self.eval(expr)? {
-                        EvalResult::Value(v) => v,
-                        EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                        EvalResult::Break => return Ok(EvalResult::Break),
-                        EvalResult::Continue => return Ok(EvalResult::Continue),
-                    }
-                } else {
-                    Value::Null
-                };
-                self.current_env_mut().insert(name.clone(), (val.clone(), true));
-                Ok(EvalResult::Value(val))
+    #[inline(never)]
+    fn eval_program(&mut self, statements: &[ASTNode]) -> Result<EvalResult, PlutoError> {
+        let mut last = Value::Null;
+        for stmt in statements {
+            match self.eval(stmt)? {
+                EvalResult::Value(val) => last = val,
+                EvalResult::Return(val) => return Ok(EvalResult::Return(val)),
+                EvalResult::Break => return Ok(EvalResult::Break),
+                EvalResult::Continue => return Ok(EvalResult::Continue),
             }
+        }
+        Ok(EvalResult::Value(last))
+    }
 
-            ASTNode::Assignment(name, expr) => {
-                let value = match self.eval(expr)? {
-                    EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                };
-                for env in self.env_stack.iter_mut().rev() {
-                    if let Some((_, is_const)) = env.get(name) {
-                        if *is_const {
-                            return Err(format!("Cannot assign to constant '{}'", name));
-                        }
-                    }
-                    if let Some((val, _)) = env.get_mut(name) {
-                        *val = value.clone();
-                        return Ok(EvalResult::Value(value));
-                    }
+    #[inline(never)]
+    fn eval_block(&mut self, statements: &[ASTNode]) -> Result<EvalResult, PlutoError> {
+        // a block that declares nothing runs in the enclosing scope, as resolved
+        let scoped = statements.iter().any(ASTNode::is_declaration);
+        if scoped {
+            self.push_scope(Frame::new());
+        }
+        let mut result = EvalResult::Value(Value::Null);
+        for stmt in statements {
+            match self.eval(stmt)? {
+                EvalResult::Value(_) => {}
+                signal => {
+                    result = signal;
+                    break;
                 }
-                Err(format!("Undefined variable '{}'", name))
             }
+        }
+        if scoped {
+            self.env_stack.pop();
+        }
+        Ok(result)
+    }
 
-            ASTNode::BinaryExpression(left, op, right) => {
-                let left_val = match self.eval(left)? {
-                    EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                };
-                let right_val = match self.eval(right)? {
-                    EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                };
-                Ok(EvalResult::Value(self.eval_binary(left_val, op, right_val)?))
+    /// `let` and `const`.
+    #[inline(never)]
+    fn eval_declaration(&mut self, name: &str, maybe_expr: Option<&ASTNode>, constant: bool) -> Result<EvalResult, PlutoError> {
+        if constant && self.current_env_mut().contains_key(name) {
+            return Err(PlutoError::runtime(format!("Variable '{}' already declared", name)));
+        }
+        let val = if let Some(expr) = maybe_expr {
+            match self.eval(expr)? {
+                EvalResult::Value(v) => v,
+                EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+                EvalResult::Break => return Ok(EvalResult::Break),
+                EvalResult::Continue => return Ok(EvalResult::Continue),
             }
+        } else {
+            Value::Null
+        };
+        self.current_env_mut().insert(name.to_string(), (val.clone(), constant));
+        Ok(EvalResult::Value(val))
+    }
 
-            ASTNode::NumberLiteral(n) => Ok(EvalResult::Value(Value::Number(*n))),
+    #[inline(never)]
+    fn eval_assignment(&mut self, name: &str, slot: Option<Slot>, expr: &ASTNode) -> Result<EvalResult, PlutoError> {
+        let value = match self.eval(expr)? {
+            EvalResult::Value(v) => v,
+            EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+            EvalResult::Break => return Ok(EvalResult::Break),
+            EvalResult::Continue => return Ok(EvalResult::Continue),
+        };
+        self.assign_variable(name, slot, value.clone())?;
+        Ok(EvalResult::Value(value))
+    }
 
-            ASTNode::FloatLiteral(f) => Ok(EvalResult::Value(Value::Float(*f))),
+    #[inline(never)]
+    fn eval_assignment_member(&mut self, object: &ASTNode, field: &str, value_expr: &ASTNode) -> Result<EvalResult, PlutoError> {
+        let value = match self.eval(value_expr)? {
+            EvalResult::Value(v) => v,
+            result => return Ok(result),
+        };
+        self.assign_member(object, field, value.clone())?;
+        Ok(EvalResult::Value(value))
+    }
 
-            ASTNode::StringLiteral(s) => Ok(EvalResult::Value(Value::String(s.clone()))),
+    #[inline(never)]
+    fn eval_impl(&mut self, name: &str, methods: &[ASTNode]) -> Result<EvalResult, PlutoError> {
+        let def = self.impl_target(name)?;
+        for method in methods {
+            if let ASTNodeKind::FunctionDeclaration(method_name, params, body) = &method.kind {
+                let func = self.closure(params, body, method.doc.clone());
+                def.methods.borrow_mut().insert(method_name.clone(), func);
+            }
+        }
+        Ok(EvalResult::Value(Value::Null))
+    }
 
-            ASTNode::NullLiteral => Ok(EvalResult::Value(Value::Null)),
+    #[inline(never)]
+    fn eval_binary_expression(&mut self, left: &ASTNode, op: BinaryOp, right: &ASTNode) -> Result<EvalResult, PlutoError> {
+        let left_val = match self.eval(left)? {
+            EvalResult::Value(v) => v,
+            EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+            EvalResult::Break => return Ok(EvalResult::Break),
+            EvalResult::Continue => return Ok(EvalResult::Continue),
+        };
+        if let Some(result) = self.short_circuit(op, &left_val) {
+            return Ok(EvalResult::Value(result));
+        }
+        let right_val = match self.eval(right)? {
+            EvalResult::Value(v) => v,
+            EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+            EvalResult::Break => return Ok(EvalResult::Break),
+            EvalResult::Continue => return Ok(EvalResult::Continue),
+        };
+        Ok(EvalResult::Value(self.eval_binary(left_val, op, right_val)?))
+    }
 
-            ASTNode::Identifier(name) => {
-                if let Some(val) = self.lookup(name) {
-                    Ok(EvalResult::Value(val))
-                } else {
-                    Err(format!("Undefined variable '{}'", name))
-                }
+    #[inline(never)]
+    fn eval_interpolated_string(&mut self, parts: &[Box<ASTNode>]) -> Result<EvalResult, PlutoError> {
+        let mut string = String::new();
+        for part in parts {
+            match self.eval(part)? {
+                EvalResult::Value(value) => string.push_str(&value.to_string()),
+                other => return Ok(other),
             }
-            
-            ASTNode::AnonymousFunction(params, body) => {
-                Ok(EvalResult::Value(Value::UserFunction {
-                    params: params.clone(),
-                    body: body.clone(),
-                    env: self.env_stack.clone(),
-                }))
+        }
+        Ok(EvalResult::Value(Value::String(string)))
+    }
+
+    #[inline(never)]
+    fn eval_immediate_invocation(&mut self, func: &ASTNode, args: &[(Option<String>, Box<ASTNode>)]) -> Result<EvalResult, PlutoError> {
+        let func_val = match self.eval(func)? {
+            EvalResult::Value(v) => v,
+            result => return Ok(result),
+        };
+        match func_val {
+            Value::UserFunction { .. } => self.call_function(func_val, args, None),
+            _ => Err(PlutoError::type_error("Cannot invoke a non-function value")),
+        }
+    }
+
+    #[inline(never)]
+    fn eval_function_call(&mut self, name: &str, args: &[(Option<String>, Box<ASTNode>)], node: &ASTNode) -> Result<EvalResult, PlutoError> {
+        match self.with_variable(name, node.slot, |(val, _)| val.clone()) {
+            Some(func @ (Value::BuiltInFunction(_) | Value::UserFunction { .. } | Value::StructType(_))) => {
+                self.call_function(func, args, Some((name, node.span)))
             }
+            Some(_) => Err(PlutoError::type_error(format!("'{}' is not a function", name))),
+            None => Err(PlutoError::name(format!("Unknown function '{}'", name))),
+        }
+    }
 
-            ASTNode::ImmediateInvocation(func, args) => {
-                let func_val = match self.eval(func)? {
-                    EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                };
+    #[inline(never)]
+    fn eval_method_call(
+        &mut self,
+        obj: &ASTNode,
+        method: &str,
+        args: &[(Option<String>, Box<ASTNode>)],
+        span: Span,
+    ) -> Result<EvalResult, PlutoError> {
+        let obj_val = match self.eval(obj)? {
+            EvalResult::Value(v) => v,
+            EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+            EvalResult::Break => return Ok(EvalResult::Break),
+            EvalResult::Continue => return Ok(EvalResult::Continue),
+        };
+        // the module is not borrowed during the call, which may update it
+        let export = match &obj_val {
+            Value::Module(frame) => frame.borrow().value(method),
+            _ => None,
+        };
+        if let Some(func @ (Value::BuiltInFunction(_) | Value::UserFunction { .. } | Value::StructType(_))) = export {
+            return self.call_function(func, args, Some((method, span)));
+        }
+        let mut arg_vals = Vec::new();
+        for (_, a) in args {
+            let v = match self.eval(a)? {
+                EvalResult::Value(v) => v,
+                EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+                EvalResult::Break => return Ok(EvalResult::Break),
+                EvalResult::Continue => return Ok(EvalResult::Continue),
+            };
+            arg_vals.push(v);
+        }
+        Ok(EvalResult::Value(obj_val.call_method(self, method, arg_vals)?))
+    }
 
-                match func_val {
-                    Value::UserFunction { params, body, env } => {
-                        let mut new_env = env.clone();
-                        let mut local_env: HashMap<String, (Value, bool)> = HashMap::new();
-
-                        let mut evaluated_args = Vec::new();
-                        for (name, arg) in args {
-                            let v = match self.eval(arg)? {
-                                EvalResult::Value(v) => v,
-                                EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                                EvalResult::Break => return Ok(EvalResult::Break),
-                                EvalResult::Continue => return Ok(EvalResult::Continue),
-                            };
-                            evaluated_args.push((name, v));
-                        }
-
-                        let mut used_params = vec![false; params.len()];
-                        
-                        for (arg_name, value) in evaluated_args.iter() {
-                            if let Some(name) = arg_name {
-                                if let Some(pos) = params.iter().position(|p| &p.0 == name) {
-                                    if used_params[pos] {
-                                        return Err(format!("Parameter '{}' specified multiple times", name));
-                                    }
-                                    local_env.insert(name.clone(), (value.clone(), false));
-                                    used_params[pos] = true;
-                                } else {
-                                    return Err(format!("Unknown parameter name '{}'", name));
-                                }
-                            }
-                        }
-
-                        let mut pos = 0;
-                        for (param_name, default_value) in params.iter() {
-                            if !used_params[pos] {
-                                if let Some(arg_value) = evaluated_args.get(pos) {
-                                    local_env.insert(param_name.clone(), (arg_value.1.clone(), false));
-                                } else if let Some(default) = default_value {
-                                    let default_val = match self.eval(default)? {
-                                        EvalResult::Value(v) => v,
-                                        EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                                        EvalResult::Break => return Ok(EvalResult::Break),
-                                        EvalResult::Continue => return Ok(EvalResult::Continue),
-                                    };
-                                    local_env.insert(param_name.clone(), (default_val, false));
-                                } else {
-                                    return Err(format!("Missing argument for parameter '{}'", param_name));
-                                }
-                            }
-                            pos += 1;
-                        }
-
-                        new_env.push(local_env);
-                        let mut evaluator = Evaluator {
-                            env_stack: new_env,
-                        };
-                        let result = evaluator.eval(&body)?;
-                        match result {
-                            EvalResult::Return(val) => Ok(EvalResult::Value(val)),
-                            EvalResult::Value(val) => Ok(EvalResult::Value(val)),
-                            EvalResult::Break => return Ok(EvalResult::Break),
-                            EvalResult::Continue => return Ok(EvalResult::Continue),
-                        }
-                    }
-                    _ => Err("Cannot invoke a non-function value".to_string()),
+    #[inline(never)]
+    fn eval_member_access(&mut self, object: &ASTNode, member: &str) -> Result<EvalResult, PlutoError> {
+        let obj_val = match self.eval(object)? {
+            EvalResult::Value(v) => v,
+            EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+            EvalResult::Break => return Ok(EvalResult::Break),
+            EvalResult::Continue => return Ok(EvalResult::Continue),
+        };
+        Self::member(&obj_val, member, || object.to_string()).map(EvalResult::Value)
+    }
+
+    /// `value.member`; `object` renders the receiver for the error message.
+    pub(crate) fn member(obj_val: &Value, member: &str, object: impl FnOnce() -> String) -> Result<Value, PlutoError> {
+        if let Value::Module(frame) = obj_val
+            && let Some(val) = frame.borrow().value(member)
+        {
+            return Ok(val);
+        }
+        if let Value::Struct { def, fields } = obj_val {
+            return Ok(fields[def.field_index(member)?].clone());
+        }
+        if let Value::Error(err) = obj_val {
+            match member {
+                "message" => return Ok(Value::String(err.message.clone())),
+                "kind" => return Ok(Value::String(err.kind.to_string())),
+                "line" => {
+                    let line = match err.span {
+                        Some(span) if span.line > 0 => Value::Number(span.line as i64),
+                        _ => Value::Null,
+                    };
+                    return Ok(line);
                 }
+                _ => {}
             }
+        }
+        Err(PlutoError::attribute(format!(
+            "No such member '{}' for '{}'",
+            member,
+            object()
+        )))
+    }
 
-            ASTNode::FunctionCall(name, args) => {
-                if let Some(val) = self.lookup(name) {
-                    match val {
-                        Value::BuiltInFunction(f) => {
-                            let mut arg_values = Vec::new();
-                            for (_, arg) in args {
-                                let v = match self.eval(arg)? {
-                                    EvalResult::Value(v) => v,
-                                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                                    EvalResult::Break => return Ok(EvalResult::Break),
-                                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                                };
-                                arg_values.push(v);
-                            }
-                            let result = f(arg_values);
-                            Ok(EvalResult::Value(result))
-                        }
-                        Value::UserFunction { params, body, env } => {
-                            let mut new_env = env.clone();
-                            let mut local_env: HashMap<String, (Value, bool)> = HashMap::new();
-
-                            let mut evaluated_args = Vec::new();
-                            for (name, arg) in args {
-                                let v = match self.eval(arg)? {
-                                    EvalResult::Value(v) => v,
-                                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                                    EvalResult::Break => return Ok(EvalResult::Break),
-                                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                                };
-                                evaluated_args.push((name, v));
-                            }
-
-                            let mut used_params = vec![false; params.len()];
-                            
-                            for (arg_name, value) in evaluated_args.iter() {
-                                if let Some(name) = arg_name {
-                                    if let Some(pos) = params.iter().position(|p| &p.0 == name) {
-                                        if used_params[pos] {
-                                            return Err(format!("Parameter '{}' specified multiple times", name));
-                                        }
-                                        local_env.insert(name.clone(), (value.clone(), false));
-                                        used_params[pos] = true;
-                                    } else {
-                                        return Err(format!("Unknown parameter name '{}'", name));
-                                    }
-                                }
-                            }
-
-                            let mut pos = 0;
-                            for (arg_name, value) in evaluated_args.iter() {
-                                if arg_name.is_none() {
-                                    while pos < params.len() && used_params[pos] {
-                                        pos += 1;
-                                    }
-                                    if pos >= params.len() {
-                                        return Err("Too many arguments".to_string());
-                                    }
-                                    local_env.insert(params[pos].0.clone(), (value.clone(), false));
-                                    used_params[pos] = true;
-                                    pos += 1;
-                                }
-                            }
-
-                            for (i, (param_name, default_value)) in params.iter().enumerate() {
-                                if !used_params[i] {
-                                    if let Some(default_expr) = default_value {
-                                        let default_val = match self.eval(default_expr)? {
-                                            EvalResult::Value(v) => v,
-                                            EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                                            EvalResult::Break => return Ok(EvalResult::Break),
-                                            EvalResult::Continue => return Ok(EvalResult::Continue),
-                                        };
-                                        local_env.insert(param_name.clone(), (default_val, false));
-                                    } else {
-                                        return Err(format!("Missing argument for parameter '{}'", param_name));
-                                    }
-                                }
-                            }
-
-                            new_env.push(local_env);
-                            let mut evaluator = Evaluator {
-                                env_stack: new_env,
-                            };
-                            let result = evaluator.eval(&body)?;
-                            match result {
-                                EvalResult::Return(val) => Ok(EvalResult::Value(val)),
-                                EvalResult::Value(val) => Ok(EvalResult::Value(val)),
-                                EvalResult::Break => return Ok(EvalResult::Break),
-                                EvalResult::Continue => return Ok(EvalResult::Continue),
-                            }
-                        }
-                        _ => Err(format!("'{}' is not a function", name)),
-                    }
-                } else {
-                    Err(format!("Unknown function '{}'", name))
+    /// `if` and the ternary operator.
+    #[inline(never)]
+    fn eval_conditional(&mut self, condition: &ASTNode, then_branch: &ASTNode, else_branch: Option<&ASTNode>) -> Result<EvalResult, PlutoError> {
+        let cond_val = match self.eval(condition)? {
+            EvalResult::Value(v) => v,
+            EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+            EvalResult::Break => return Ok(EvalResult::Break),
+            EvalResult::Continue => return Ok(EvalResult::Continue),
+        };
+        if self.is_truthy(&cond_val) {
+            self.eval(then_branch)
+        } else if let Some(else_branch) = else_branch {
+            self.eval(else_branch)
+        } else {
+            Ok(EvalResult::Value(Value::Null))
+        }
+    }
+
+    #[inline(never)]
+    fn eval_unary_expression(&mut self, op: UnaryOp, expr: &ASTNode) -> Result<EvalResult, PlutoError> {
+        let val = match self.eval(expr)? {
+            EvalResult::Value(v) => v,
+            EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+            EvalResult::Break => return Ok(EvalResult::Break),
+            EvalResult::Continue => return Ok(EvalResult::Continue),
+        };
+        Ok(EvalResult::Value(self.eval_unary(op, val)?))
+    }
+
+    #[inline(never)]
+    fn eval_array_literal(&mut self, elements: &[Box<ASTNode>]) -> Result<EvalResult, PlutoError> {
+        let mut vals = Vec::new();
+        for el in elements {
+            let v = match self.eval(el)? {
+                EvalResult::Value(v) => v,
+                EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+                EvalResult::Break => return Ok(EvalResult::Break),
+                EvalResult::Continue => return Ok(EvalResult::Continue),
+            };
+            vals.push(v);
+        }
+        Ok(EvalResult::Value(Value::Array(vals)))
+    }
+
+    #[inline(never)]
+    fn eval_index_access(&mut self, array_expr: &ASTNode, index_expr: &ASTNode) -> Result<EvalResult, PlutoError> {
+        let array_val = match self.eval(array_expr)? {
+            EvalResult::Value(v) => v,
+            EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+            EvalResult::Break => return Ok(EvalResult::Break),
+            EvalResult::Continue => return Ok(EvalResult::Continue),
+        };
+        let index_val = match self.eval(index_expr)? {
+            EvalResult::Value(v) => v,
+            EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+            EvalResult::Break => return Ok(EvalResult::Break),
+            EvalResult::Continue => return Ok(EvalResult::Continue),
+        };
+        Self::index(&array_val, index_val).map(EvalResult::Value)
+    }
+
+    /// `container[index]` on evaluated operands.
+    pub(crate) fn index(container: &Value, index: Value) -> Result<Value, PlutoError> {
+        match (container, index) {
+            (Value::Array(arr), Value::Number(idx)) => {
+                let idx = idx as usize;
+                arr.get(idx)
+                    .cloned()
+                    .ok_or_else(|| PlutoError::index("Array index out of bounds"))
+            }
+            (Value::Array(arr), Value::Float(idx)) => {
+                let idx = idx as usize;
+                arr.get(idx)
+                    .cloned()
+                    .ok_or_else(|| PlutoError::index("Array index out of bounds"))
+            }
+            (Value::Array(arr), range @ Value::Range { .. }) => {
+                let mut slice = Vec::new();
+                for i in IntRange::from_value(&range).unwrap().iter() {
+                    let item = usize::try_from(i).ok().and_then(|i| arr.get(i));
+                    slice.push(item.cloned().ok_or_else(|| PlutoError::index("Array index out of bounds"))?);
                 }
+                Ok(Value::Array(slice))
             }
-
-            ASTNode::MethodCall(obj, method, args) => {
-                let obj_val = match self.eval(obj)? {
-                    EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                };
-                let mut arg_vals = Vec::new();
-                for (_, a) in args {
-                    let v = match self.eval(a)? {
-                        EvalResult::Value(v) => v,
-                        EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                        EvalResult::Break => return Ok(EvalResult::Break),
-                        EvalResult::Continue => return Ok(EvalResult::Continue),
-                    };
-                    arg_vals.push(v);
+            (range @ Value::Range { .. }, Value::Number(idx)) => {
+                IntRange::from_value(range)
+                    .unwrap()
+                    .get(idx)
+                    .map(Value::Number)
+                    .ok_or_else(|| PlutoError::index("Range index out of bounds"))
+            }
+            (range @ Value::Range { .. }, indices @ Value::Range { .. }) => {
+                IntRange::from_value(range)
+                    .unwrap()
+                    .slice(IntRange::from_value(&indices).unwrap())
+                    .map(IntRange::to_value)
+                    .ok_or_else(|| PlutoError::index("Range index out of bounds"))
+            }
+            (Value::String(s), Value::Number(idx)) => {
+                usize::try_from(idx)
+                    .ok()
+                    .and_then(|i| s.chars().nth(i))
+                    .map(|c| Value::String(c.to_string()))
+                    .ok_or_else(|| PlutoError::index("String index out of bounds"))
+            }
+            (Value::String(s), range @ Value::Range { .. }) => {
+                let chars: Vec<char> = s.chars().collect();
+                let mut slice = String::new();
+                for i in IntRange::from_value(&range).unwrap().iter() {
+                    let c = usize::try_from(i).ok().and_then(|i| chars.get(i));
+                    slice.push(*c.ok_or_else(|| PlutoError::index("String index out of bounds"))?);
                 }
-                match obj_val.call_method(method, arg_vals) {
-                    Ok(result) => Ok(EvalResult::Value(result)),
-                    Err(e) => Err(format!(
-                        "No such method '{}' for '{}': {}",
-                        method,
-                        obj.to_string(),
-                        e
-                    )),
+                Ok(Value::String(slice))
+            }
+            (Value::HashMapV(hashm), Value::String(key)) => {
+                hashm.get(&key)
+                    .cloned()
+                    .ok_or_else(|| PlutoError::key(format!("Key '{}' not found in hash map", key)))
+            }
+            _ => Err(PlutoError::type_error("Indexing only supported for arrays, strings and ranges with integer or range indices, and hash maps with string keys")),
+        }
+    }
+
+    #[inline(never)]
+    fn eval_assignment_index(&mut self, target: &ASTNode, index_expr: &ASTNode, value_expr: &ASTNode) -> Result<EvalResult, PlutoError> {
+        let index = match self.eval(index_expr)? {
+            EvalResult::Value(v) => v,
+            result => return Ok(result),
+        };
+        let value = match self.eval(value_expr)? {
+            EvalResult::Value(v) => v,
+            result => return Ok(result),
+        };
+        self.assign_index(target, index, value.clone())?;
+        Ok(EvalResult::Value(value))
+    }
+
+    #[inline(never)]
+    fn eval_while(&mut self, condition: &ASTNode, body: &ASTNode) -> Result<EvalResult, PlutoError> {
+        let mut last = Value::Null;
+        loop {
+            let cond_val = match self.eval(condition)? {
+                EvalResult::Value(v) => v,
+                EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+                EvalResult::Break => break,
+                EvalResult::Continue => continue,
+            };
+            if !self.is_truthy(&cond_val) {
+                break;
+            }
+            match self.eval(body)? {
+                EvalResult::Value(val) => last = val,
+                EvalResult::Return(val) => return Ok(EvalResult::Return(val)),
+                EvalResult::Break => break,
+                EvalResult::Continue => continue,
+            }
+        }
+        Ok(EvalResult::Value(last))
+    }
+
+    #[inline(never)]
+    fn eval_return(&mut self, value: Option<&ASTNode>) -> Result<EvalResult, PlutoError> {
+        if self.in_function
+            && let Some(call) = value
+            && let ASTNodeKind::FunctionCall(name, args) = &call.kind
+            && let Some(func @ Value::UserFunction { .. }) = self.with_variable(name, call.slot, |(val, _)| val.clone())
+        {
+            let mut evaluated_args = Vec::new();
+            for (arg_name, arg) in args {
+                match self.eval(arg)? {
+                    EvalResult::Value(v) => evaluated_args.push((arg_name.clone(), v)),
+                    result => return Ok(result),
                 }
             }
+            self.tail_call = Some(TailCall {
+                func,
+                args: evaluated_args,
+                name: name.clone(),
+                call_site: call.span,
+            });
+            return Ok(EvalResult::Return(Value::Null));
+        }
+        let val = if let Some(val) = value {
+            match self.eval(val)? {
+                EvalResult::Value(v) => v,
+                EvalResult::Return(v) => v,
+                EvalResult::Break => return Ok(EvalResult::Break),
+                EvalResult::Continue => return Ok(EvalResult::Continue),
+            }
+        } else {
+            Value::Null
+        };
+        Ok(EvalResult::Return(val))
+    }
 
-            ASTNode::MemberAccess(object, member) => {
-                let obj_val = match self.eval(object)? {
-                    EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                };
-                if let Value::Module(ref map) = obj_val {
-                    if let Some(val) = map.get(member) {
-                        return Ok(EvalResult::Value(val.clone()));
-                    }
+    #[inline(never)]
+    fn eval_for(
+        &mut self,
+        init: Option<&ASTNode>,
+        cond: Option<&ASTNode>,
+        incr: Option<&ASTNode>,
+        body: &ASTNode,
+    ) -> Result<EvalResult, PlutoError> {
+        self.push_scope(Frame::new());
+        if let Some(init) = init {
+            self.eval(init)?;
+        }
+        loop {
+            if let Some(cond) = cond {
+                match self.eval(cond)? {
+                    EvalResult::Value(val) if !self.is_truthy(&val) => break,
+                    EvalResult::Value(_) => {}
+                    EvalResult::Return(val) => return Ok(EvalResult::Return(val)),
+                    EvalResult::Break => break,
+                    EvalResult::Continue => continue,
                 }
-                Err(format!(
-                    "No such member '{}' for '{}'",
-                    member,
-                    object.to_string()
-                ))
             }
+            let res = self.eval(body)?;
+            match res {
+                EvalResult::Break => break,
+                EvalResult::Continue => {},
+                EvalResult::Return(val) => return Ok(EvalResult::Return(val)),
+                _ => {}
+            }
+            if let Some(incr) = incr {
+                self.eval(incr)?;
+            }
+        }
+        self.env_stack.pop();
+        Ok(EvalResult::Value(Value::Bool(true)))
+    }
 
-            ASTNode::BooleanLiteral(b) => Ok(EvalResult::Value(Value::Bool(*b))),
+    /// The struct an `impl` block adds methods to.
+    pub(crate) fn impl_target(&self, name: &str) -> Result<Rc<StructDef>, PlutoError> {
+        match self.lookup(name) {
+            Some(Value::StructType(def)) => Ok(def),
+            Some(_) => Err(PlutoError::type_error(format!("'{}' is not a struct", name))),
+            None => Err(PlutoError::name(format!("Unknown struct '{}'", name))),
+        }
+    }
 
-            ASTNode::TernaryExpression(condition, then_branch, else_branch) => {
-                let cond_val = match self.eval(condition)? {
-                    EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                };
-                match cond_val {
-                    Value::Bool(true) => self.eval(then_branch),
-                    Value::Bool(false) => self.eval(else_branch),
-                    _ => Err("Condition in ternary expression must be a boolean".to_string()),
-                }
+    #[inline(never)]
+    pub(crate) fn eval_from_import(&mut self, path: &str, names: &[String], site: Span) -> Result<EvalResult, PlutoError> {
+        let module = self.import_module(path, site)?;
+        let Value::Module(frame) = module else { unreachable!() };
+        for name in names {
+            let value = frame.borrow().value(name).ok_or_else(|| {
+                PlutoError::import(format!("Module '{}' has no export named '{}'", path, name))
+            })?;
+            self.current_env_mut().insert(name.clone(), (value, false));
+        }
+        Ok(EvalResult::Value(Value::Null))
+    }
+
+    #[inline(never)]
+    fn eval_for_in(&mut self, bindings: &[String], iterable: &ASTNode, body: &ASTNode) -> Result<EvalResult, PlutoError> {
+        let items = match self.eval(iterable)? {
+            EvalResult::Value(value) => Self::iterate(value)?,
+            result => return Ok(result),
+        };
+        for item in items {
+            let scope = Self::bind_loop_variables(bindings, item)?;
+            self.push_scope(scope);
+            let result = self.eval(body);
+            self.env_stack.pop();
+            match result? {
+                EvalResult::Break => break,
+                EvalResult::Return(val) => return Ok(EvalResult::Return(val)),
+                _ => {}
             }
+        }
+        Ok(EvalResult::Value(Value::Null))
+    }
 
-            ASTNode::IfStatement(condition, then_branch, else_branch) => {
-                let cond_val = match self.eval(condition)? {
-                    EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                };
-                if self.is_truthy(&cond_val) {
-                    self.eval(then_branch)
-                } else {
-                    if let Some(else_branch) = else_branch {
-                        self.eval(else_branch)
-                    } else {
-                        Ok(EvalResult::Value(Value::Null))
-                    }
-                }
+    /// The items a `for ... in` loop visits.
+    pub(crate) fn iterate(iterable: Value) -> Result<Box<dyn Iterator<Item = Value>>, PlutoError> {
+        Ok(match iterable {
+            Value::Array(arr) => Box::new(arr.into_iter()),
+            range @ Value::Range { .. } => {
+                Box::new(IntRange::from_value(&range).unwrap().iter().map(Value::Number))
+            }
+            Value::String(s) => {
+                let chars: Vec<Value> = s.chars().map(|c| Value::String(c.to_string())).collect();
+                Box::new(chars.into_iter())
+            }
+            Value::HashMapV(map) => {
+                let pairs: Vec<Value> = map.iter()
+                    .map(|(k, v)| Value::Array(vec![Value::String(k.clone()), v.clone()]))
+                    .collect();
+                Box::new(pairs.into_iter())
             }
+            other => return Err(PlutoError::type_error(format!("Cannot iterate over {}", other.type_name()))),
+        })
+    }
 
-            ASTNode::UnaryExpression(op, expr) => {
-                let val = match self.eval(expr)? {
-                    EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                };
-                match (op.as_str(), val) {
-                    ("!", Value::Bool(b)) => Ok(EvalResult::Value(Value::Bool(!b))),
-                    _ => Err("Unsupported unary operation".to_string()),
-                }
+    #[inline(never)]
+    fn eval_assignment_op(&mut self, op: BinaryOp, left: &ASTNode, right: &ASTNode) -> Result<EvalResult, PlutoError> {
+        if let ASTNodeKind::MemberAccess(object, field) = &left.kind {
+            let current = match self.eval(left)? {
+                EvalResult::Value(v) => v,
+                result => return Ok(result),
+            };
+            let right_val = match self.eval(right)? {
+                EvalResult::Value(v) => v,
+                result => return Ok(result),
+            };
+            let new_val = self.eval_binary(current, op, right_val)?;
+            self.assign_member(object, field, new_val.clone())?;
+            return Ok(EvalResult::Value(new_val));
+        }
+        if let ASTNodeKind::Identifier(ref name) = left.kind {
+            let right_val = match self.eval(right)? {
+                EvalResult::Value(v) => v,
+                _ => return Err(PlutoError::type_error("Invalid right value")),
+            };
+            Ok(EvalResult::Value(self.compound_assign(op, name, left.slot, right_val)?))
+        } else {
+            Err(PlutoError::type_error("Assignment operator only supported on variables"))
+        }
+    }
+
+    #[inline(never)]
+    fn eval_hashmap_literal(&mut self, pairs: &[(String, Box<ASTNode>)]) -> Result<EvalResult, PlutoError> {
+        let mut map = OrderedMap::new();
+        for (k, v_expr) in pairs {
+            let v = match self.eval(v_expr)? {
+                EvalResult::Value(val) => val,
+                _ => return Err(PlutoError::type_error("Invalid value in hash map literal")),
+            };
+            map.insert(k.clone(), v);
+        }
+        Ok(EvalResult::Value(Value::HashMapV(map)))
+    }
+
+    #[inline(never)]
+    fn eval_match(&mut self, expr: &ASTNode, arms: &[MatchArm]) -> Result<EvalResult, PlutoError> {
+        let value = match self.eval(expr)? {
+            EvalResult::Value(v) => v,
+            result => return Ok(result),
+        };
+
+        for (pattern, guard, result) in arms {
+            let mut bindings = Frame::new();
+            if !self.match_pattern(pattern, &value, &mut bindings)? {
+                continue;
+            }
+            // bindings live in their own scope, visible to the guard and the arm
+            let depth = self.env_stack.len();
+            self.push_scope(bindings);
+            let outcome = self.eval_match_arm(guard.as_deref(), result);
+            self.env_stack.truncate(depth);
+            if let Some(outcome) = outcome? {
+                return Ok(outcome);
             }
+        }
+        Ok(EvalResult::Value(Value::Null))
+    }
 
-            ASTNode::ArrayLiteral(elements) => {
-                let mut vals = Vec::new();
-                for el in elements {
-                    let v = match self.eval(el)? {
-                        EvalResult::Value(v) => v,
-                        EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                        EvalResult::Break => return Ok(EvalResult::Break),
-                        EvalResult::Continue => return Ok(EvalResult::Continue),
-                    };
-                    vals.push(v);
-                }
-                Ok(EvalResult::Value(Value::Array(vals)))
+    #[inline(never)]
+    fn eval_throw(&mut self, expr: &ASTNode) -> Result<EvalResult, PlutoError> {
+        let value = match self.eval(expr)? {
+            EvalResult::Value(v) => v,
+            result => return Ok(result),
+        };
+        Err(thrown(value))
+    }
+
+    #[inline(never)]
+    fn eval_try(
+        &mut self,
+        body: &ASTNode,
+        catch: Option<&(String, Box<ASTNode>)>,
+        finally: Option<&ASTNode>,
+    ) -> Result<EvalResult, PlutoError> {
+        // scopes pushed by a failing block are never popped, so unwind to here
+        let depth = self.env_stack.len();
+        // a `return f(x)` is made here rather than by the caller, inside the `try`
+        let outcome = match (self.eval(body).and_then(|r| self.finish_tail_call(r)), catch) {
+            (Err(err), Some((name, handler))) => {
+                self.env_stack.truncate(depth);
+                let mut scope = Frame::new();
+                scope.insert(name.clone(), (Value::Error(Box::new(err)), false));
+                self.push_scope(scope);
+                self.eval(handler).and_then(|r| self.finish_tail_call(r))
             }
+            (outcome, _) => outcome,
+        };
+        self.env_stack.truncate(depth);
 
-            ASTNode::IndexAccess(array_expr, index_expr) => {
-                let array_val = match self.eval(array_expr)? {
-                    EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                };
-                let index_val = match self.eval(index_expr)? {
-                    EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                };
-                match (array_val, index_val) {
-                    (Value::Array(arr), Value::Number(idx)) => {
-                        let idx = idx as usize;
-                        arr.get(idx)
-                            .cloned()
-                            .map(EvalResult::Value)
-                            .ok_or_else(|| "Array index out of bounds".to_string())
-                    }
-                    (Value::Array(arr), Value::Float(idx)) => {
-                        let idx = idx as usize;
-                        arr.get(idx)
-                            .cloned()
-                            .map(EvalResult::Value)
-                            .ok_or_else(|| "Array index out of bounds".to_string())
-                    }
-                    (Value::HashMapV(hashm), Value::String(key)) => {
-                        hashm.get(&key)
-                            .cloned()
-                            .map(EvalResult::Value)
-                            .ok_or_else(|| format!("Key '{}' not found in hash map", key))
-                    }
-                    _ => Err("Indexing only supported for arrays with integer indices".to_string()),
+        if let Some(finally) = finally {
+            match self.eval(finally)? {
+                EvalResult::Value(_) => {}
+                result => return Ok(result),
+            }
+        }
+        outcome
+    }
+
+    #[inline(never)]
+    fn eval_range(&mut self, start: &ASTNode, end: &ASTNode, inclusive: bool) -> Result<EvalResult, PlutoError> {
+        let start_val = match self.eval(start)? {
+            EvalResult::Value(v) => v,
+            EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+            EvalResult::Break => return Ok(EvalResult::Break),
+            EvalResult::Continue => return Ok(EvalResult::Continue),
+        };
+        let end_val = match self.eval(end)? {
+            EvalResult::Value(v) => v,
+            EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
+            EvalResult::Break => return Ok(EvalResult::Break),
+            EvalResult::Continue => return Ok(EvalResult::Continue),
+        };
+        Ok(EvalResult::Value(range(start_val, end_val, inclusive)?))
+    }
+
+    /// Loads `path` (relative to the file containing `site`) once per program
+    /// and returns its top-level bindings as a `Value::Module`.
+    pub(crate) fn import_module(&mut self, path: &str, site: Span) -> Result<Value, PlutoError> {
+        let resolved = self.modules.borrow().resolve(site.file, path)?;
+        if let Some(module) = self.modules.borrow().cached(&resolved) {
+            return Ok(module);
+        }
+        let file = self.modules.borrow_mut().begin(&resolved)?;
+        let result = self.load_module(&resolved, file);
+        self.modules.borrow_mut().finish(&resolved, result.as_ref().ok().cloned());
+        result
+    }
+
+    fn load_module(&mut self, path: &Path, file: usize) -> Result<Value, PlutoError> {
+        let source = fs::read_to_string(path)
+            .map_err(|e| PlutoError::io(format!("Cannot read module '{}': {}", path.display(), e)))?;
+        let in_file = |mut e: PlutoError| {
+            if let Some(span) = e.span.as_mut() {
+                span.file = file;
+            }
+            e
+        };
+        let tokens = tokenize(&source).map_err(in_file)?;
+        let mut ast = Parser::for_file(tokens, file).parse()?;
+
+        // the module's globals get a frame of their own above the builtins,
+        // so everything it defines, even a name like `len`, is an export
+        let builtins = Rc::new(RefCell::new(default_env().into_iter().collect()));
+        let mut module = self.child(vec![builtins, Rc::new(RefCell::new(Frame::new()))]);
+        resolve(&mut ast, &module.env_stack)?;
+        match module.eval(&ast)? {
+            EvalResult::Break => return Err(PlutoError::syntax("Unexpected 'break' outside of loop").with_span(ast.span)),
+            EvalResult::Continue => return Err(PlutoError::syntax("Unexpected 'continue' outside of loop").with_span(ast.span)),
+            _ => {}
+        }
+
+        Ok(Value::Module(Rc::clone(&module.env_stack[1])))
+    }
+
+    /// Binds `args` (positional and named) to the callee's parameters and runs it.
+    /// `frame` names the call in error call stacks.
+    fn call_function(
+        &mut self,
+        func: Value,
+        args: &[(Option<String>, Box<ASTNode>)],
+        frame: Option<(&str, Span)>,
+    ) -> Result<EvalResult, PlutoError> {
+        let mut evaluated_args = Vec::new();
+        for (name, arg) in args {
+            let v = match self.eval(arg)? {
+                EvalResult::Value(v) => v,
+                result => return Ok(result),
+            };
+            evaluated_args.push((name.clone(), v));
+        }
+        self.apply(func, evaluated_args, frame)
+    }
+
+    /// Calls any callable value with positional arguments. This is how native
+    /// functions call back into Pluto, e.g. `map` invoking its callback.
+    pub fn call_value(&mut self, func: &Value, args: Vec<Value>) -> Result<Value, PlutoError> {
+        let args = args.into_iter().map(|v| (None, v)).collect();
+        match self.apply(func.clone(), args, None)? {
+            EvalResult::Value(v) | EvalResult::Return(v) => Ok(v),
+            _ => Err(PlutoError::runtime("Unexpected control flow in user function")),
+        }
+    }
+
+    /// Calls a function, or constructs a struct instance, with already evaluated arguments.
+    pub(crate) fn apply(
+        &mut self,
+        func: Value,
+        args: Vec<(Option<String>, Value)>,
+        frame: Option<(&str, Span)>,
+    ) -> Result<EvalResult, PlutoError> {
+        let mut call = (func, args, frame.map(|(name, span)| (name.to_string(), span)));
+        loop {
+            let (func, args, frame) = call;
+            let (params, body, env) = match func {
+                Value::BuiltInFunction(f) => {
+                    let values = args.into_iter().map(|(_, v)| v).collect();
+                    return Ok(EvalResult::Value(f.call(self, values)?));
+                }
+                Value::StructType(def) => {
+                    let bound = self.bind_arguments(&def.fields, args, "field")?;
+                    let fields = bound.into_iter().map(|(_, (v, _))| v).collect();
+                    return Ok(EvalResult::Value(Value::Struct { def, fields }));
                 }
+                Value::UserFunction { params, body, env, .. } => (params, body, env),
+                _ => return Err(PlutoError::type_error("Cannot invoke a non-function value")),
+            };
+
+            let local_env = self.bind_arguments(&params, args, "parameter")?;
+            // the body runs in the closure's scopes, on this evaluator
+            let caller_env = std::mem::replace(&mut self.env_stack, env);
+            let caller_in_function = std::mem::replace(&mut self.in_function, true);
+            self.push_scope(local_env);
+            let result = self.enter_call().and_then(|_| {
+                let result = self.eval(&body);
+                self.leave_call();
+                result
+            });
+            self.env_stack = caller_env;
+            self.in_function = caller_in_function;
+            let result = result.map_err(|e| match &frame {
+                Some((name, call_site)) => e.with_frame(name, *call_site),
+                None => e,
+            })?;
+            // the body ended in `return g(y)`: call g in place of this function
+            if let Some(tail) = self.tail_call.take() {
+                call = (tail.func, tail.args, Some((tail.name, tail.call_site)));
+                continue;
             }
+            return match result {
+                EvalResult::Return(val) => Ok(EvalResult::Value(val)),
+                other => Ok(other),
+            };
+        }
+    }
 
-            ASTNode::AssignmentIndex(array_expr, index_expr, value_expr) => {
-                if let ASTNode::Identifier(var_name) = &**array_expr {
-                    let index_val = match self.eval(index_expr)? {
-                        EvalResult::Value(v) => v,
-                        EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                        EvalResult::Break => return Ok(EvalResult::Break),
-                        EvalResult::Continue => return Ok(EvalResult::Continue),
-                    };
-                    let value_val = match self.eval(value_expr)? {
-                        EvalResult::Value(v) => v,
-                        EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                        EvalResult::Break => return Ok(EvalResult::Break),
-                        EvalResult::Continue => return Ok(EvalResult::Continue),
+    /// Makes a pending tail call now, for a `try` that must see how it ends.
+    pub(crate) fn finish_tail_call(&mut self, result: EvalResult) -> Result<EvalResult, PlutoError> {
+        let Some(tail) = self.tail_call.take() else {
+            return Ok(result);
+        };
+        match self.apply(tail.func, tail.args, Some((&tail.name, tail.call_site)))? {
+            EvalResult::Value(val) => Ok(EvalResult::Return(val)),
+            other => Ok(other),
+        }
+    }
+
+    /// Matches named arguments first, then fills the remaining slots in order;
+    /// anything still missing takes its default. `what` names a slot in errors.
+    /// The scope holds the parameters in declaration order, as the resolver expects.
+    pub(crate) fn bind_arguments(
+        &mut self,
+        params: &Params,
+        args: Vec<(Option<String>, Value)>,
+        what: &str,
+    ) -> Result<Frame, PlutoError> {
+        let mut values: Vec<Option<Value>> = vec![None; params.len()];
+        let mut positional = Vec::new();
+
+        for (arg_name, value) in args {
+            match arg_name {
+                Some(name) => {
+                    let Some(pos) = params.iter().position(|p| p.0 == name) else {
+                        return Err(PlutoError::argument(format!("Unknown {} name '{}'", what, name)));
                     };
-                    if let Some((val, _)) = self.current_env_mut().get_mut(var_name) {
-                        if let Value::Array(arr) = val {
-                            if let Value::Number(idx) = index_val {
-                                let idx = idx as usize;
-                                if idx < arr.len() {
-                                    arr[idx] = value_val.clone();
-                                    return Ok(EvalResult::Value(Value::Array(arr.clone())));
-                                } else {
-                                    return Err("Array index out of bounds".to_string());
-                                }
-                            } else {
-                                return Err("Assignment only supported for arrays with integer indices".to_string());
-                            }
-                        } else {
-                            return Err(format!("'{}' is not an array", var_name));
-                        }
-                    } else {
-                        return Err(format!("'{}' is not an array", var_name));
+                    if values[pos].is_some() {
+                        let what = what[..1].to_uppercase() + &what[1..];
+                        return Err(PlutoError::argument(format!("{} '{}' specified multiple times", what, name)));
                     }
+                    values[pos] = Some(value);
                 }
-                let mut array_val = match self.eval(array_expr)? {
-                    EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                };
-                let index_val = match self.eval(index_expr)? {
-                    EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                };
-                let value_val = match self.eval(value_expr)? {
-                    EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
-                };
-                if let Value::Array(ref mut arr) = array_val {
-                    if let Value::Number(idx) = index_val {
-                        let idx = idx as usize;
-                        if idx < arr.len() {
-                            arr[idx] = value_val.clone();
-                            return Ok(EvalResult::Value(Value::Array(arr.clone())));
-                        } else {
-                            return Err("Array index out of bounds".to_string());
-                        }
-                    } else {
-                        return Err("Assignment only supported for arrays with integer indices".to_string());
-                    }
-                } else if let Value::HashMapV(ref mut hashm) = array_val {
-                    if let Value::String(key) = index_val {
-                        hashm.insert(key, value_val.clone());
-                        return Ok(EvalResult::Value(Value::HashMapV(hashm.clone())));
-                    } else {
-                        return Err("Assignment only supported for hash maps with string keys".to_string());
-                    }
-                } else {
-                    return Err("Assignment only supported for arrays".to_string());
-                }
+                None => positional.push(value),
             }
+        }
 
-            ASTNode::FunctionDeclaration(name, params, body) => {
-                let func = Value::UserFunction {
-                    params: params.clone(),
-                    body: Box::new((**body).clone()),
-                    env: self.env_stack.clone(),
-                };
-                self.current_env_mut().insert(name.clone(), (func, false));
-                Ok(EvalResult::Value(Value::Null))
+        let mut pos = 0;
+        for value in positional {
+            while pos < params.len() && values[pos].is_some() {
+                pos += 1;
+            }
+            if pos >= params.len() {
+                return Err(PlutoError::argument("Too many arguments"));
             }
+            values[pos] = Some(value);
+            pos += 1;
+        }
 
-            ASTNode::WhileStatement(condition, body) => {
-                let mut last = Value::Null;
-                loop {
-                    let cond_val = match self.eval(condition)? {
-                        EvalResult::Value(v) => v,
-                        EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                        EvalResult::Break => break,
-                        EvalResult::Continue => continue,
+        let mut local_env = Frame::new();
+        for ((param_name, default_value), value) in params.iter().zip(values) {
+            let value = match value {
+                Some(value) => value,
+                None => {
+                    let Some(default_expr) = default_value else {
+                        return Err(PlutoError::argument(format!("Missing argument for {} '{}'", what, param_name)));
                     };
-                    if let Value::Bool(false) = cond_val {
-                        break;
-                    }
-                    match self.eval(body)? {
-                        EvalResult::Value(val) => last = val,
-                        EvalResult::Return(val) => return Ok(EvalResult::Return(val)),
-                        EvalResult::Break => break,
-                        EvalResult::Continue => continue,
+                    match self.eval(default_expr)? {
+                        EvalResult::Value(v) | EvalResult::Return(v) => v,
+                        _ => Value::Null,
                     }
                 }
-                Ok(EvalResult::Value(last))
-            }
-            
-            ASTNode::ReturnStatement(value) => {
-                let val = if let Some(val) = value {
-                    match self.eval(val)? {
-                        EvalResult::Value(v) => v,
-                        EvalResult::Return(v) => v,
-                        EvalResult::Break => return Ok(EvalResult::Break),
-                        EvalResult::Continue => return Ok(EvalResult::Continue),
-                    }
-                } else {
-                    Value::Null
-                };
-                Ok(EvalResult::Return(val))
+            };
+            local_env.insert(param_name.clone(), (value, false));
+        }
+        Ok(local_env)
+    }
+
+    /// Runs an arm whose pattern matched; `None` when its guard rejects the value.
+    fn eval_match_arm(&mut self, guard: Option<&ASTNode>, result: &ASTNode) -> Result<Option<EvalResult>, PlutoError> {
+        if let Some(guard) = guard {
+            let passed = match self.eval(guard)? {
+                EvalResult::Value(v) => self.is_truthy(&v),
+                other => return Ok(Some(other)),
+            };
+            if !passed {
+                return Ok(None);
             }
+        }
+        self.eval(result).map(Some)
+    }
 
-            ASTNode::ForStatement(init, cond, incr, body) => {
-                self.env_stack.push(HashMap::new());
-                if let Some(init) = init {
-                    self.eval(&*init)?;
+    /// Tests `value` against `pattern`, collecting the names it binds.
+    fn match_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value: &Value,
+        bindings: &mut Frame,
+    ) -> Result<bool, PlutoError> {
+        match pattern {
+            Pattern::Wildcard => Ok(true),
+            Pattern::Binding(name) => {
+                bindings.insert(name.clone(), (value.clone(), false));
+                Ok(true)
+            }
+            Pattern::Value(expr) => {
+                let expected = match self.eval(expr)? {
+                    EvalResult::Value(v) => v,
+                    _ => return Ok(false),
+                };
+                // values of unrelated types simply don't match
+                Ok(matches!(self.eval_binary(value.clone(), BinaryOp::Eq, expected), Ok(Value::Bool(true))))
+            }
+            Pattern::Range(start, end, inclusive) => match (self.eval(start)?, self.eval(end)?) {
+                (EvalResult::Value(start), EvalResult::Value(end)) => in_range_pattern(value, start, end, *inclusive),
+                _ => Err(PlutoError::type_error("Range pattern bounds must be numbers")),
+            },
+            Pattern::Array(elements, rest) => {
+                let Value::Array(items) = value else {
+                    return Ok(false);
+                };
+                let fits = match rest {
+                    Some(_) => items.len() >= elements.len(),
+                    None => items.len() == elements.len(),
+                };
+                if !fits {
+                    return Ok(false);
                 }
-                loop {
-                    if let Some(cond) = cond {
-                        let cond_val = self.eval(&*cond)?;
-                        match cond_val {
-                            EvalResult::Value(Value::Bool(false)) => break,
-                            EvalResult::Value(Value::Bool(true)) => {},
-                            EvalResult::Return(val) => return Ok(EvalResult::Return(val)),
-                            EvalResult::Break => break,
-                            EvalResult::Continue => continue,
-                            _ => return Err("Condition in 'for' must be a boolean".to_string()),
-                        }
-                    }
-                    let res = self.eval(&*body)?;
-                    match res {
-                        EvalResult::Break => break,
-                        EvalResult::Continue => {},
-                        EvalResult::Return(val) => return Ok(EvalResult::Return(val)),
-                        _ => {}
-                    }
-                    if let Some(incr) = incr {
-                        self.eval(&*incr)?;
+                for (element, item) in elements.iter().zip(items) {
+                    if !self.match_pattern(element, item, bindings)? {
+                        return Ok(false);
                     }
                 }
-                self.env_stack.pop();
-                Ok(EvalResult::Value(Value::Bool(true)))
-            }
-
-            ASTNode::PostfixUnaryExpression(op, expr) => {
-                if let ASTNode::Identifier(ref name) = **expr {
-                    let mut val = self.lookup(name).ok_or(format!("Undefined variable '{}'", name))?;
-                    match (&op[..], &mut val) {
-                        ("++", Value::Number(n)) => {
-                            let old = *n;
-                            *n += 1;
-                            for env in self.env_stack.iter_mut().rev() {
-                                if let Some((Value::Number(v), _)) = env.get_mut(name) {
-                                    *v = *n;
-                                    break;
-                            }
-                            }
-                            Ok(EvalResult::Value(Value::Number(old)))
-                        }
-                        ("++", Value::Float(n)) => {
-                            let old = *n;
-                            *n += 1.0;
-                            for env in self.env_stack.iter_mut().rev() {
-                                if let Some((Value::Float(v), _)) = env.get_mut(name) {
-                                    *v = *n;
-                                    break;
-                                }
-                            }
-                            Ok(EvalResult::Value(Value::Float(old)))
-                        }
-                        ("--", Value::Number(n)) => {
-                            let old = *n;
-                            *n -= 1;
-                            for env in self.env_stack.iter_mut().rev() {
-                                if let Some((Value::Number(v), _)) = env.get_mut(name) {
-                                    *v = *n;
-                                    break;
-                            }
-                            }
-                            Ok(EvalResult::Value(Value::Number(old)))
-                        }
-                        ("--", Value::Float(n)) => {
-                            let old = *n;
-                            *n -= 1.0;
-                            for env in self.env_stack.iter_mut().rev() {
-                                if let Some((Value::Float(v), _)) = env.get_mut(name) {
-                                    *v = *n;
-                                    break;
-                                }
-                            }
-                            Ok(EvalResult::Value(Value::Float(old)))
-                        }
-                        _ => Err("Unsupported postfix operation".to_string()),
-                    }
-                } else {
-                    Err("Postfix unary operation only supported on variables".to_string())
+                if let Some(Some(name)) = rest {
+                    bindings.insert(name.clone(), (Value::Array(items[elements.len()..].to_vec()), false));
                 }
+                Ok(true)
             }
-
-            ASTNode::AssignmentOp(op, left, right) => {
-                if let ASTNode::Identifier(ref name) = **left {
-                    let right_val = match self.eval(right)? {
-                        EvalResult::Value(v) => v,
-                        _ => return Err("Invalid right value".to_string()),
-                    };
-                    let mut val = self.lookup(name).ok_or(format!("Undefined variable '{}'", name))?;
-                    let new_val = match (op.as_str(), &mut val, right_val) {
-                        ("+=", Value::Number(n), Value::Number(r)) => {
-                            *n += r;
-                            Value::Number(*n)
-                        }
-                        ("-=", Value::Number(n), Value::Number(r)) => {
-                            *n -= r;
-                            Value::Number(*n)
-                        }
-                        ("*=", Value::Number(n), Value::Number(r)) => {
-                            *n *= r;
-                            Value::Number(*n)
-                        }
-                        ("/=", Value::Number(n), Value::Number(r)) => {
-                            *n /= r;
-                            Value::Number(*n)
-                        }
-                        ("+=", Value::Number(n), Value::Float(r)) => {
-                            *n = (*n as f64 + r) as i64;
-                            Value::Number(*n)
-                        }
-                        ("-=", Value::Number(n), Value::Float(r)) => {
-                            *n = (*n as f64 - r) as i64;
-                            Value::Number(*n)
-                        }
-                        ("*=", Value::Number(n), Value::Float(r)) => {
-                            *n = (*n as f64 * r) as i64;
-                            Value::Number(*n)
-                        }
-                        ("/=", Value::Number(n), Value::Float(r)) => {
-                            *n = (*n as f64 / r) as i64;
-                            Value::Number(*n)
-                        }
-                        ("+=", Value::Float(n), Value::Float(r)) => {
-                            *n += r;
-                            Value::Float(*n)
-                        }
-                        ("-=", Value::Float(n), Value::Float(r)) => {
-                            *n -= r;
-                            Value::Float(*n)
-                        }
-                        ("*=", Value::Float(n), Value::Float(r)) => {
-                            *n *= r;
-                            Value::Float(*n)
-                        }
-                        ("/=", Value::Float(n), Value::Float(r)) => {
-                            *n /= r;
-                            Value::Float(*n)
-                        }
-                        ("+=", Value::Float(n), Value::Number(r)) => {
-                            *n += r as f64;
-                            Value::Float(*n)
-                        }
-                        ("-=", Value::Float(n), Value::Number(r)) => {
-                            *n -= r as f64;
-                            Value::Float(*n)
-                        }
-                        ("*=", Value::Float(n), Value::Number(r)) => {
-                            *n *= r as f64;
-                            Value::Float(*n)
-                        }
-                        ("/=", Value::Float(n), Value::Number(r)) => {
-                            *n /= r as f64;
-                            Value::Float(*n)
-                        }
-                        ("+=", Value::String(s), Value::String(r)) => {
-                            s.push_str(&r);
-                            Value::String(s.clone())
-                        }
-                        _ => return Err("Unsupported assignment operator or type".to_string()),
-                    };
-                    for env in self.env_stack.iter_mut().rev() {
-                        if let Some((_, is_const)) = env.get_mut(name) {
-                            *env.get_mut(name).unwrap() = (new_val.clone(), *is_const);
-                            break;
-                        }
+            Pattern::Map(entries) => {
+                if !matches!(value, Value::HashMapV(_) | Value::Struct { .. }) {
+                    return Ok(false);
+                }
+                for (key, entry) in entries {
+                    match pattern_field(value, key) {
+                        Some(field) if self.match_pattern(entry, &field, bindings)? => {}
+                        _ => return Ok(false),
                     }
-                    Ok(EvalResult::Value(new_val))
-                } else {
-                    Err("Assignment operator only supported on variables".to_string())
                 }
+                Ok(true)
             }
-
-            ASTNode::HashMapLiteral(pairs) => {
-                let mut map = std::collections::HashMap::new();
-                for (k, v_expr) in pairs {
-                    let v = match self.eval(v_expr)? {
-                        EvalResult::Value(val) => val,
-                        _ => return Err("Invalid value in hash map literal".to_string()),
-                    };
-                    map.insert(k.clone(), v);
+            Pattern::Or(alternatives) => {
+                for alternative in alternatives {
+                    let mut scratch = Frame::new();
+                    if self.match_pattern(alternative, value, &mut scratch)? {
+                        bindings.extend(scratch);
+                        return Ok(true);
+                    }
                 }
-                Ok(EvalResult::Value(Value::HashMapV(map)))
+                Ok(false)
             }
+        }
+    }
 
-            ASTNode::Break => Ok(EvalResult::Break),
-            
-            ASTNode::Continue => Ok(EvalResult::Continue),
+    /// `name++` / `name--`: updates the variable and returns its old value.
+    pub(crate) fn postfix_update(&mut self, op: PostfixOp, name: &str, slot: Option<Slot>) -> Result<Value, PlutoError> {
+        let old = self.read_mutable(name, slot)?;
+        let new_val = self.postfix_value(op, &old)?;
+        self.assign_variable(name, slot, new_val)?;
+        Ok(old)
+    }
 
-            ASTNode::MatchExpression(expr, arms) => {
-                let value = match self.eval(expr)? {
-                    EvalResult::Value(v) => v,
-                    result => return Ok(result),
-                };
+    /// The value `old` becomes after `++` or `--`.
+    pub(crate) fn postfix_value(&self, op: PostfixOp, old: &Value) -> Result<Value, PlutoError> {
+        match (op, old) {
+            (PostfixOp::Increment, Value::Number(_) | Value::BigInt(_)) => self.eval_binary(old.clone(), BinaryOp::Add, Value::Number(1)),
+            (PostfixOp::Increment, Value::Float(n)) => Ok(Value::Float(n + 1.0)),
+            (PostfixOp::Decrement, Value::Number(_) | Value::BigInt(_)) => self.eval_binary(old.clone(), BinaryOp::Sub, Value::Number(1)),
+            (PostfixOp::Decrement, Value::Float(n)) => Ok(Value::Float(n - 1.0)),
+            _ => Err(PlutoError::type_error("Unsupported postfix operation")),
+        }
+    }
 
-                let mut default_arm = None;
-                if let Some((pattern, result)) = arms.last() {
-                    if let ASTNode::Identifier(name) = &**pattern {
-                        if name == "_" {
-                            default_arm = Some(result);
-                        }
-                    }
-                }
+    /// `name op= value` on a variable, e.g. `total += x`.
+    pub(crate) fn compound_assign(&mut self, op: BinaryOp, name: &str, slot: Option<Slot>, right_val: Value) -> Result<Value, PlutoError> {
+        let mut val = self.read_mutable(name, slot)?;
+        let new_val = self.compound_value(op, &mut val, right_val)?;
+        self.assign_variable(name, slot, new_val.clone())?;
+        Ok(new_val)
+    }
 
-                for (pattern, result) in arms.iter().take(arms.len() - default_arm.is_some() as usize) {
-                    match &**pattern {
-                        ASTNode::NumberLiteral(n) => {
-                            if let Value::Number(val) = value {
-                                if val == *n {
-                                    return self.eval(result);
-                                }
-                            }
-                            continue;
-                        }
-                        ASTNode::StringLiteral(s) => {
-                            if let Value::String(val) = &value {
-                                if val == s {
-                                    return self.eval(result);
-                                }
-                            }
-                            continue;
-                        }
-                        ASTNode::BooleanLiteral(b) => {
-                            if let Value::Bool(val) = value {
-                                if val == *b {
-                                    return self.eval(result);
-                                }
-                            }
-                            continue;
-                        }
-                        ASTNode::NullLiteral => {
-                            if let Value::Null = value {
-                                return self.eval(result);
-                            }
-                            continue;
-                        }
-                        _ => {}
-                    }
+    /// Applies `op=` to `val` in place and returns the result. Unlike the
+    /// binary operator, a `Number` stays a `Number` when a `Float` is added.
+    pub(crate) fn compound_value(&self, op: BinaryOp, val: &mut Value, right_val: Value) -> Result<Value, PlutoError> {
+        let new_val = match (op, &mut *val, right_val) {
+            (
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor | BinaryOp::Shl | BinaryOp::Shr,
+                left @ (Value::Number(_) | Value::BigInt(_)),
+                right @ (Value::Number(_) | Value::BigInt(_)),
+            ) => self.eval_binary(left.clone(), op, right)?,
+            (BinaryOp::Add, Value::Number(n), Value::Float(r)) => {
+                *n = (*n as f64 + r) as i64;
+                Value::Number(*n)
+            }
+            (BinaryOp::Sub, Value::Number(n), Value::Float(r)) => {
+                *n = (*n as f64 - r) as i64;
+                Value::Number(*n)
+            }
+            (BinaryOp::Mul, Value::Number(n), Value::Float(r)) => {
+                *n = (*n as f64 * r) as i64;
+                Value::Number(*n)
+            }
+            (BinaryOp::Div, Value::Number(n), Value::Float(r)) => {
+                *n = (*n as f64 / r) as i64;
+                Value::Number(*n)
+            }
+            (BinaryOp::Add, Value::Float(n), Value::Float(r)) => {
+                *n += r;
+                Value::Float(*n)
+            }
+            (BinaryOp::Sub, Value::Float(n), Value::Float(r)) => {
+                *n -= r;
+                Value::Float(*n)
+            }
+            (BinaryOp::Mul, Value::Float(n), Value::Float(r)) => {
+                *n *= r;
+                Value::Float(*n)
+            }
+            (BinaryOp::Div, Value::Float(n), Value::Float(r)) => {
+                *n /= r;
+                Value::Float(*n)
+            }
+            (BinaryOp::Add, Value::Float(n), Value::Number(r)) => {
+                *n += r as f64;
+                Value::Float(*n)
+            }
+            (BinaryOp::Sub, Value::Float(n), Value::Number(r)) => {
+                *n -= r as f64;
+                Value::Float(*n)
+            }
+            (BinaryOp::Mul, Value::Float(n), Value::Number(r)) => {
+                *n *= r as f64;
+                Value::Float(*n)
+            }
+            (BinaryOp::Div, Value::Float(n), Value::Number(r)) => {
+                *n /= r as f64;
+                Value::Float(*n)
+            }
+            (BinaryOp::Add, Value::String(s), Value::String(r)) => {
+                s.push_str(&r);
+                Value::String(s.clone())
+            }
+            _ => return Err(PlutoError::type_error("Unsupported assignment operator or type")),
+        };
+        *val = new_val.clone();
+        Ok(new_val)
+    }
 
-                    let pattern_val = match self.eval(pattern)? {
-                        EvalResult::Value(v) => v,
-                        result => return Ok(result),
-                    };
+    pub(crate) fn assign_variable(&mut self, name: &str, slot: Option<Slot>, value: Value) -> Result<(), PlutoError> {
+        match self.with_variable(name, slot, |(val, is_const)| if *is_const { Err(()) } else { *val = value; Ok(()) }) {
+            Some(Ok(())) => Ok(()),
+            Some(Err(())) => Err(PlutoError::runtime(format!("Cannot assign to constant '{}'", name))),
+            None => Err(PlutoError::name(format!("Undefined variable '{}'", name))),
+        }
+    }
 
-                    match self.eval_binary(value.clone(), "==", pattern_val)? {
-                        Value::Bool(true) => return self.eval(result),
-                        _ => {}
-                    }
-                }
+    /// The current value of a variable that is about to be updated in place.
+    fn read_mutable(&self, name: &str, slot: Option<Slot>) -> Result<Value, PlutoError> {
+        match self.with_variable(name, slot, |(val, is_const)| (val.clone(), *is_const)) {
+            Some((_, true)) => Err(PlutoError::runtime(format!("Cannot assign to constant '{}'", name))),
+            Some((val, false)) => Ok(val),
+            None => Err(PlutoError::name(format!("Undefined variable '{}'", name))),
+        }
+    }
 
-                if let Some(default_result) = default_arm {
-                    self.eval(default_result)
-                } else {
-                    Ok(EvalResult::Value(Value::Null))
-                }
+    /// `target[index] = value`: a variable's array or map is updated where it
+    /// is stored; a field or element is updated on a copy that is written back.
+    fn assign_index(&mut self, target: &ASTNode, index: Value, value: Value) -> Result<(), PlutoError> {
+        let ASTNodeKind::Identifier(name) = &target.kind else {
+            let mut container = match self.eval(target)? {
+                EvalResult::Value(v) => v,
+                _ => return Err(PlutoError::runtime("Invalid assignment target")),
+            };
+            set_index(&mut container, index, value)?;
+            return match target.kind {
+                ASTNodeKind::IndexAccess(..) | ASTNodeKind::MemberAccess(..) => self.write_back(target, container),
+                _ => Ok(()),
+            };
+        };
+        self.assign_variable_index(name, target.slot, index, value)
+    }
+
+    /// `name[index] = value`, on the array or map where the variable stores it.
+    pub(crate) fn assign_variable_index(&mut self, name: &str, slot: Option<Slot>, index: Value, value: Value) -> Result<(), PlutoError> {
+        let result = self.with_variable(name, slot, |(container, is_const)| {
+            if *is_const {
+                return Err(PlutoError::runtime(format!("Cannot assign to constant '{}'", name)));
             }
+            set_index(container, index, value)
+        });
+        result.unwrap_or_else(|| Err(PlutoError::name(format!("Undefined variable '{}'", name))))
+    }
+
+    /// `object.field = value`: updates a copy of the struct and writes it back
+    /// through `object`, which may itself be a field or an index (`a[0].pos.x = 1`).
+    fn assign_member(&mut self, object: &ASTNode, field: &str, value: Value) -> Result<(), PlutoError> {
+        let mut container = match self.eval(object)? {
+            EvalResult::Value(v) => v,
+            _ => return Err(PlutoError::runtime("Invalid assignment target")),
+        };
+        set_field(&mut container, field, value)?;
+        self.write_back(object, container)
+    }
 
-            ASTNode::Range(start, end, inclusive) => {
-                let start_val = match self.eval(start)? {
+    fn write_back(&mut self, target: &ASTNode, value: Value) -> Result<(), PlutoError> {
+        match &target.kind {
+            ASTNodeKind::Identifier(name) => self.assign_variable(name, target.slot, value),
+            ASTNodeKind::MemberAccess(object, field) => self.assign_member(object, field, value),
+            ASTNodeKind::IndexAccess(object, index) => {
+                let mut container = match self.eval(object)? {
                     EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
+                    _ => return Err(PlutoError::runtime("Invalid assignment target")),
                 };
-                let end_val = match self.eval(end)? {
+                let index = match self.eval(index)? {
                     EvalResult::Value(v) => v,
-                    EvalResult::Return(v) => return Ok(EvalResult::Return(v)),
-                    EvalResult::Break => return Ok(EvalResult::Break),
-                    EvalResult::Continue => return Ok(EvalResult::Continue),
+                    _ => return Err(PlutoError::runtime("Invalid assignment target")),
                 };
+                set_index(&mut container, index, value)?;
+                self.write_back(object, container)
+            }
+            _ => Err(PlutoError::runtime("Invalid assignment target")),
+        }
+    }
 
-                match (start_val, end_val) {
-                    (Value::Number(start), Value::Number(end)) => {
-                        let range: Vec<Value> = if *inclusive {
-                            (start..=end).map(Value::Number).collect()
-                        } else {
-                            (start..end).map(Value::Number).collect()
-                        };
-                        Ok(EvalResult::Value(Value::Array(range)))
-                    }
-                    _ => Err("Range bounds must be numbers".to_string())
+    /// One name takes the whole item; several names unpack an array of the same length.
+    pub(crate) fn bind_loop_variables(bindings: &[String], item: Value) -> Result<Frame, PlutoError> {
+        let mut scope = Frame::new();
+        if let [name] = bindings {
+            scope.insert(name.clone(), (item, false));
+            return Ok(scope);
+        }
+        let values = Self::unpack_loop_item(bindings.len(), item)?;
+        scope.extend(bindings.iter().cloned().zip(values.into_iter().map(|value| (value, false))));
+        Ok(scope)
+    }
+
+    /// The values of `count` loop variables unpacked from an array item.
+    pub(crate) fn unpack_loop_item(count: usize, item: Value) -> Result<Vec<Value>, PlutoError> {
+        match item {
+            Value::Array(values) if values.len() == count => Ok(values),
+            other => Err(PlutoError::type_error(format!(
+                "Cannot unpack {} into {} loop variables",
+                other.type_name(),
+                count
+            ))),
+        }
+    }
+
+    /// Integer arithmetic once either operand is a `BigInt`.
+    fn eval_big_binary(&self, a: BigInt, op: BinaryOp, b: BigInt) -> Result<Value, PlutoError> {
+        let result = match op {
+            BinaryOp::Add => a.add(&b),
+            BinaryOp::Sub => a.sub(&b),
+            BinaryOp::Mul => a.mul(&b),
+            BinaryOp::Div => a.div_rem(&b).ok_or_else(|| PlutoError::runtime("Division by zero"))?.0,
+            BinaryOp::Rem => a.div_rem(&b).ok_or_else(|| PlutoError::runtime("Modulo by zero"))?.1,
+            BinaryOp::Eq => return Ok(Value::Bool(a == b)),
+            BinaryOp::Ne => return Ok(Value::Bool(a != b)),
+            BinaryOp::Lt => return Ok(Value::Bool(a < b)),
+            BinaryOp::Gt => return Ok(Value::Bool(a > b)),
+            BinaryOp::Le => return Ok(Value::Bool(a <= b)),
+            BinaryOp::Ge => return Ok(Value::Bool(a >= b)),
+            BinaryOp::Shl | BinaryOp::Shr => {
+                let shift = match b.to_i64() {
+                    Some(shift) if shift < 0 => return Err(PlutoError::runtime("Negative shift amount")),
+                    Some(shift) if shift <= u32::MAX as i64 => shift as u32,
+                    _ => return Err(PlutoError::runtime("Shift amount too large")),
+                };
+                let power = BigInt::from(2).pow(shift);
+                if op == BinaryOp::Shl {
+                    a.mul(&power)
+                } else {
+                    // shifting right rounds toward negative infinity
+                    let (quotient, remainder) = a.div_rem(&power).unwrap();
+                    if remainder < BigInt::from(0) { quotient.sub(&BigInt::from(1)) } else { quotient }
                 }
             }
+            BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor => {
+                return Err(PlutoError::type_error(format!("Operator '{}' needs integers that fit in 64 bits", op)));
+            }
+            _ => return Err(PlutoError::type_error(format!("Unknown number operator: {}", op))),
+        };
+        Ok(result.to_value())
+    }
+
+    pub(crate) fn eval_unary(&self, op: UnaryOp, value: Value) -> Result<Value, PlutoError> {
+        match (op, value) {
+            (UnaryOp::Not, value) => Ok(Value::Bool(!self.is_truthy(&value))),
+            (UnaryOp::BitNot, Value::Number(n)) => Ok(Value::Number(!n)),
+            (UnaryOp::BitNot, Value::BigInt(n)) => Ok(BigInt::from(-1).sub(&n).to_value()),
+            _ => Err(PlutoError::type_error("Unsupported unary operation")),
         }
     }
 
-    fn eval_binary(&self, left: Value, op: &str, right: Value) -> Result<Value, String> {
-        if op == "?:" {
-            return Ok(if self.is_truthy(&left) { left } else { right });
+    pub(crate) fn eval_binary(&self, left: Value, op: BinaryOp, right: Value) -> Result<Value, PlutoError> {
+        if let Some(result) = self.short_circuit(op, &left) {
+            return Ok(result);
+        }
+        match op {
+            BinaryOp::And | BinaryOp::Or => return Ok(Value::Bool(self.is_truthy(&right))),
+            BinaryOp::Elvis => return Ok(right),
+            _ => {}
         }
         match (left, right) {
+            (Value::Number(_), Value::Number(0)) if op == BinaryOp::Div || op == BinaryOp::Rem => {
+                Err(PlutoError::runtime(if op == BinaryOp::Div { "Division by zero" } else { "Modulo by zero" }))
+            }
             (Value::Number(a), Value::Number(b)) => match op {
-                "+" => Ok(Value::Number(a + b)),
-                "-" => Ok(Value::Number(a - b)),
-                "*" => Ok(Value::Number(a * b)),
-                "/" => Ok(Value::Number(a / b)),
-                "%" => Ok(Value::Number(a % b)),
-                "==" => Ok(Value::Bool(a == b)),
-                "!=" => Ok(Value::Bool(a != b)),
-                "<" => Ok(Value::Bool(a < b)),
-                ">" => Ok(Value::Bool(a > b)),
-                "<=" => Ok(Value::Bool(a <= b)),
-                ">=" => Ok(Value::Bool(a >= b)),
-                _ => Err(format!("Unknown number operator: {}", op)),
+                BinaryOp::Add => self.integer_result(a.checked_add(b), || BigInt::from(a).add(&BigInt::from(b))),
+                BinaryOp::Sub => self.integer_result(a.checked_sub(b), || BigInt::from(a).sub(&BigInt::from(b))),
+                BinaryOp::Mul => self.integer_result(a.checked_mul(b), || BigInt::from(a).mul(&BigInt::from(b))),
+                // only i64::MIN / -1 overflows
+                BinaryOp::Div => self.integer_result(a.checked_div(b), || BigInt::from(a).div_rem(&BigInt::from(b)).unwrap().0),
+                BinaryOp::Rem => Ok(Value::Number(a.checked_rem(b).unwrap_or(0))),
+                BinaryOp::BitAnd => Ok(Value::Number(a & b)),
+                BinaryOp::BitOr => Ok(Value::Number(a | b)),
+                BinaryOp::BitXor => Ok(Value::Number(a ^ b)),
+                BinaryOp::Shl | BinaryOp::Shr if b < 0 => Err(PlutoError::runtime("Negative shift amount")),
+                BinaryOp::Shl => self.integer_result(
+                    u32::try_from(b).ok().and_then(|shift| a.checked_shl(shift)).filter(|r| r >> b == a),
+                    || BigInt::from(a).mul(&BigInt::from(2).pow(b.min(u32::MAX as i64) as u32)),
+                ),
+                BinaryOp::Shr => Ok(Value::Number(a >> b.min(63))),
+                BinaryOp::Eq => Ok(Value::Bool(a == b)),
+                BinaryOp::Ne => Ok(Value::Bool(a != b)),
+                BinaryOp::Lt => Ok(Value::Bool(a < b)),
+                BinaryOp::Gt => Ok(Value::Bool(a > b)),
+                BinaryOp::Le => Ok(Value::Bool(a <= b)),
+                BinaryOp::Ge => Ok(Value::Bool(a >= b)),
+                _ => Err(PlutoError::type_error(format!("Unknown number operator: {}", op))),
             },
+            (left @ (Value::Number(_) | Value::BigInt(_)), right @ (Value::Number(_) | Value::BigInt(_))) => {
+                self.eval_big_binary(BigInt::from_value(&left).unwrap(), op, BigInt::from_value(&right).unwrap())
+            }
+            (Value::BigInt(a), right @ Value::Float(_)) => self.eval_binary(Value::Float(a.to_f64()), op, right),
+            (left @ Value::Float(_), Value::BigInt(b)) => self.eval_binary(left, op, Value::Float(b.to_f64())),
+            (Value::BigInt(a), Value::String(b)) if op == BinaryOp::Add => Ok(Value::String(a.to_string() + &b)),
+            (Value::String(a), Value::BigInt(b)) if op == BinaryOp::Add => Ok(Value::String(a + &b.to_string())),
             (Value::Float(a), Value::Float(b)) => match op {
-                "+" => Ok(Value::Float(a + b)),
-                "-" => Ok(Value::Float(a - b)),
-                "*" => Ok(Value::Float(a * b)),
-                "/" => Ok(Value::Float(a / b)),
-                "%" => Ok(Value::Float(a % b)),
-                "==" => Ok(Value::Bool(a == b)),
-                "!=" => Ok(Value::Bool(a != b)),
-                "<" => Ok(Value::Bool(a < b)),
-                ">" => Ok(Value::Bool(a > b)),
-                "<=" => Ok(Value::Bool(a <= b)),
-                ">=" => Ok(Value::Bool(a >= b)),
-                _ => Err(format!("Unknown float operator: {}", op)),
+                BinaryOp::Add => Ok(Value::Float(a + b)),
+                BinaryOp::Sub => Ok(Value::Float(a - b)),
+                BinaryOp::Mul => Ok(Value::Float(a * b)),
+                BinaryOp::Div => Ok(Value::Float(a / b)),
+                BinaryOp::Rem => Ok(Value::Float(a % b)),
+                BinaryOp::Eq => Ok(Value::Bool(a == b)),
+                BinaryOp::Ne => Ok(Value::Bool(a != b)),
+                BinaryOp::Lt => Ok(Value::Bool(a < b)),
+                BinaryOp::Gt => Ok(Value::Bool(a > b)),
+                BinaryOp::Le => Ok(Value::Bool(a <= b)),
+                BinaryOp::Ge => Ok(Value::Bool(a >= b)),
+                _ => Err(PlutoError::type_error(format!("Unknown float operator: {}", op))),
             },
             (Value::String(a), Value::String(b)) => match op {
-                "+" => Ok(Value::String(a + &b)),
-                "==" => Ok(Value::Bool(a == b)),
-                "!=" => Ok(Value::Bool(a != b)),
-                _ => Err(format!("Unknown string operator: {}", op)),
+                BinaryOp::Add => Ok(Value::String(a + &b)),
+                BinaryOp::Eq => Ok(Value::Bool(a == b)),
+                BinaryOp::Ne => Ok(Value::Bool(a != b)),
+                _ => Err(PlutoError::type_error(format!("Unknown string operator: {}", op))),
             },
             (Value::Number(a), Value::Float(b)) => match op {
-                "+" => Ok(Value::Float(a as f64 + b)),
-                "-" => Ok(Value::Float(a as f64 - b)),
-                "*" => Ok(Value::Float(a as f64 * b)),
-                "/" => Ok(Value::Float(a as f64 / b)),
-                "%" => Ok(Value::Float(a as f64 % b)),
-                "==" => Ok(Value::Bool((a as f64) == b)),
-                "!=" => Ok(Value::Bool((a as f64) != b)),
-                "<" => Ok(Value::Bool((a as f64) < b)),
-                ">" => Ok(Value::Bool((a as f64) > b)),
-                "<=" => Ok(Value::Bool((a as f64) <= b)),
-                ">=" => Ok(Value::Bool((a as f64) >= b)),
-                _ => Err(format!("Unknown mixed operator: {}", op)),
+                BinaryOp::Add => Ok(Value::Float(a as f64 + b)),
+                BinaryOp::Sub => Ok(Value::Float(a as f64 - b)),
+                BinaryOp::Mul => Ok(Value::Float(a as f64 * b)),
+                BinaryOp::Div => Ok(Value::Float(a as f64 / b)),
+                BinaryOp::Rem => Ok(Value::Float(a as f64 % b)),
+                BinaryOp::Eq => Ok(Value::Bool((a as f64) == b)),
+                BinaryOp::Ne => Ok(Value::Bool((a as f64) != b)),
+                BinaryOp::Lt => Ok(Value::Bool((a as f64) < b)),
+                BinaryOp::Gt => Ok(Value::Bool((a as f64) > b)),
+                BinaryOp::Le => Ok(Value::Bool((a as f64) <= b)),
+                BinaryOp::Ge => Ok(Value::Bool((a as f64) >= b)),
+                _ => Err(PlutoError::type_error(format!("Unknown mixed operator: {}", op))),
             },
             (Value::Float(a), Value::Number(b)) => match op {
-                "+" => Ok(Value::Float(a + b as f64)),
-                "-" => Ok(Value::Float(a - b as f64)),
-                "*" => Ok(Value::Float(a * b as f64)),
-                "/" => Ok(Value::Float(a / b as f64)),
-                "%" => Ok(Value::Float(a % b as f64)),
-                "==" => Ok(Value::Bool(a == b as f64)),
-                "!=" => Ok(Value::Bool(a != b as f64)),
-                "<" => Ok(Value::Bool(a < b as f64)),
-                ">" => Ok(Value::Bool(a > b as f64)),
-                "<=" => Ok(Value::Bool(a <= b as f64)),
-                ">=" => Ok(Value::Bool(a >= b as f64)),
-                _ => Err(format!("Unknown mixed operator: {}", op)),
+                BinaryOp::Add => Ok(Value::Float(a + b as f64)),
+                BinaryOp::Sub => Ok(Value::Float(a - b as f64)),
+                BinaryOp::Mul => Ok(Value::Float(a * b as f64)),
+                BinaryOp::Div => Ok(Value::Float(a / b as f64)),
+                BinaryOp::Rem => Ok(Value::Float(a % b as f64)),
+                BinaryOp::Eq => Ok(Value::Bool(a == b as f64)),
+                BinaryOp::Ne => Ok(Value::Bool(a != b as f64)),
+                BinaryOp::Lt => Ok(Value::Bool(a < b as f64)),
+                BinaryOp::Gt => Ok(Value::Bool(a > b as f64)),
+                BinaryOp::Le => Ok(Value::Bool(a <= b as f64)),
+                BinaryOp::Ge => Ok(Value::Bool(a >= b as f64)),
+                _ => Err(PlutoError::type_error(format!("Unknown mixed operator: {}", op))),
             },
             (Value::String(a), Value::Number(b)) => match op {
-                "*" => Ok(Value::String(a.repeat(b as usize))),
-                "+" => Ok(Value::String(a + &b.to_string())),
-                "==" => Ok(Value::Bool(false)),
-                _ => Err(format!("Unknown string-number operator: {}", op)),
+                BinaryOp::Mul => Ok(Value::String(a.repeat(b as usize))),
+                BinaryOp::Add => Ok(Value::String(a + &b.to_string())),
+                BinaryOp::Eq => Ok(Value::Bool(false)),
+                _ => Err(PlutoError::type_error(format!("Unknown string-number operator: {}", op))),
             },
             (Value::Number(a), Value::String(b)) => match op {
-                "+" => Ok(Value::String(a.to_string() + &b)),
-                "==" => Ok(Value::Bool(false)),
-                _ => Err(format!("Unknown number-string operator: {}", op)),
+                BinaryOp::Add => Ok(Value::String(a.to_string() + &b)),
+                BinaryOp::Eq => Ok(Value::Bool(false)),
+                _ => Err(PlutoError::type_error(format!("Unknown number-string operator: {}", op))),
             },
             (Value::String(a), Value::Float(b)) => match op {
-                "*" => Ok(Value::String(a.repeat(b as usize))),
-                "+" => Ok(Value::String(a + &b.to_string())),
-                "==" => Ok(Value::Bool(false)),
-                _ => Err(format!("Unknown string-float operator: {}", op)),
+                BinaryOp::Mul => Ok(Value::String(a.repeat(b as usize))),
+                BinaryOp::Add => Ok(Value::String(a + &b.to_string())),
+                BinaryOp::Eq => Ok(Value::Bool(false)),
+                _ => Err(PlutoError::type_error(format!("Unknown string-float operator: {}", op))),
             },
             (Value::Float(a), Value::String(b)) => match op {
-                "+" => Ok(Value::String(a.to_string() + &b)),
-                "==" => Ok(Value::Bool(false)),
-                _ => Err(format!("Unknown float-string operator: {}", op)),
+                BinaryOp::Add => Ok(Value::String(a.to_string() + &b)),
+                BinaryOp::Eq => Ok(Value::Bool(false)),
+                _ => Err(PlutoError::type_error(format!("Unknown float-string operator: {}", op))),
             },
             (Value::Bool(a), Value::Bool(b)) => match op {
-                "&&" => Ok(Value::Bool(a && b)),
-                "||" => Ok(Value::Bool(a || b)),
-                "==" => Ok(Value::Bool(a == b)),
-                "!=" => Ok(Value::Bool(a != b)),
-                _ => Err(format!("Unknown boolean operator: {}", op)),
+                BinaryOp::Eq => Ok(Value::Bool(a == b)),
+                BinaryOp::Ne => Ok(Value::Bool(a != b)),
+                _ => Err(PlutoError::type_error(format!("Unknown boolean operator: {}", op))),
             },
+            (left @ Value::Range { .. }, right @ Value::Range { .. }) => {
+                let (a, b) = (IntRange::from_value(&left).unwrap(), IntRange::from_value(&right).unwrap());
+                let same = a.len() == b.len() && (a.is_empty() || (a.start == b.start && (a.len() == 1 || a.step == b.step)));
+                match op {
+                    BinaryOp::Eq => Ok(Value::Bool(same)),
+                    BinaryOp::Ne => Ok(Value::Bool(!same)),
+                    _ => Err(PlutoError::type_error(format!("Unknown range operator: {}", op))),
+                }
+            }
+            (Value::Struct { def: da, fields: fa }, Value::Struct { def: db, fields: fb }) => {
+                let mut same = Rc::ptr_eq(&da, &db);
+                for (a, b) in fa.into_iter().zip(fb) {
+                    if !same {
+                        break;
+                    }
+                    same = matches!(self.eval_binary(a, BinaryOp::Eq, b)?, Value::Bool(true));
+                }
+                match op {
+                    BinaryOp::Eq => Ok(Value::Bool(same)),
+                    BinaryOp::Ne => Ok(Value::Bool(!same)),
+                    _ => Err(PlutoError::type_error(format!("Unknown struct operator: {}", op))),
+                }
+            }
             (Value::Array(a), Value::Array(b)) => match op {
-                "+" => Ok(Value::Array(a.into_iter().chain(b.into_iter()).collect())),
-                _ => Err(format!("Unknown array operator: {}", op)),
+                BinaryOp::Add => Ok(Value::Array(a.into_iter().chain(b).collect())),
+                BinaryOp::Eq | BinaryOp::Ne => {
+                    let same = a.len() == b.len()
+                        && a.into_iter().zip(b).all(|(x, y)| matches!(self.eval_binary(x, BinaryOp::Eq, y), Ok(Value::Bool(true))));
+                    Ok(Value::Bool(same == (op == BinaryOp::Eq)))
+                }
+                _ => Err(PlutoError::type_error(format!("Unknown array operator: {}", op))),
             },
-            _ => Err("Type error: incompatible types for binary operation".to_string()),
+            // anything can be compared with null, which only equals itself
+            (left, right) if matches!(op, BinaryOp::Eq | BinaryOp::Ne) && (matches!(left, Value::Null) || matches!(right, Value::Null)) => {
+                let same = matches!((left, right), (Value::Null, Value::Null));
+                Ok(Value::Bool(same == (op == BinaryOp::Eq)))
+            }
+            _ => Err(PlutoError::type_error("Type error: incompatible types for binary operation")),
         }
     }
-}
\ No newline at end of file
+}
+
+/// Stores `value` at `container[index]`.
+pub(crate) fn set_index(container: &mut Value, index: Value, value: Value) -> Result<(), PlutoError> {
+    match (container, index) {
+        (Value::Array(arr), Value::Number(i)) => {
+            let slot = usize::try_from(i).ok().and_then(|i| arr.get_mut(i));
+            *slot.ok_or_else(|| PlutoError::index("Array index out of bounds"))? = value;
+        }
+        (Value::HashMapV(map), Value::String(key)) => {
+            map.insert(key, value);
+        }
+        (Value::Array(_), _) => return Err(PlutoError::type_error("Assignment only supported for arrays with integer indices")),
+        (Value::HashMapV(_), _) => return Err(PlutoError::type_error("Assignment only supported for hash maps with string keys")),
+        (other, _) => return Err(PlutoError::type_error(format!("Cannot assign to an index of a {}", other.type_name()))),
+    }
+    Ok(())
+}
+
+pub(crate) fn set_field(container: &mut Value, field: &str, value: Value) -> Result<(), PlutoError> {
+    match container {
+        Value::Struct { def, fields } => fields[def.field_index(field)?] = value,
+        other => return Err(PlutoError::type_error(format!("Cannot set field '{}' on {}", field, other.type_name()))),
+    }
+    Ok(())
+}
+
+/// The error `throw value` raises: a caught error is rethrown as it was,
+/// anything else becomes a `User` error with its printed form.
+pub(crate) fn thrown(value: Value) -> PlutoError {
+    match value {
+        Value::Error(err) => *err,
+        other => PlutoError::new(ErrorKind::User, other.to_string()),
+    }
+}
+
+/// The value of `start..end` or `start..=end`.
+pub(crate) fn range(start: Value, end: Value, inclusive: bool) -> Result<Value, PlutoError> {
+    match (start, end) {
+        (Value::Number(start), Value::Number(end)) => Ok(Value::Range { start, end, step: 1, inclusive }),
+        _ => Err(PlutoError::type_error("Range bounds must be numbers")),
+    }
+}
+
+/// Whether `value` falls in the range pattern `start..end` (or `..=`).
+pub(crate) fn in_range_pattern(value: &Value, start: Value, end: Value, inclusive: bool) -> Result<bool, PlutoError> {
+    let (Value::Number(start), Value::Number(end)) = (start, end) else {
+        return Err(PlutoError::type_error("Range pattern bounds must be numbers"));
+    };
+    let n = match value {
+        Value::Number(n) => *n as f64,
+        Value::Float(f) => *f,
+        _ => return Ok(false),
+    };
+    let below_end = if inclusive { n <= end as f64 } else { n < end as f64 };
+    Ok(n >= start as f64 && below_end)
+}
+
+/// The entry `key` of a map, or the field `key` of a struct, that a map pattern matches.
+pub(crate) fn pattern_field(value: &Value, key: &str) -> Option<Value> {
+    match value {
+        Value::HashMapV(map) => map.get(key).cloned(),
+        Value::Struct { def, fields } => def.field_index(key).ok().map(|i| fields[i].clone()),
+        _ => None,
+    }
+}
+
+/// Address of a local of the calling function, a measure of how deep the
+/// native stack is at that point.
+#[inline(never)]
+fn stack_position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
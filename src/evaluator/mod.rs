@@ -1 +1,8 @@
-pub mod evaluator;
\ No newline at end of file
+pub mod bigint;
+pub mod cycles;
+pub mod evaluator;
+pub mod module_loader;
+pub mod ordered_map;
+pub mod range;
+pub mod scope;
+pub mod structs;
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::error::PlutoError;
+use crate::evaluator::evaluator::Value;
+
+/// Bookkeeping shared by every evaluator of one program: which files are
+/// loaded, their exports, and the chain of imports currently in progress.
+///
+/// Spans refer to files by id: `0` is the main program, module `n` is
+/// `files[n - 1]`.
+#[derive(Default)]
+pub struct ModuleLoader {
+    main: Option<PathBuf>,
+    files: Vec<PathBuf>,
+    cache: HashMap<PathBuf, Value>,
+    loading: Vec<PathBuf>,
+}
+
+impl ModuleLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the path of the main program so its imports resolve next to it.
+    pub fn set_main(&mut self, path: &Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.loading = vec![path.clone()];
+        self.main = Some(path);
+    }
+
+    pub fn path_of(&self, file: usize) -> Option<&Path> {
+        match file {
+            0 => self.main.as_deref(),
+            n => self.files.get(n - 1).map(PathBuf::as_path),
+        }
+    }
+
+    /// Path shown in error messages; `None` for the main program.
+    pub fn display_name(&self, file: usize) -> Option<String> {
+        match file {
+            0 => None,
+            n => self.files.get(n - 1).map(|p| p.display().to_string()),
+        }
+    }
+
+    /// Resolves `target` relative to the directory of the importing file.
+    pub fn resolve(&self, from_file: usize, target: &str) -> Result<PathBuf, PlutoError> {
+        let base = self
+            .path_of(from_file)
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        base.join(target)
+            .canonicalize()
+            .map_err(|_| PlutoError::io(format!("Cannot find module '{}'", target)))
+    }
+
+    pub fn cached(&self, path: &Path) -> Option<Value> {
+        self.cache.get(path).cloned()
+    }
+
+    /// Marks `path` as being loaded, failing if it is already part of the
+    /// current import chain. Returns the file id for its spans.
+    pub fn begin(&mut self, path: &Path) -> Result<usize, PlutoError> {
+        if let Some(pos) = self.loading.iter().position(|p| p == path) {
+            let chain: Vec<String> = self.loading[pos..]
+                .iter()
+                .chain(std::iter::once(&path.to_path_buf()))
+                .map(|p| short_name(p))
+                .collect();
+            return Err(PlutoError::import(format!("Import cycle detected: {}", chain.join(" -> "))));
+        }
+        self.loading.push(path.to_path_buf());
+        let id = match self.files.iter().position(|p| p == path) {
+            Some(i) => i + 1,
+            None => {
+                self.files.push(path.to_path_buf());
+                self.files.len()
+            }
+        };
+        Ok(id)
+    }
+
+    /// Pops `path` off the import chain, caching its exports on success.
+    pub fn finish(&mut self, path: &Path, exports: Option<Value>) {
+        self.loading.pop();
+        if let Some(module) = exports {
+            self.cache.insert(path.to_path_buf(), module);
+        }
+    }
+}
+
+fn short_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// The name `import "path"` binds: the alias if given, else the file stem.
+pub fn import_binding(path: &str, alias: Option<&String>) -> String {
+    match alias {
+        Some(alias) => alias.clone(),
+        None => Path::new(path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string()),
+    }
+}
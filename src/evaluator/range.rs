@@ -0,0 +1,106 @@
+use crate::evaluator::evaluator::Value;
+
+/// Arithmetic behind `Value::Range`; elements are computed on demand,
+/// never stored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntRange {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+    pub inclusive: bool,
+}
+
+impl IntRange {
+    pub fn new(start: i64, end: i64, step: i64, inclusive: bool) -> Self {
+        IntRange { start, end, step, inclusive }
+    }
+
+    pub fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Range { start, end, step, inclusive } => Some(IntRange::new(*start, *end, *step, *inclusive)),
+            _ => None,
+        }
+    }
+
+    pub fn to_value(self) -> Value {
+        Value::Range {
+            start: self.start,
+            end: self.end,
+            step: self.step,
+            inclusive: self.inclusive,
+        }
+    }
+
+    /// Up to 2^64 elements, one more than an `i64` holds.
+    pub fn len(&self) -> i128 {
+        let (start, end, step) = (self.start as i128, self.end as i128, self.step as i128);
+        let distance = if step > 0 {
+            let bound = if self.inclusive { end + 1 } else { end };
+            bound - start
+        } else {
+            let bound = if self.inclusive { end - 1 } else { end };
+            start - bound
+        };
+        if distance <= 0 {
+            0
+        } else {
+            let step = step.abs();
+            (distance + step - 1) / step
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: i64) -> Option<i64> {
+        if index < 0 || index as i128 >= self.len() {
+            return None;
+        }
+        Some((self.start as i128 + index as i128 * self.step as i128) as i64)
+    }
+
+    pub fn contains(&self, n: i64) -> bool {
+        let offset = n as i128 - self.start as i128;
+        if offset % self.step as i128 != 0 {
+            return false;
+        }
+        let index = offset / self.step as i128;
+        index >= 0 && index < self.len()
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = i64> {
+        let len = self.len();
+        let mut index = 0i128;
+        std::iter::from_fn(move || {
+            if index >= len {
+                return None;
+            }
+            let value = self.start as i128 + index * self.step as i128;
+            index += 1;
+            Some(value as i64)
+        })
+    }
+
+    pub fn rev(self) -> Self {
+        match self.len() {
+            0 => IntRange::new(self.start, self.start, 1, false),
+            len => {
+                let last = self.start as i128 + (len - 1) * self.step as i128;
+                IntRange::new(last as i64, self.start, self.step.saturating_neg(), true)
+            }
+        }
+    }
+
+    /// The elements at `indices`, still as a range, or `None` if an index
+    /// is out of bounds. Indices move in one direction, so checking the
+    /// first and last is enough.
+    pub fn slice(&self, indices: IntRange) -> Option<Self> {
+        let (Some(first), Some(last)) = (indices.get(0), indices.rev().get(0)) else {
+            return Some(IntRange::new(self.start, self.start, 1, false));
+        };
+        let (start, end) = (self.get(first)?, self.get(last)?);
+        let step = if first == last { 1 } else { self.step.checked_mul(indices.step)? };
+        Some(IntRange::new(start, end, step, true))
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use crate::evaluator::evaluator::Value;
+
+/// The variables of one scope. Each name gets the next slot when first
+/// declared, so the resolver can predict where a local lives and the
+/// evaluator can reach it by index instead of hashing its name.
+#[derive(Debug, Default, Clone)]
+pub struct Frame {
+    names: Vec<String>,
+    slots: Vec<(Value, bool)>,
+    index: HashMap<String, usize>,
+    /// Registered with `Cycles`, having been captured by a closure.
+    captured: bool,
+}
+
+impl Frame {
+    pub fn new() -> Self {
+        Frame::default()
+    }
+
+    /// Declares `name`, reusing its slot if it is already declared here.
+    pub fn insert(&mut self, name: String, entry: (Value, bool)) {
+        match self.index.get(&name) {
+            Some(&slot) => self.slots[slot] = entry,
+            None => {
+                self.index.insert(name.clone(), self.slots.len());
+                self.names.push(name);
+                self.slots.push(entry);
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&(Value, bool)> {
+        self.index.get(name).map(|&slot| &self.slots[slot])
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut (Value, bool)> {
+        self.index.get(name).map(|&slot| &mut self.slots[slot])
+    }
+
+    /// A copy of the value of `name`.
+    pub fn value(&self, name: &str) -> Option<Value> {
+        self.get(name).map(|(value, _)| value.clone())
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.index.contains_key(name)
+    }
+
+    /// The variable in `slot`, where the resolver placed `name`. `None` when
+    /// the declaration has not run yet, or when the slot holds another name
+    /// because the scope changed since the program was resolved (e.g. a
+    /// native function added a global); the caller then searches by name.
+    pub fn get_at(&self, name: &str, slot: usize) -> Option<&(Value, bool)> {
+        (self.names.get(slot)? == name).then(|| &self.slots[slot])
+    }
+
+    pub fn get_mut_at(&mut self, name: &str, slot: usize) -> Option<&mut (Value, bool)> {
+        (self.names.get(slot)? == name).then(|| &mut self.slots[slot])
+    }
+
+    /// Marks the scope as captured by a closure; true the first time.
+    pub(crate) fn mark_captured(&mut self) -> bool {
+        !std::mem::replace(&mut self.captured, true)
+    }
+
+    /// Variables in declaration order.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &(Value, bool))> {
+        self.names.iter().zip(&self.slots)
+    }
+}
+
+impl Extend<(String, (Value, bool))> for Frame {
+    fn extend<I: IntoIterator<Item = (String, (Value, bool))>>(&mut self, iter: I) {
+        for (name, entry) in iter {
+            self.insert(name, entry);
+        }
+    }
+}
+
+impl FromIterator<(String, (Value, bool))> for Frame {
+    fn from_iter<I: IntoIterator<Item = (String, (Value, bool))>>(iter: I) -> Self {
+        let mut frame = Frame::new();
+        frame.extend(iter);
+        frame
+    }
+}
+
+impl IntoIterator for Frame {
+    type Item = (String, (Value, bool));
+    type IntoIter = std::iter::Zip<std::vec::IntoIter<String>, std::vec::IntoIter<(Value, bool)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.names.into_iter().zip(self.slots)
+    }
+}
@@ -0,0 +1,37 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::error::error::PlutoError;
+use crate::evaluator::evaluator::Value;
+use crate::parser::ast::Params;
+
+/// A `struct` declaration. Instances share it through an `Rc`, so methods
+/// added by a later `impl` block are visible to existing instances.
+#[derive(Debug)]
+pub struct StructDef {
+    pub name: String,
+    /// Field names in declaration order, with optional default expressions.
+    pub fields: Params,
+    pub methods: RefCell<HashMap<String, Value>>,
+}
+
+impl StructDef {
+    pub fn new(name: String, fields: Params) -> Self {
+        StructDef {
+            name,
+            fields,
+            methods: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn field_index(&self, field: &str) -> Result<usize, PlutoError> {
+        self.fields
+            .iter()
+            .position(|(name, _)| name == field)
+            .ok_or_else(|| PlutoError::attribute(format!("'{}' has no field '{}'", self.name, field)))
+    }
+
+    pub fn method(&self, name: &str) -> Option<Value> {
+        self.methods.borrow().get(name).cloned()
+    }
+}
@@ -0,0 +1,149 @@
+use std::path::Path;
+
+use crate::error::error::PlutoError;
+use crate::evaluator::evaluator::{DEFAULT_MAX_DEPTH, DEFAULT_MAX_STACK, Evaluator, NativeFunction, Overflow, Value};
+use crate::lexer::tokenizer::tokenize;
+use crate::parser::ast::ASTNode;
+use crate::parser::parser::Parser;
+use crate::resolver::resolver::resolve;
+use crate::vm::vm::Vm;
+
+/// How `run_source` executes a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// Walks the AST directly.
+    #[default]
+    TreeWalker,
+    /// Compiles to bytecode and runs it on a stack VM.
+    Vm,
+}
+
+/// Embedding entry point: owns a global environment that persists
+/// between calls, so globals defined by one script are visible to the next.
+pub struct Interpreter {
+    evaluator: Evaluator,
+    engine: Engine,
+    vm: Vm,
+    max_depth: usize,
+    max_stack: usize,
+    overflow: Overflow,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Interpreter {
+    fn drop(&mut self) {
+        self.evaluator.release();
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter {
+            evaluator: Evaluator::new(),
+            engine: Engine::default(),
+            vm: Vm::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_stack: DEFAULT_MAX_STACK,
+            overflow: Overflow::default(),
+        }
+    }
+
+    /// Selects the engine used by `run_source` and `run_script`. Both give
+    /// the same results; `eval_expr` always uses the tree walker.
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+    }
+
+    /// Limits how deeply Pluto calls may nest; going deeper raises a
+    /// catchable `StackOverflowError`. Tail calls do not count. Every level
+    /// also uses native stack, which `set_max_stack` limits separately, so
+    /// a deep limit may need a thread with a larger stack as well.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+        self.evaluator.set_max_depth(max_depth);
+    }
+
+    /// Limits how many bytes of native stack nested Pluto calls may use;
+    /// going further raises a `StackOverflowError` before the thread runs
+    /// out. The default, `DEFAULT_MAX_STACK`, is safe on a thread with Rust's
+    /// default 2 MiB stack; raise it only for a thread with a larger one.
+    pub fn set_max_stack(&mut self, max_stack: usize) {
+        self.max_stack = max_stack;
+        self.evaluator.set_max_stack(max_stack);
+    }
+
+    /// Chooses whether integer results too large for a `Number` become a
+    /// `BigInt` (the default) or raise a `RuntimeError`.
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+        self.evaluator.set_overflow(overflow);
+    }
+
+    /// Runs a whole program. A top-level `return` stops the script and its
+    /// value becomes the result; otherwise the last statement's value is returned.
+    /// Use `PlutoError::render(source)` to display a failure with its location.
+    pub fn run_source(&mut self, source: &str) -> Result<Value, PlutoError> {
+        let ast = self.parse(source)?;
+        match self.engine {
+            Engine::TreeWalker => self.evaluator.evaluate_ast(ast),
+            Engine::Vm => self.vm.run(&mut self.evaluator, &ast),
+        }
+    }
+
+    /// Like `run_source`, for a script loaded from `path`: its `import`s
+    /// resolve relative to the script's directory instead of the working directory.
+    pub fn run_script(&mut self, source: &str, path: &Path) -> Result<Value, PlutoError> {
+        self.evaluator.set_script_path(path);
+        self.run_source(source)
+    }
+
+    /// Evaluates a single expression, e.g. `eval_expr("x * 2")`.
+    pub fn eval_expr(&mut self, expr: &str) -> Result<Value, PlutoError> {
+        let tokens = tokenize(expr)?;
+        let mut parser = Parser::new(tokens);
+        let mut ast = parser.parse_expression_only()?;
+        resolve(&mut ast, &self.evaluator.env_stack)?;
+        self.evaluator.evaluate(&ast)
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.evaluator.get_global(name)
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.evaluator.set_global(name, value);
+    }
+
+    /// Exposes a Rust closure to scripts as a global function. It receives
+    /// the evaluator, so it can call Pluto functions passed to it with `call_value`.
+    pub fn register_fn(
+        &mut self,
+        name: &str,
+        f: impl Fn(&mut Evaluator, Vec<Value>) -> Result<Value, PlutoError> + 'static,
+    ) {
+        self.evaluator.set_global(name, Value::BuiltInFunction(NativeFunction::new(f)));
+    }
+
+    /// Drops every user-defined global and restores the default environment.
+    pub fn reset(&mut self) {
+        self.evaluator.release();
+        self.evaluator = Evaluator::new();
+        self.evaluator.set_max_depth(self.max_depth);
+        self.evaluator.set_max_stack(self.max_stack);
+        self.evaluator.set_overflow(self.overflow);
+        self.vm = Vm::new();
+    }
+
+    fn parse(&self, source: &str) -> Result<ASTNode, PlutoError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser::new(tokens);
+        let mut ast = parser.parse()?;
+        resolve(&mut ast, &self.evaluator.env_stack)?;
+        Ok(ast)
+    }
+}
@@ -0,0 +1 @@
+pub mod interpreter;
\ No newline at end of file
@@ -1,10 +1,30 @@
 use crate::constants::token::TokenKind;
-use crate::constants::token::Token;
+use crate::constants::token::{StringPart, Token};
+use crate::error::error::PlutoError;
+use crate::parser::ast::Span;
 
-pub fn tokenize(input: &str) -> Vec<Token> {
-    let mut tokens = Vec::new();
+pub fn tokenize(input: &str) -> Result<Vec<Token>, PlutoError> {
     let chars: Vec<char> = input.chars().collect();
-    let mut position = 0;
+    let mut tokens = scan(&chars, 0)?;
+
+    tokens.push(Token::new(TokenKind::EOF, chars.len(), chars.len()));
+
+    let mut line = 1;
+    let mut scanned = 0;
+    for token in tokens.iter_mut() {
+        let upto = token.position;
+        line += chars[scanned.min(upto)..upto].iter().filter(|&&c| c == '\n').count();
+        scanned = scanned.max(upto);
+        token.line = line;
+    }
+    Ok(tokens)
+}
+
+/// Tokenizes `chars` from `position` to the end. Positions stay relative to
+/// the whole source, so the code of a `${}` is scanned by passing the source
+/// cut off at its closing brace.
+fn scan(chars: &[char], mut position: usize) -> Result<Vec<Token>, PlutoError> {
+    let mut tokens = Vec::new();
 
     while position < chars.len() {
         let current_char = chars[position];
@@ -15,241 +35,208 @@ pub fn tokenize(input: &str) -> Vec<Token> {
             continue;
         }
 
-        if current_char == '/' && position + 1 < chars.len() && chars[position + 1] == '*' {
-            position += 2; // /*
-            
-            while position + 1 < chars.len() {
-                let curr = chars[position];
-                let next = chars[position + 1];
-                
-                if curr == '*' && next == '/' {
-                    position += 2; // */
-                    break;
-                }
-                position += 1;
+        if current_char == '/' && matches!(chars.get(position + 1), Some('/' | '*')) {
+            let Some(end) = comment_end(chars, position) else {
+                return Err(PlutoError::syntax("Unterminated block comment")
+                    .with_span(Span::new(position, position + 2, line_at(chars, position))));
+            };
+            if let Some(doc) = doc_comment(&chars[position..end]) {
+                tokens.push(Token::new(TokenKind::DocComment(doc), position, end));
             }
+            position = end;
             continue;
         }
 
-        if current_char.is_digit(10) {
-            let start = position;
-            let mut has_dot = false;
-            let mut dot_position = 0;
-            
-            while position < chars.len() && (chars[position].is_digit(10) || chars[position] == '.') {
-                if chars[position] == '.' {
-                    if position + 1 < chars.len() && chars[position + 1] == '.' {
-                        break;
-                    }
-                    if has_dot {
-                        break;
-                    }
-                    has_dot = true;
-                    dot_position = position;
-                }
-                position += 1;
-            }
-
-            let number_str = chars[start..position].iter().collect::<String>();
-            if has_dot && dot_position + 1 < position {
-                let number: f64 = number_str.parse().unwrap();
-                tokens.push(Token::new(TokenKind::Float(number), start));
-            } else {
-                let end = if has_dot { dot_position } else { position };
-                let number_str = chars[start..end].iter().collect::<String>();
-                let number: i64 = number_str.parse().unwrap();
-                tokens.push(Token::new(TokenKind::Number(number), start));
-                
-                if has_dot {
-                    position = dot_position + 1;
-                    tokens.push(Token::new(TokenKind::Dot, dot_position));
-                }
-            }
+        if current_char.is_ascii_digit()
+            || current_char == '.' && chars.get(position + 1).is_some_and(|c| c.is_ascii_digit())
+        {
+            let (kind, end) = number(chars, position)?;
+            tokens.push(Token::new(kind, position, end));
+            position = end;
             continue;
         }
 
         match current_char {
-            '[' => tokens.push(Token::new(TokenKind::LBracket, position)),
-            ']' => tokens.push(Token::new(TokenKind::RBracket, position)),
-            '\"' => {
-                let start = position + 1;
-                position += 1;
-                let mut string = String::new();
-                while position < chars.len() {
-                    let c = chars[position];
-                    if c == '\\' && position + 1 < chars.len() {
-                        let next = chars[position + 1];
-                        match next {
-                            '"' => {
-                                string.push('"');
-                                position += 2;
-                            }
-                            'n' => {
-                                string.push('\n');
-                                position += 2;
-                            }
-                            't' => {
-                                string.push('\t');
-                                position += 2;
-                            }
-                            '\\' => {
-                                string.push('\\');
-                                position += 2;
-                            }
-                            _ => {
-                                string.push(c);
-                                position += 1;
-                            }
-                        }
-                    } else if c == '"' {
-                        position += 1;
-                        break;
-                    } else {
-                        string.push(c);
-                        position += 1;
-                    }
-                }
-                tokens.push(Token::new(TokenKind::StringLiteral(string), start));
+            '[' => tokens.push(Token::new(TokenKind::LBracket, position, position + 1)),
+            ']' => tokens.push(Token::new(TokenKind::RBracket, position, position + 1)),
+            '"' | 'r' if current_char == '"' || chars.get(position + 1) == Some(&'"') => {
+                let (kind, end) = string(chars, position)?;
+                tokens.push(Token::new(kind, position, end));
+                position = end;
                 continue;
             }
             '+' => {
                 if position + 1 < chars.len() && chars[position + 1] == '+' {
-                    tokens.push(Token::new(TokenKind::PlusPlus, position));
+                    tokens.push(Token::new(TokenKind::PlusPlus, position, position + 2));
                     position += 2;
                     continue;
                 } else if position + 1 < chars.len() && chars[position + 1] == '=' {
-                    tokens.push(Token::new(TokenKind::PlusEqual, position));
+                    tokens.push(Token::new(TokenKind::PlusEqual, position, position + 2));
                     position += 2;
                     continue;
                 } else {
-                    tokens.push(Token::new(TokenKind::Plus, position));
+                    tokens.push(Token::new(TokenKind::Plus, position, position + 1));
                 }
             }
             '-' => {
                 if position + 1 < chars.len() && chars[position + 1] == '-' {
-                    tokens.push(Token::new(TokenKind::MinusMinus, position));
+                    tokens.push(Token::new(TokenKind::MinusMinus, position, position + 2));
                     position += 2;
                     continue;
                 } else if position + 1 < chars.len() && chars[position + 1] == '=' {
-                    tokens.push(Token::new(TokenKind::MinusEqual, position));
+                    tokens.push(Token::new(TokenKind::MinusEqual, position, position + 2));
                     position += 2;
                     continue;
                 } else if position + 1 < chars.len() && chars[position + 1] == '>' {
-                    tokens.push(Token::new(TokenKind::ArrowFunc, position));
+                    tokens.push(Token::new(TokenKind::ArrowFunc, position, position + 2));
                     position += 2;
                     continue;
                 } else {
-                    tokens.push(Token::new(TokenKind::Minus, position));
+                    tokens.push(Token::new(TokenKind::Minus, position, position + 1));
                 }
             }
             '*' => {
                 if position + 1 < chars.len() && chars[position + 1] == '=' {
-                    tokens.push(Token::new(TokenKind::StarEqual, position));
+                    tokens.push(Token::new(TokenKind::StarEqual, position, position + 2));
                     position += 2;
                     continue;
                 } else {
-                    tokens.push(Token::new(TokenKind::Star, position));
+                    tokens.push(Token::new(TokenKind::Star, position, position + 1));
                 }
             }
             '/' => {
                 if position + 1 < chars.len() && chars[position + 1] == '=' {
-                    tokens.push(Token::new(TokenKind::SlashEqual, position));
+                    tokens.push(Token::new(TokenKind::SlashEqual, position, position + 2));
                     position += 2;
                     continue;
                 } else {
-                    tokens.push(Token::new(TokenKind::Slash, position));
+                    tokens.push(Token::new(TokenKind::Slash, position, position + 1));
                 }
             }
             '%' => {
-                tokens.push(Token::new(TokenKind::Percent, position));
+                tokens.push(Token::new(TokenKind::Percent, position, position + 1));
             }
             '=' => {
                 if position + 1 < chars.len() && chars[position + 1] == '=' {
-                    tokens.push(Token::new(TokenKind::EqualsEqual, position));
+                    tokens.push(Token::new(TokenKind::EqualsEqual, position, position + 2));
                     position += 2;
                     continue;
                 } else {
-                    tokens.push(Token::new(TokenKind::Equal, position));
+                    tokens.push(Token::new(TokenKind::Equal, position, position + 1));
                 }
             }
             '!' => {
                 if position + 1 < chars.len() && chars[position + 1] == '=' {
-                    tokens.push(Token::new(TokenKind::NotEqual, position));
+                    tokens.push(Token::new(TokenKind::NotEqual, position, position + 2));
                     position += 2;
                     continue;
                 } else {
-                    tokens.push(Token::new(TokenKind::Not, position));
+                    tokens.push(Token::new(TokenKind::Not, position, position + 1));
                 }
             }
             '<' => {
-                if position + 1 < chars.len() && chars[position + 1] == '=' {
-                    tokens.push(Token::new(TokenKind::LessThanEqual, position));
+                if chars[position..].starts_with(&['<', '<', '=']) {
+                    tokens.push(Token::new(TokenKind::ShiftLeftEqual, position, position + 3));
+                    position += 3;
+                    continue;
+                } else if position + 1 < chars.len() && chars[position + 1] == '<' {
+                    tokens.push(Token::new(TokenKind::ShiftLeft, position, position + 2));
+                    position += 2;
+                    continue;
+                } else if position + 1 < chars.len() && chars[position + 1] == '=' {
+                    tokens.push(Token::new(TokenKind::LessThanEqual, position, position + 2));
                     position += 2;
                     continue;
                 } else {
-                    tokens.push(Token::new(TokenKind::LessThan, position));
+                    tokens.push(Token::new(TokenKind::LessThan, position, position + 1));
                 }
             }
             '>' => {
-                if position + 1 < chars.len() && chars[position + 1] == '=' {
-                    tokens.push(Token::new(TokenKind::GreaterThanEqual, position));
+                if chars[position..].starts_with(&['>', '>', '=']) {
+                    tokens.push(Token::new(TokenKind::ShiftRightEqual, position, position + 3));
+                    position += 3;
+                    continue;
+                } else if position + 1 < chars.len() && chars[position + 1] == '>' {
+                    tokens.push(Token::new(TokenKind::ShiftRight, position, position + 2));
+                    position += 2;
+                    continue;
+                } else if position + 1 < chars.len() && chars[position + 1] == '=' {
+                    tokens.push(Token::new(TokenKind::GreaterThanEqual, position, position + 2));
                     position += 2;
                     continue;
                 } else {
-                    tokens.push(Token::new(TokenKind::GreaterThan, position));
+                    tokens.push(Token::new(TokenKind::GreaterThan, position, position + 1));
                 }
             }
             '&' => {
                 if position + 1 < chars.len() && chars[position + 1] == '&' {
-                    tokens.push(Token::new(TokenKind::And, position));
+                    tokens.push(Token::new(TokenKind::And, position, position + 2));
+                    position += 2;
+                    continue;
+                } else if position + 1 < chars.len() && chars[position + 1] == '=' {
+                    tokens.push(Token::new(TokenKind::AmpersandEqual, position, position + 2));
                     position += 2;
                     continue;
                 } else {
-                    tokens.push(Token::new(TokenKind::Unknown('&'), position));
+                    tokens.push(Token::new(TokenKind::Ampersand, position, position + 1));
                 }
             }
             '|' => {
                 if position + 1 < chars.len() && chars[position + 1] == '|' {
-                    tokens.push(Token::new(TokenKind::Or, position));
+                    tokens.push(Token::new(TokenKind::Or, position, position + 2));
+                    position += 2;
+                    continue;
+                } else if position + 1 < chars.len() && chars[position + 1] == '=' {
+                    tokens.push(Token::new(TokenKind::PipeEqual, position, position + 2));
                     position += 2;
                     continue;
                 } else {
-                    tokens.push(Token::new(TokenKind::Unknown('|'), position));
+                    tokens.push(Token::new(TokenKind::Pipe, position, position + 1));
                 }
             }
-            '(' => tokens.push(Token::new(TokenKind::LParen, position)),
-            ')' => tokens.push(Token::new(TokenKind::RParen, position)),
-            '{' => tokens.push(Token::new(TokenKind::LBrace, position)),
-            '}' => tokens.push(Token::new(TokenKind::RBrace, position)),
-            ';' => tokens.push(Token::new(TokenKind::Semicolon, position)),
-            ',' => tokens.push(Token::new(TokenKind::Comma, position)),
+            '^' => {
+                if position + 1 < chars.len() && chars[position + 1] == '=' {
+                    tokens.push(Token::new(TokenKind::CaretEqual, position, position + 2));
+                    position += 2;
+                    continue;
+                } else {
+                    tokens.push(Token::new(TokenKind::Caret, position, position + 1));
+                }
+            }
+            '~' => tokens.push(Token::new(TokenKind::Tilde, position, position + 1)),
+            '(' => tokens.push(Token::new(TokenKind::LParen, position, position + 1)),
+            ')' => tokens.push(Token::new(TokenKind::RParen, position, position + 1)),
+            '{' => tokens.push(Token::new(TokenKind::LBrace, position, position + 1)),
+            '}' => tokens.push(Token::new(TokenKind::RBrace, position, position + 1)),
+            ';' => tokens.push(Token::new(TokenKind::Semicolon, position, position + 1)),
+            ',' => tokens.push(Token::new(TokenKind::Comma, position, position + 1)),
             '.' => {
                 if position + 1 < chars.len() {
                     if chars[position + 1] == '.' {
                         if position + 2 < chars.len() && chars[position + 2] == '=' {
-                            tokens.push(Token::new(TokenKind::DotDotEqual, position));
+                            tokens.push(Token::new(TokenKind::DotDotEqual, position, position + 3));
                             position += 3;
                         } else {
-                            tokens.push(Token::new(TokenKind::DotDot, position));
+                            tokens.push(Token::new(TokenKind::DotDot, position, position + 2));
                             position += 2;
                         }
                     } else {
-                        tokens.push(Token::new(TokenKind::Dot, position));
+                        tokens.push(Token::new(TokenKind::Dot, position, position + 1));
                         position += 1;
                     }
                 } else {
-                    tokens.push(Token::new(TokenKind::Dot, position));
+                    tokens.push(Token::new(TokenKind::Dot, position, position + 1));
                     position += 1;
                 }
                 continue;
             }
-            ':' => tokens.push(Token::new(TokenKind::Colon, position)),
+            ':' => tokens.push(Token::new(TokenKind::Colon, position, position + 1)),
             '?' => {
                 if position + 1 < chars.len() && chars[position + 1] == ':' {
-                    tokens.push(Token::new(TokenKind::Elvis, position));
+                    tokens.push(Token::new(TokenKind::Elvis, position, position + 2));
                     position += 2;
                 } else {
-                    tokens.push(Token::new(TokenKind::QuestionMark, position));
+                    tokens.push(Token::new(TokenKind::QuestionMark, position, position + 1));
                 }
             }
             '_' => {
@@ -261,10 +248,10 @@ pub fn tokenize(input: &str) -> Vec<Token> {
                         position += 1;
                     }
                     let identifier = chars[start..position].iter().collect::<String>();
-                    tokens.push(Token::new(TokenKind::Identifier(identifier), start));
+                    tokens.push(Token::new(TokenKind::Identifier(identifier), start, position));
                     continue;
                 } else {
-                    tokens.push(Token::new(TokenKind::Underscore, position));
+                    tokens.push(Token::new(TokenKind::Underscore, position, position + 1));
                 }
             },
             'a'..='z' | 'A'..='Z' => {
@@ -277,6 +264,7 @@ pub fn tokenize(input: &str) -> Vec<Token> {
                     "let" => TokenKind::Let,
                     "const" => TokenKind::Const,
                     "for" => TokenKind::For,
+                    "in" => TokenKind::In,
                     "while" => TokenKind::While,
                     "fn" => TokenKind::Fn,
                     "return" => TokenKind::Return,
@@ -288,17 +276,358 @@ pub fn tokenize(input: &str) -> Vec<Token> {
                     "continue" => TokenKind::Continue,
                     "null" => TokenKind::Null,
                     "match" => TokenKind::Match,
+                    "try" => TokenKind::Try,
+                    "catch" => TokenKind::Catch,
+                    "finally" => TokenKind::Finally,
+                    "throw" => TokenKind::Throw,
+                    "import" => TokenKind::Import,
+                    "struct" => TokenKind::Struct,
+                    "impl" => TokenKind::Impl,
                     _ => TokenKind::Identifier(identifier),
                 };
-                tokens.push(Token::new(kind, start));
+                tokens.push(Token::new(kind, start, position));
+                continue;
+            }
+            _ => tokens.push(Token::new(TokenKind::Unknown(current_char), position, position + 1)),
+        }
+
+        position += 1;
+    }
+
+    Ok(tokens)
+}
+
+/// The `}` that closes the interpolation whose `{` is at `open`, skipping
+/// braces inside nested strings and comments.
+fn interpolation_end(chars: &[char], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut position = open;
+    while position < chars.len() {
+        match chars[position] {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(position);
+                }
+            }
+            '"' => {
+                position = string_end(chars, position, false)?;
+                continue;
+            }
+            'r' if chars.get(position + 1) == Some(&'"') => {
+                position = string_end(chars, position + 1, true)?;
+                continue;
+            }
+            '/' if matches!(chars.get(position + 1), Some('/' | '*')) => {
+                position = comment_end(chars, position)?;
                 continue;
             }
-            _ => tokens.push(Token::new(TokenKind::Unknown(current_char), position)),
+            _ => {}
         }
+        position += 1;
+    }
+    None
+}
+
+/// Just past the comment that starts at `start`: a `//` comment runs to the
+/// end of the line, and `/* */` comments nest. `None` if a block comment is
+/// never closed.
+fn comment_end(chars: &[char], start: usize) -> Option<usize> {
+    if chars[start + 1] == '/' {
+        let newline = chars[start..].iter().position(|&c| c == '\n');
+        return Some(newline.map_or(chars.len(), |offset| start + offset));
+    }
+    let mut depth = 0;
+    let mut position = start;
+    while position + 1 < chars.len() {
+        match (chars[position], chars[position + 1]) {
+            ('/', '*') => {
+                depth += 1;
+                position += 2;
+            }
+            ('*', '/') => {
+                depth -= 1;
+                position += 2;
+                if depth == 0 {
+                    return Some(position);
+                }
+            }
+            _ => position += 1,
+        }
+    }
+    None
+}
+
+/// The text of a `///` or `/** */` doc comment, without its markers and
+/// leading `*`s. `////` and `/***` start ordinary comments, as in Rust.
+fn doc_comment(comment: &[char]) -> Option<String> {
+    let text: String = comment.iter().collect();
+    if let Some(line) = text.strip_prefix("///") {
+        if line.starts_with('/') {
+            return None;
+        }
+        return Some(line.strip_prefix(' ').unwrap_or(line).trim_end().to_string());
+    }
+    let body = text.strip_prefix("/**")?.strip_suffix("*/")?;
+    if body.is_empty() || body.starts_with('*') {
+        return None;
+    }
+    let lines: Vec<&str> = body
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line).trim_end()
+        })
+        .collect();
+    Some(lines.join("\n").trim().to_string())
+}
+
+/// Scans the string literal starting at `start`, returning its token and
+/// end. `r"..."` strings are raw: no escapes or interpolation. A `"""` string
+/// may span lines; a line break right after the opening quotes, the
+/// indentation of the closing quotes, and the indentation every line shares
+/// are left out of it.
+fn string(chars: &[char], start: usize) -> Result<(TokenKind, usize), PlutoError> {
+    let raw = chars[start] == 'r';
+    let open = start + raw as usize;
+    let triple = chars[open..].starts_with(&['"'; 3]);
+    let unterminated = || {
+        PlutoError::syntax("Unterminated string")
+            .with_span(Span::new(start, open + 1, line_at(chars, start)))
+    };
+
+    let mut position = open + if triple { 3 } else { 1 };
+    let mut limit = chars.len();
+    let mut end = 0;
+    let mut indent = 0;
+    if triple {
+        end = string_end(chars, open, raw).ok_or_else(unterminated)?;
+        limit = end - 3;
+        if chars.get(position) == Some(&'\n') {
+            position += 1;
+        }
+        let last_line = chars[position..limit].iter().rposition(|&c| c == '\n').map(|i| position + i);
+        if let Some(newline) = last_line
+            && chars[newline + 1..limit].iter().all(|&c| c == ' ' || c == '\t')
+        {
+            indent = limit - (newline + 1);
+            limit = newline;
+        } else {
+            indent = usize::MAX;
+        }
+        indent = chars[position..limit]
+            .split(|&c| c == '\n')
+            .filter(|line| line.iter().any(|c| !c.is_whitespace()))
+            .map(|line| line.iter().take_while(|&&c| c == ' ' || c == '\t').count())
+            .fold(indent, usize::min);
+    }
+
+    let mut parts = Vec::new();
+    let mut string = String::new();
+    let mut line_start = triple;
+    let mut terminated = triple;
+    while position < limit {
+        let c = chars[position];
+        if line_start {
+            let skip = chars[position..limit].iter().take(indent).take_while(|&&c| c == ' ' || c == '\t').count();
+            position += skip;
+            line_start = false;
+        } else if !triple && c == '"' {
+            position += 1;
+            terminated = true;
+            break;
+        } else if !raw && c == '\\' {
+            let (escaped, len) = escape(chars, position)?;
+            string.push(escaped);
+            position += len;
+        } else if !raw && c == '$' && chars.get(position + 1) == Some(&'{') {
+            let Some(close) = interpolation_end(chars, position + 1) else {
+                return Err(PlutoError::syntax("Unterminated '${' in string")
+                    .with_span(Span::new(position, position + 2, line_at(chars, position))));
+            };
+            let mut code = scan(&chars[..close], position + 2)?;
+            if code.is_empty() {
+                return Err(PlutoError::syntax("Empty '${}' in string")
+                    .with_span(Span::new(position, close + 1, line_at(chars, position))));
+            }
+            code.push(Token::new(TokenKind::EOF, close, close));
+            for token in code.iter_mut() {
+                token.line = line_at(chars, token.position);
+            }
+            if !string.is_empty() {
+                parts.push(StringPart::Text(std::mem::take(&mut string)));
+            }
+            parts.push(StringPart::Code(code));
+            position = close + 1;
+        } else {
+            string.push(c);
+            line_start = triple && c == '\n';
+            position += 1;
+        }
+    }
+    if !terminated {
+        return Err(unterminated());
+    }
+    if triple {
+        position = end;
+    }
+
+    let kind = if parts.is_empty() {
+        TokenKind::StringLiteral(string)
+    } else {
+        if !string.is_empty() {
+            parts.push(StringPart::Text(string));
+        }
+        TokenKind::InterpolatedString(parts)
+    };
+    Ok((kind, position))
+}
+
+/// Decodes the escape sequence whose backslash is at `start`, returning the
+/// character and the length of the sequence.
+fn escape(chars: &[char], start: usize) -> Result<(char, usize), PlutoError> {
+    let error = |message: String, end: usize| {
+        PlutoError::syntax(message).with_span(Span::new(start, end, line_at(chars, start)))
+    };
+    let escaped = match chars.get(start + 1) {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('0') => '\0',
+        Some('\\') => '\\',
+        Some('"') => '"',
+        Some('$') => '$',
+        Some('x') => {
+            let digits = chars.get(start + 2..start + 4).unwrap_or_default();
+            if digits.len() < 2 || !digits.iter().all(|c| c.is_ascii_hexdigit()) {
+                return Err(error("Expected two hex digits after '\\x'".to_string(), start + 2));
+            }
+            let code = u8::from_str_radix(&digits.iter().collect::<String>(), 16).unwrap_or_default();
+            return Ok((code as char, 4));
+        }
+        Some('u') => {
+            let digits = chars[(start + 3).min(chars.len())..].iter().take_while(|c| c.is_ascii_hexdigit()).count();
+            let close = start + 3 + digits;
+            if chars.get(start + 2) != Some(&'{') || chars.get(close) != Some(&'}') {
+                return Err(error("Expected '\\u{...}' with hex digits".to_string(), start + 2));
+            }
+            let digits = chars[start + 3..close].iter().collect::<String>();
+            let code = u32::from_str_radix(&digits, 16)
+                .ok()
+                .filter(|_| (1..=6).contains(&digits.len()))
+                .and_then(char::from_u32)
+                .ok_or_else(|| error(format!("Invalid unicode escape '\\u{{{}}}'", digits), close + 1))?;
+            return Ok((code, close + 1 - start));
+        }
+        Some(other) => return Err(error(format!("Unknown escape sequence '\\{}'", other), start + 2)),
+        None => {
+            return Err(PlutoError::syntax("Unterminated string")
+                .with_span(Span::new(start, start + 1, line_at(chars, start))));
+        }
+    };
+    Ok((escaped, 2))
+}
+
+/// Just past the closing quotes of the string literal whose opening quote is
+/// at `open`. Escapes and interpolations are skipped unless it is raw.
+fn string_end(chars: &[char], open: usize, raw: bool) -> Option<usize> {
+    let quotes = if chars[open..].starts_with(&['"'; 3]) { 3 } else { 1 };
+    let mut position = open + quotes;
+    while position < chars.len() {
+        match chars[position] {
+            '\\' if !raw => position += 2,
+            '$' if !raw && chars.get(position + 1) == Some(&'{') => position = interpolation_end(chars, position + 1)? + 1,
+            '"' if chars[position..].starts_with(&['"'; 3][..quotes]) => return Some(position + quotes),
+            _ => position += 1,
+        }
+    }
+    None
+}
+
+/// Scans the numeric literal starting at `start`, returning its token and
+/// end. Decimal literals may have a fraction (`1.5`, `.5`) and an exponent
+/// (`6.02e23`); `0x`, `0o` and `0b` prefix hex, octal and binary integers.
+/// Digits may be separated with `_`.
+fn number(chars: &[char], start: usize) -> Result<(TokenKind, usize), PlutoError> {
+    let error = |message: String, end: usize| {
+        PlutoError::syntax(message).with_span(Span::new(start, end, line_at(chars, start)))
+    };
+    let radix = match (chars[start], chars.get(start + 1)) {
+        ('0', Some('x' | 'X')) => Some((16, "hex")),
+        ('0', Some('o' | 'O')) => Some((8, "octal")),
+        ('0', Some('b' | 'B')) => Some((2, "binary")),
+        _ => None,
+    };
+
+    let mut end = start;
+    if radix.is_some() {
+        end += 2;
+        while end < chars.len() && (chars[end].is_ascii_alphanumeric() || chars[end] == '_') {
+            end += 1;
+        }
+    } else {
+        end = digits_end(chars, end);
+        if chars.get(end) == Some(&'.') && chars.get(end + 1).is_some_and(|c| c.is_ascii_digit()) {
+            end = digits_end(chars, end + 1);
+        }
+        if matches!(chars.get(end), Some('e' | 'E')) {
+            let digits = if matches!(chars.get(end + 1), Some('+' | '-')) { end + 2 } else { end + 1 };
+            if !chars.get(digits).is_some_and(|c| c.is_ascii_digit()) {
+                let literal = chars[start..digits].iter().collect::<String>();
+                return Err(error(format!("Missing digits in exponent of '{}'", literal), digits));
+            }
+            end = digits_end(chars, digits);
+        }
+    }
+
+    let literal = chars[start..end].iter().collect::<String>();
+    // a separator must sit between two digits, so never doubled, leading or trailing
+    let first_digit = if radix.is_some() { start + 2 } else { start };
+    let is_digit = |i: usize| {
+        i >= first_digit && i < end && if radix.is_some() { chars[i].is_ascii_alphanumeric() } else { chars[i].is_ascii_digit() }
+    };
+    let misplaced = (start..end).any(|i| chars[i] == '_' && !(i > 0 && is_digit(i - 1) && is_digit(i + 1)));
+    if misplaced {
+        return Err(error(format!("Invalid '_' in numeric literal '{}'", literal), end));
+    }
+
+    if let Some((radix, name)) = radix {
+        let digits = chars[start + 2..end].iter().filter(|&&c| c != '_').collect::<String>();
+        if digits.is_empty() {
+            return Err(error(format!("Missing digits in {} literal '{}'", name, literal), end));
+        }
+        if let Some(digit) = digits.chars().find(|c| !c.is_digit(radix)) {
+            return Err(error(format!("Invalid digit '{}' in {} literal '{}'", digit, name, literal), end));
+        }
+        let value = i64::from_str_radix(&digits, radix)
+            .map_err(|_| error(format!("Integer literal '{}' is out of range", literal), end))?;
+        return Ok((TokenKind::Number(value), end));
+    }
+
+    let digits = literal.replace('_', "");
+    if digits.contains(['.', 'e', 'E']) {
+        match digits.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok((TokenKind::Float(value), end)),
+            _ => Err(error(format!("Float literal '{}' is out of range", literal), end)),
+        }
+    } else {
+        let value = digits
+            .parse::<i64>()
+            .map_err(|_| error(format!("Integer literal '{}' is out of range", literal), end))?;
+        Ok((TokenKind::Number(value), end))
+    }
+}
 
+/// Skips decimal digits and `_` separators from `position`.
+fn digits_end(chars: &[char], mut position: usize) -> usize {
+    while position < chars.len() && (chars[position].is_ascii_digit() || chars[position] == '_') {
         position += 1;
     }
+    position
+}
 
-    tokens.push(Token::new(TokenKind::EOF, input.len()));
-    tokens
+fn line_at(chars: &[char], position: usize) -> usize {
+    chars[..position.min(chars.len())].iter().filter(|&&c| c == '\n').count() + 1
 }
\ No newline at end of file
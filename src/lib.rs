@@ -0,0 +1,16 @@
+#![allow(clippy::module_inception)]
+
+pub mod constants;
+pub mod lexer;
+pub mod parser;
+pub mod resolver;
+pub mod evaluator;
+pub mod utils;
+pub mod builtins;
+pub mod error;
+pub mod interpreter;
+pub mod vm;
+
+pub use error::error::{ErrorKind, PlutoError};
+pub use evaluator::evaluator::{DEFAULT_MAX_DEPTH, DEFAULT_MAX_STACK, Evaluator, NativeFunction, Overflow, Value};
+pub use interpreter::interpreter::{Engine, Interpreter};
@@ -1,42 +1,79 @@
-mod constants;
-mod lexer;
-mod parser;
-mod evaluator;
-mod utils;
-mod builtins;
+#![allow(clippy::module_inception)]
+
 mod repl;
 
-use utils::args::get_args;
-use utils::colors::{self};
+use pluto_lang::utils::args::get_args;
+use pluto_lang::{Engine, Interpreter, Overflow};
 use std::fs;
+use std::path::Path;
+use std::thread;
 use repl::repl::repl;
 
+/// Native stack used by one level of Pluto calls, with room to spare.
+const STACK_PER_CALL: usize = if cfg!(debug_assertions) { 64 * 1024 } else { 16 * 1024 };
+/// Native stack left free below the deepest call, for the work a call does
+/// before it makes the next one.
+const STACK_RESERVE: usize = 1024 * 1024;
+
 fn main() {
-    let args = get_args();
+    let (options, args): (Vec<String>, Vec<String>) = get_args().into_iter().partition(|a| a.starts_with("--"));
+
+    let mut engine = Engine::TreeWalker;
+    let mut max_depth = pluto_lang::DEFAULT_MAX_DEPTH;
+    let mut overflow = Overflow::Promote;
+    for option in &options {
+        match option.as_str() {
+            "--engine=vm" => engine = Engine::Vm,
+            "--engine=tree" => engine = Engine::TreeWalker,
+            "--overflow=promote" => overflow = Overflow::Promote,
+            "--overflow=error" => overflow = Overflow::Error,
+            _ if let Some(depth) = option.strip_prefix("--max-depth=") => match depth.parse() {
+                Ok(depth) => max_depth = depth,
+                Err(_) => {
+                    eprintln!("Invalid maximum depth '{}'", depth);
+                    std::process::exit(2);
+                }
+            },
+            _ => {
+                eprintln!("Unknown option '{}'", option);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // the main thread's stack is too small for deep recursion, so size one to the limit
+    let stack_size = max_depth.saturating_mul(STACK_PER_CALL).saturating_add(8 * 1024 * 1024);
+    let runner = thread::Builder::new()
+        .stack_size(stack_size)
+        .spawn(move || {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_engine(engine);
+            interpreter.set_max_depth(max_depth);
+            interpreter.set_max_stack(stack_size - STACK_RESERVE);
+            interpreter.set_overflow(overflow);
+            run(interpreter, &args)
+        })
+        .unwrap_or_else(|_| {
+            eprintln!("Cannot reserve enough stack for a maximum depth of {}", max_depth);
+            std::process::exit(2);
+        });
+    if runner.join().is_err() {
+        std::process::exit(101);
+    }
+}
 
+fn run(mut interpreter: Interpreter, args: &[String]) {
     if args.len() < 2 {
-        repl();
+        repl(interpreter);
         return;
     }
     let filename = &args[1];
     let contents = fs::read_to_string(filename).expect("Something went wrong reading the file");
 
-    let tokens = lexer::tokenizer::tokenize(&contents);
-    let mut parser = parser::parser::Parser::new(tokens, contents);
-    
-    match parser.parse() {
-        Ok(ast) => {
-            let mut evaluator = evaluator::evaluator::Evaluator::new();
-            match evaluator.evaluate(&ast) {
-                Ok(_) => (),
-                Err(e) => {
-                    println!("{}{}Error: {}{}", colors::BOLD, colors::RED, colors::RESET, &e);
-                    std::process::exit(1);
-                }
-            }
-        },
+    match interpreter.run_script(&contents, Path::new(filename)) {
+        Ok(_) => (),
         Err(e) => {
-            println!("{}", e);
+            println!("{}", e.render(&contents));
             std::process::exit(1);
         }
     }
@@ -1,23 +1,189 @@
+use std::fmt;
+use std::rc::Rc;
+
+pub type Params = Vec<(String, Option<Box<ASTNode>>)>;
+/// A match arm: pattern, optional guard, result.
+pub type MatchArm = (Pattern, Option<Box<ASTNode>>, Box<ASTNode>);
+
+/// Char offsets into the source, `start` inclusive and `end` exclusive,
+/// plus the 1-based line of `start` (0 when unknown).
+/// `file` is 0 for the main program and an imported module's id otherwise.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub file: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize) -> Self {
+        Span { start, end, line, file: 0 }
+    }
+
+    pub fn in_file(self, file: usize) -> Span {
+        Span { file, ..self }
+    }
+
+    pub fn to(self, other: Span) -> Span {
+        let line = if self.start <= other.start { self.line } else { other.line };
+        Span::new(self.start.min(other.start), self.end.max(other.end), line).in_file(self.file)
+    }
+}
+
+/// Where a variable lives, filled in by the resolver: `depth` scopes out
+/// from the innermost one, at position `index` within that scope.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize,
+}
+
+/// An infix operator, also the operation of a compound assignment (`+=`).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    And,
+    Or,
+    /// `a ?: b`: `a` if it is truthy, otherwise `b`.
+    Elvis,
+}
+
+impl BinaryOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Gt => ">",
+            BinaryOp::Le => "<=",
+            BinaryOp::Ge => ">=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+            BinaryOp::Elvis => "?:",
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UnaryOp {
+    /// `!x`
+    Not,
+    /// `~x`
+    BitNot,
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UnaryOp::Not => "!",
+            UnaryOp::BitNot => "~",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PostfixOp {
+    /// `x++`
+    Increment,
+    /// `x--`
+    Decrement,
+}
+
+impl fmt::Display for PostfixOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PostfixOp::Increment => "++",
+            PostfixOp::Decrement => "--",
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
-pub enum ASTNode {
+pub struct ASTNode {
+    pub kind: ASTNodeKind,
+    pub span: Span,
+    /// Set on identifiers, assignments, calls by name and declarations.
+    pub slot: Option<Slot>,
+    /// The doc comment of a `fn` declaration or method, or of a function
+    /// literal that initializes a documented `let` or `const`.
+    pub doc: Option<Rc<str>>,
+}
+
+impl ASTNode {
+    pub fn new(kind: ASTNodeKind, span: Span) -> Self {
+        ASTNode { kind, span, slot: None, doc: None }
+    }
+
+    /// Whether this statement adds a name to the scope it runs in. Only a
+    /// block containing one gets a scope of its own.
+    pub fn is_declaration(&self) -> bool {
+        matches!(
+            self.kind,
+            ASTNodeKind::VariableDeclaration(..)
+                | ASTNodeKind::ConstDeclaration(..)
+                | ASTNodeKind::FunctionDeclaration(..)
+                | ASTNodeKind::StructDeclaration(..)
+                | ASTNodeKind::ImportStatement(..)
+                | ASTNodeKind::FromImport(..)
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ASTNodeKind {
     Program(Vec<ASTNode>),
     Block(Vec<ASTNode>),
     VariableDeclaration(String, Option<Box<ASTNode>>),
     ConstDeclaration(String, Option<Box<ASTNode>>),
     Assignment(String, Box<ASTNode>),
-    BinaryExpression(Box<ASTNode>, String, Box<ASTNode>),
-    UnaryExpression(String, Box<ASTNode>),
-    PostfixUnaryExpression(String, Box<ASTNode>),
-    AssignmentOp(String, Box<ASTNode>, Box<ASTNode>),
+    BinaryExpression(Box<ASTNode>, BinaryOp, Box<ASTNode>),
+    UnaryExpression(UnaryOp, Box<ASTNode>),
+    PostfixUnaryExpression(PostfixOp, Box<ASTNode>),
+    /// `target op= value`
+    AssignmentOp(BinaryOp, Box<ASTNode>, Box<ASTNode>),
     NumberLiteral(i64),
     FloatLiteral(f64),
     NullLiteral,
     StringLiteral(String),
+    /// `"a ${b} c"`: literal text and expressions, joined by their `Display` forms.
+    InterpolatedString(Vec<Box<ASTNode>>),
     ArrayLiteral(Vec<Box<ASTNode>>),
     HashMapLiteral(Vec<(String, Box<ASTNode>)>),
     Identifier(String),
-    FunctionDeclaration(String, Vec<(String, Option<Box<ASTNode>>)>, Box<ASTNode>),
-    AnonymousFunction(Vec<(String, Option<Box<ASTNode>>)>, Box<ASTNode>),
+    FunctionDeclaration(String, Params, Rc<ASTNode>),
+    AnonymousFunction(Params, Rc<ASTNode>),
     FunctionCall(String, Vec<(Option<String>, Box<ASTNode>)>),
     ImmediateInvocation(Box<ASTNode>, Vec<(Option<String>, Box<ASTNode>)>),
     IfStatement(Box<ASTNode>, Box<ASTNode>, Option<Box<ASTNode>>),
@@ -29,6 +195,7 @@ pub enum ASTNode {
     BooleanLiteral(bool),
     IndexAccess(Box<ASTNode>, Box<ASTNode>),
     AssignmentIndex(Box<ASTNode>, Box<ASTNode>, Box<ASTNode>), // array, index, value
+    AssignmentMember(Box<ASTNode>, String, Box<ASTNode>), // object, field, value
     Break,
     Continue,
     ForStatement(
@@ -37,21 +204,83 @@ pub enum ASTNode {
         Option<Box<ASTNode>>,
         Box<ASTNode>,
     ),
-    MatchExpression(Box<ASTNode>, Vec<(Box<ASTNode>, Box<ASTNode>)>),
+    ForInStatement(Vec<String>, Box<ASTNode>, Box<ASTNode>), // bindings, iterable, body
+    MatchExpression(Box<ASTNode>, Vec<MatchArm>), // subject, arms
+    Range(Box<ASTNode>, Box<ASTNode>, bool),
+    ImportStatement(String, Option<String>), // path, alias
+    FromImport(String, Vec<String>),
+    StructDeclaration(String, Params), // name, fields with optional defaults
+    ImplBlock(String, Vec<ASTNode>), // struct name, function declarations
+    TryStatement(Box<ASTNode>, Option<(String, Box<ASTNode>)>, Option<Box<ASTNode>>), // body, catch (name, body), finally
+    ThrowStatement(Box<ASTNode>),
+}
+
+/// The left-hand side of a match arm.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Binding(String),
+    /// Any other expression, compared with `==`.
+    Value(Box<ASTNode>),
     Range(Box<ASTNode>, Box<ASTNode>, bool),
+    /// Element patterns, then `..rest` (`Some(None)` for a bare `..`).
+    Array(Vec<Pattern>, Option<Option<String>>),
+    Map(Vec<(String, Pattern)>),
+    Or(Vec<Pattern>),
 }
 
 pub trait ASTNodeTrait {
     fn to_string(&self) -> String;
 }
 
+impl ASTNodeTrait for Pattern {
+    fn to_string(&self) -> String {
+        match self {
+            Pattern::Wildcard => "_".to_string(),
+            Pattern::Binding(name) => name.clone(),
+            Pattern::Value(expr) => expr.to_string(),
+            Pattern::Range(start, end, inclusive) => {
+                format!("{}{}{}", start.to_string(), if *inclusive { "..=" } else { ".." }, end.to_string())
+            }
+            Pattern::Array(elements, rest) => {
+                let mut parts: Vec<String> = elements.iter().map(|p| p.to_string()).collect();
+                match rest {
+                    Some(Some(name)) => parts.push(format!("..{}", name)),
+                    Some(None) => parts.push("..".to_string()),
+                    None => {}
+                }
+                format!("[{}]", parts.join(", "))
+            }
+            Pattern::Map(entries) => {
+                let parts: Vec<String> = entries
+                    .iter()
+                    .map(|(key, pattern)| match pattern {
+                        Pattern::Binding(name) if name == key => key.clone(),
+                        _ => format!("{}: {}", key, pattern.to_string()),
+                    })
+                    .collect();
+                format!("{{{}}}", parts.join(", "))
+            }
+            Pattern::Or(alternatives) => {
+                alternatives.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(" | ")
+            }
+        }
+    }
+}
+
 impl ASTNodeTrait for ASTNode {
+    fn to_string(&self) -> String {
+        self.kind.to_string()
+    }
+}
+
+impl ASTNodeTrait for ASTNodeKind {
     fn to_string(&self) -> String {
         match self {
-            ASTNode::TernaryExpression(condition, then_branch, else_branch) => {
+            ASTNodeKind::TernaryExpression(condition, then_branch, else_branch) => {
                 format!("? {} -> {} : {}", condition.to_string(), then_branch.to_string(), else_branch.to_string())
             }
-            ASTNode::ConstDeclaration(name, initializer) => {
+            ASTNodeKind::ConstDeclaration(name, initializer) => {
                 let init_str = if let Some(init) = initializer {
                     format!(" = {}", init.to_string())
                 } else {
@@ -59,25 +288,25 @@ impl ASTNodeTrait for ASTNode {
                 };
                 format!("const {}{}", name, init_str)
             }
-            ASTNode::NullLiteral => "null".to_string(),
-            ASTNode::HashMapLiteral(pairs) => {
+            ASTNodeKind::NullLiteral => "null".to_string(),
+            ASTNodeKind::HashMapLiteral(pairs) => {
                 let pairs_str: Vec<String> = pairs
                     .iter()
                     .map(|(key, value)| format!("{}: {}", key, value.to_string()))
                     .collect();
                 format!("{{{}}}", pairs_str.join(", "))
             }
-            ASTNode::AnonymousFunction(params, body) => {
+            ASTNodeKind::AnonymousFunction(params, body) => {
                 let params_str = params.iter().map(|(param, _)| param.clone()).collect::<Vec<String>>().join(", ");
                 format!("({}) {}", params_str, body.to_string())
             }
-            ASTNode::PostfixUnaryExpression(operator, expression) => {
+            ASTNodeKind::PostfixUnaryExpression(operator, expression) => {
                 format!("{}{}", expression.to_string(), operator)
             }
-            ASTNode::AssignmentOp(operator, left, right) => {
-                format!("{} {} {}", left.to_string(), operator, right.to_string())
+            ASTNodeKind::AssignmentOp(operator, left, right) => {
+                format!("{} {}= {}", left.to_string(), operator, right.to_string())
             }
-            ASTNode::Block(statements) => {
+            ASTNodeKind::Block(statements) => {
                 let mut result = String::new();
                 for statement in statements {
                     result.push_str(&statement.to_string());
@@ -85,14 +314,14 @@ impl ASTNodeTrait for ASTNode {
                 }
                 result
             }
-            ASTNode::ArrayLiteral(elements) => {
+            ASTNodeKind::ArrayLiteral(elements) => {
                 let elements_str: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
                 format!("[{}]", elements_str.join(", "))
             }
-            ASTNode::UnaryExpression(operator, expression) => {
+            ASTNodeKind::UnaryExpression(operator, expression) => {
                 format!("{}{}", operator, expression.to_string())
             }
-            ASTNode::Program(statements) => {
+            ASTNodeKind::Program(statements) => {
                 let mut result = String::new();
                 for statement in statements {
                     result.push_str(&statement.to_string());
@@ -100,7 +329,7 @@ impl ASTNodeTrait for ASTNode {
                 }
                 result
             }
-            ASTNode::VariableDeclaration(name, initializer) => {
+            ASTNodeKind::VariableDeclaration(name, initializer) => {
                 let init_str = if let Some(init) = initializer {
                     format!(" = {}", init.to_string())
                 } else {
@@ -108,21 +337,28 @@ impl ASTNodeTrait for ASTNode {
                 };
                 format!("let {}{}", name, init_str)
             }
-            ASTNode::Assignment(name, value) => format!("{} = {}", name, value.to_string()),
-            ASTNode::BinaryExpression(left, operator, right) => format!("{} {} {}", left.to_string(), operator, right.to_string()),
-            ASTNode::NumberLiteral(value) => value.to_string(),
-            ASTNode::FloatLiteral(value) => value.to_string(),
-            ASTNode::StringLiteral(value) => format!("\"{}\"", value),
-            ASTNode::Identifier(name) => name.clone(),
-            ASTNode::FunctionDeclaration(name, params, body) => {
+            ASTNodeKind::Assignment(name, value) => format!("{} = {}", name, value.to_string()),
+            ASTNodeKind::BinaryExpression(left, operator, right) => format!("{} {} {}", left.to_string(), operator, right.to_string()),
+            ASTNodeKind::NumberLiteral(value) => value.to_string(),
+            ASTNodeKind::FloatLiteral(value) => value.to_string(),
+            ASTNodeKind::StringLiteral(value) => format!("\"{}\"", value),
+            ASTNodeKind::InterpolatedString(parts) => {
+                let parts: String = parts.iter().map(|part| match &part.kind {
+                    ASTNodeKind::StringLiteral(text) => text.clone(),
+                    _ => format!("${{{}}}", part.to_string()),
+                }).collect();
+                format!("\"{}\"", parts)
+            }
+            ASTNodeKind::Identifier(name) => name.clone(),
+            ASTNodeKind::FunctionDeclaration(name, params, body) => {
                 let params_str = params.iter().map(|(param, _)| param.clone()).collect::<Vec<String>>().join(", ");
                 format!("fn {}({}) {}", name, params_str, body.to_string())
             }
-            ASTNode::FunctionCall(name, args) => {
+            ASTNodeKind::FunctionCall(name, args) => {
                 let args_str: Vec<String> = args.iter().map(|(arg, _)| arg.clone().unwrap_or_default()).collect();
                 format!("{}({})", name, args_str.join(", "))
             }
-            ASTNode::IfStatement(condition, then_branch, else_branch) => {
+            ASTNodeKind::IfStatement(condition, then_branch, else_branch) => {
                 let else_str = if let Some(else_branch) = else_branch {
                     format!(" else {}", else_branch.to_string())
                 } else {
@@ -130,25 +366,25 @@ impl ASTNodeTrait for ASTNode {
                 };
                 format!("if {} {}{}", condition.to_string(), then_branch.to_string(), else_str)
             }
-            ASTNode::WhileStatement(condition, body) => format!("while {} {}", condition.to_string(), body.to_string()),
-            ASTNode::ReturnStatement(value) => {
+            ASTNodeKind::WhileStatement(condition, body) => format!("while {} {}", condition.to_string(), body.to_string()),
+            ASTNodeKind::ReturnStatement(value) => {
                 if let Some(value) = value {
                     format!("return {}", value.to_string())
                 } else {
                     "return".to_string()
                 }
             }
-            ASTNode::MemberAccess(object, property) => format!("{}.{}", object.to_string(), property),
-            ASTNode::MethodCall(object, method, args) => {
+            ASTNodeKind::MemberAccess(object, property) => format!("{}.{}", object.to_string(), property),
+            ASTNodeKind::MethodCall(object, method, args) => {
                 let args_str: Vec<String> = args.iter().map(|(arg, _)| arg.clone().unwrap_or_default()).collect();
                 format!("{}.{}/{}", object.to_string(), method, args_str.join(", "))
             }
-            ASTNode::BooleanLiteral(value) => value.to_string(),
-            ASTNode::IndexAccess(array, index) => format!("{}[{}]", array.to_string(), index.to_string()),
-            ASTNode::AssignmentIndex(array, index, value) => format!("{}[{}] = {}", array.to_string(), index.to_string(), value.to_string()),
-            ASTNode::Break => "break".to_string(),
-            ASTNode::Continue => "continue".to_string(),
-            ASTNode::ForStatement(initializer, condition, increment, body) => {
+            ASTNodeKind::BooleanLiteral(value) => value.to_string(),
+            ASTNodeKind::IndexAccess(array, index) => format!("{}[{}]", array.to_string(), index.to_string()),
+            ASTNodeKind::AssignmentIndex(array, index, value) => format!("{}[{}] = {}", array.to_string(), index.to_string(), value.to_string()),
+            ASTNodeKind::Break => "break".to_string(),
+            ASTNodeKind::Continue => "continue".to_string(),
+            ASTNodeKind::ForStatement(initializer, condition, increment, body) => {
                 let init_str = if let Some(init) = initializer {
                     format!("{}; ", init.to_string())
                 } else {
@@ -160,79 +396,126 @@ impl ASTNodeTrait for ASTNode {
                     String::new()
                 };
                 let inc_str = if let Some(inc) = increment {
-                    format!("{}", inc.to_string())
+                    inc.to_string()
                 } else {
                     String::new()
                 };
                 format!("for {}{}{}{}", init_str, cond_str, inc_str, body.to_string())
             }
-            ASTNode::ImmediateInvocation(func, args) => {
+            ASTNodeKind::ForInStatement(bindings, iterable, body) => {
+                let bindings_str = if bindings.len() == 1 {
+                    bindings[0].clone()
+                } else {
+                    format!("({})", bindings.join(", "))
+                };
+                format!("for {} in {} {}", bindings_str, iterable.to_string(), body.to_string())
+            }
+            ASTNodeKind::ImmediateInvocation(func, args) => {
                 let args_str: Vec<String> = args.iter().map(|(arg, _)| arg.clone().unwrap_or_default()).collect();
                 format!("{}({})", func.to_string(), args_str.join(", "))
             }
-            ASTNode::MatchExpression(expr, arms) => {
+            ASTNodeKind::MatchExpression(expr, arms) => {
                 let arms_str: Vec<String> = arms
                     .iter()
-                    .map(|(pattern, result)| format!("{} => {}", pattern.to_string(), result.to_string()))
+                    .map(|(pattern, guard, result)| match guard {
+                        Some(guard) => format!("{} if {} -> {}", pattern.to_string(), guard.to_string(), result.to_string()),
+                        None => format!("{} -> {}", pattern.to_string(), result.to_string()),
+                    })
                     .collect();
                 format!("match {} {{ {} }}", expr.to_string(), arms_str.join(", "))
             }
-            ASTNode::Range(start, end, inclusive) => {
+            ASTNodeKind::Range(start, end, inclusive) => {
                 format!("{}{}{}",
                     start.to_string(),
                     if *inclusive { "..=" } else { ".." },
                     end.to_string()
                 )
             },
+            ASTNodeKind::TryStatement(body, catch, finally) => {
+                let catch_str = if let Some((name, handler)) = catch {
+                    format!(" catch ({}) {}", name, handler.to_string())
+                } else {
+                    String::new()
+                };
+                let finally_str = if let Some(finally) = finally {
+                    format!(" finally {}", finally.to_string())
+                } else {
+                    String::new()
+                };
+                format!("try {}{}{}", body.to_string(), catch_str, finally_str)
+            }
+            ASTNodeKind::ThrowStatement(value) => format!("throw {}", value.to_string()),
+            ASTNodeKind::ImportStatement(path, alias) => match alias {
+                Some(alias) => format!("import \"{}\" as {}", path, alias),
+                None => format!("import \"{}\"", path),
+            },
+            ASTNodeKind::AssignmentMember(object, field, value) => {
+                format!("{}.{} = {}", object.to_string(), field, value.to_string())
+            }
+            ASTNodeKind::StructDeclaration(name, fields) => {
+                let fields_str: Vec<String> = fields
+                    .iter()
+                    .map(|(field, default)| match default {
+                        Some(d) => format!("{} = {}", field, d.to_string()),
+                        None => field.clone(),
+                    })
+                    .collect();
+                format!("struct {} {{ {} }}", name, fields_str.join(", "))
+            }
+            ASTNodeKind::ImplBlock(name, methods) => {
+                let methods_str: Vec<String> = methods.iter().map(|m| m.to_string()).collect();
+                format!("impl {} {{ {} }}", name, methods_str.join(" "))
+            }
+            ASTNodeKind::FromImport(path, names) => format!("from \"{}\" import {{ {} }}", path, names.join(", ")),
         }
     }
 }
 
 // impl ASTNode {
 //     pub fn new_program(statements: Vec<ASTNode>) -> Self {
-//         ASTNode::Program(statements)
+//         ASTNodeKind::Program(statements)
 //     }
 
 //     pub fn new_variable_declaration(name: String, initializer: Option<Box<ASTNode>>) -> Self {
-//         ASTNode::VariableDeclaration(name, initializer)
+//         ASTNodeKind::VariableDeclaration(name, initializer)
 //     }
 
 //     pub fn new_assignment(name: String, value: Box<ASTNode>) -> Self {
-//         ASTNode::Assignment(name, value)
+//         ASTNodeKind::Assignment(name, value)
 //     }
 
 //     pub fn new_binary_expression(left: Box<ASTNode>, operator: String, right: Box<ASTNode>) -> Self {
-//         ASTNode::BinaryExpression(left, operator, right)
+//         ASTNodeKind::BinaryExpression(left, operator, right)
 //     }
 
 //     pub fn new_number_literal(value: i64) -> Self {
-//         ASTNode::NumberLiteral(value)
+//         ASTNodeKind::NumberLiteral(value)
 //     }
 
 //     pub fn new_identifier(name: String) -> Self {
-//         ASTNode::Identifier(name)
+//         ASTNodeKind::Identifier(name)
 //     }
 
 //     pub fn new_function_declaration(name: String, params: Vec<String>, body: Box<ASTNode>) -> Self {
-//         ASTNode::FunctionDeclaration(name, params, body)
+//         ASTNodeKind::FunctionDeclaration(name, params, body)
 //     }
 
 //     pub fn new_function_call(name: String, args: Vec<Box<ASTNode>>) -> Self {
-//         ASTNode::FunctionCall(name, args)
+//         ASTNodeKind::FunctionCall(name, args)
 //     }
 //     pub fn new_if_statement(condition: Box<ASTNode>, then_branch: Box<ASTNode>, else_branch: Option<Box<ASTNode>>) -> Self {
-//         ASTNode::IfStatement(condition, then_branch, else_branch)
+//         ASTNodeKind::IfStatement(condition, then_branch, else_branch)
 //     }
 //     pub fn new_while_statement(condition: Box<ASTNode>, body: Box<ASTNode>) -> Self {
-//         ASTNode::WhileStatement(condition, body)
+//         ASTNodeKind::WhileStatement(condition, body)
 //     }
 //     pub fn new_return_statement(value: Option<Box<ASTNode>>) -> Self {
-//         ASTNode::ReturnStatement(value)
+//         ASTNodeKind::ReturnStatement(value)
 //     }
 //     pub fn new_member_access(object: Box<ASTNode>, property: String) -> Self {
-//         ASTNode::MemberAccess(object, property)
+//         ASTNodeKind::MemberAccess(object, property)
 //     }
 //     pub fn new_method_call(object: Box<ASTNode>, method: String, args: Vec<Box<ASTNode>>) -> Self {
-//         ASTNode::MethodCall(object, method, args)
+//         ASTNodeKind::MethodCall(object, method, args)
 //     }
 // }
\ No newline at end of file
@@ -1,42 +1,75 @@
-use crate::constants::token::{Token, TokenKind, TokenKindTrait};
-use crate::parser::ast::ASTNode;
-use crate::utils::colors::{YELLOW, RED, RESET, BOLD, GREY};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::constants::token::{StringPart, Token, TokenKind};
+use crate::parser::ast::{ASTNode, ASTNodeKind, BinaryOp, Params, Pattern, PostfixOp, Span, UnaryOp};
+use crate::error::error::PlutoError;
+
+/// Value patterns bind tighter than `|`, which separates alternatives.
+const PATTERN_PRECEDENCE: u8 = 4;
 
 pub struct Parser {
     tokens: Vec<Token>,
     pub current: usize,
-    source: String,
+    file: usize,
+    /// Doc comments, keyed by the index of the token that follows them.
+    docs: HashMap<usize, Rc<str>>,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>, source: String) -> Self {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Parser::for_file(tokens, 0)
+    }
+
+    /// Parser whose spans point into the module registered under `file`.
+    pub fn for_file(tokens: Vec<Token>, file: usize) -> Self {
+        let mut kept = Vec::with_capacity(tokens.len());
+        let mut docs = HashMap::new();
+        let mut doc: Option<String> = None;
+        for token in tokens {
+            match token.kind {
+                // consecutive `///` lines form one comment
+                TokenKind::DocComment(text) => match &mut doc {
+                    Some(doc) => {
+                        doc.push('\n');
+                        doc.push_str(&text);
+                    }
+                    None => doc = Some(text),
+                },
+                _ => {
+                    if let Some(doc) = doc.take() {
+                        docs.insert(kept.len(), Rc::from(doc));
+                    }
+                    kept.push(token);
+                }
+            }
+        }
         Parser { 
-            tokens, 
+            tokens: kept, 
             current: 0,
-            source,
+            file,
+            docs,
         }
     }
 
-    pub fn format_error(&self, msg: &str) -> String {
-        let mut error = format!("{}{}Error:{} {}", BOLD, RED, RESET, msg);
-        if let Some(token) = self.tokens.get(self.current.saturating_sub(1)) {
-            let line_number = self.source[..token.position].matches('\n').count() + 1;
-            let line_start = self.source[..token.position].rfind('\n').map_or(0, |i| i + 1);
-            let column = token.position - line_start + 1;
-            
-            error.push_str(&format!("\n{}At line -> {}{}:{}{}{}\n\n", BOLD, GREY, line_number, GREY, column, RESET));
-            if let Some(line) = self.source.lines().nth(line_number - 1) {
-                error.push_str(&format!("{}{}{:>4}{} {}{}{} {}{}\n", BOLD, GREY, line_number, RESET, YELLOW, "|", RESET, line, RESET));
-                error.push_str(&format!("     {}{}{} {}{}{}\n{}", YELLOW, "|", RESET, " ".repeat(column - 1), RED, "^", RESET));
-            }
-        }
-        error
+    /// Syntax error located at the last consumed token.
+    fn error(&self, msg: impl Into<String>) -> PlutoError {
+        let span = self.tokens
+            .get(self.current.saturating_sub(1))
+            .map_or(Span::default(), |token| Span::new(token.position, token.end, token.line));
+        PlutoError::syntax(msg).with_span(span.in_file(self.file))
     }
 
-    pub fn parse(&mut self) -> Result<ASTNode, String> {
-        match self.parse_program() {
-            Ok(ast) => Ok(ast),
-            Err(msg) => Err(self.format_error(&msg))
+    pub fn parse(&mut self) -> Result<ASTNode, PlutoError> {
+        self.parse_program()
+    }
+
+    pub fn parse_expression_only(&mut self) -> Result<ASTNode, PlutoError> {
+        let expr = self.parse_expression(0)?;
+        if self.peek_kind() == Some(&TokenKind::EOF) {
+            Ok(expr)
+        } else {
+            Err(self.error("Unexpected tokens after expression"))
         }
     }
 
@@ -44,58 +77,96 @@ impl Parser {
     // ------------------   STATEMENTS   -------------------
     // -----------------------------------------------------
 
-    fn parse_statement(&mut self) -> Result<ASTNode, String> {
-        if self.match_kind(TokenKind::Let) {
-            self.parse_variable_declaration()
+    fn parse_statement(&mut self) -> Result<ASTNode, PlutoError> {
+        let start = self.current;
+        let kind = if self.match_kind(TokenKind::Let) {
+            self.parse_variable_declaration()?
         } else if self.match_kind(TokenKind::Const) {
-            self.parse_const_declaration()
+            self.parse_const_declaration()?
         } else if self.match_kind(TokenKind::While) {
-            self.parse_while_statement()
+            self.parse_while_statement()?
         } else if self.match_kind(TokenKind::Return) {
-            self.parse_return_statement()
+            self.parse_return_statement()?
         } else if self.match_kind(TokenKind::Fn) {
-            self.parse_function_declaration()
+            self.parse_function_declaration()?
         } else if self.match_kind(TokenKind::If) {
-            self.parse_if_statement()
+            self.parse_if_statement()?
         } else if self.match_kind(TokenKind::Break) {
             self.consume(TokenKind::Semicolon, "Expected ';' after 'break'")?;
-            Ok(ASTNode::Break)
+            ASTNodeKind::Break
         } else if self.match_kind(TokenKind::Continue) {
             self.consume(TokenKind::Semicolon, "Expected ';' after 'continue'")?;
-            Ok(ASTNode::Continue)
+            ASTNodeKind::Continue
         } else if self.match_kind(TokenKind::For) {
-            self.parse_for_statement()
+            self.parse_for_statement()?
+        } else if self.match_kind(TokenKind::Import) {
+            self.parse_import_statement()?
+        } else if self.peek_kind() == Some(&TokenKind::Identifier("from".to_string()))
+            && matches!(self.tokens.get(self.current + 1).map(|t| &t.kind), Some(TokenKind::StringLiteral(_)))
+        {
+            self.advance(); // 'from'
+            self.parse_from_import()?
+        } else if self.match_kind(TokenKind::Struct) {
+            self.parse_struct_declaration()?
+        } else if self.match_kind(TokenKind::Impl) {
+            self.parse_impl_block()?
+        } else if self.match_kind(TokenKind::Try) {
+            self.parse_try_statement()?
+        } else if self.match_kind(TokenKind::Throw) {
+            let value = self.parse_expression(0)?;
+            self.consume(TokenKind::Semicolon, "Expected ';' after throw statement")?;
+            ASTNodeKind::ThrowStatement(Box::new(value))
         } else {
             let expr = self.parse_expression(0)?;
             self.consume(TokenKind::Semicolon, "Expected ';' after expression")?;
-            Ok(expr)
+            return Ok(expr);
+        };
+        Ok(self.documented(self.node(kind, start), start))
+    }
+
+    /// Attaches the doc comment before the token at `start` to the function
+    /// a declaration defines. Only functions carry docs.
+    fn documented(&self, mut node: ASTNode, start: usize) -> ASTNode {
+        let Some(doc) = self.docs.get(&start) else {
+            return node;
+        };
+        match &mut node.kind {
+            ASTNodeKind::VariableDeclaration(_, Some(init)) | ASTNodeKind::ConstDeclaration(_, Some(init))
+                if let ASTNodeKind::AnonymousFunction(..) = init.kind =>
+            {
+                // `let f = (x) -> ...;` documents the function itself
+                init.doc = Some(Rc::clone(doc));
+            }
+            ASTNodeKind::FunctionDeclaration(..) => node.doc = Some(Rc::clone(doc)),
+            _ => {}
         }
+        node
     }
 
-    fn parse_while_statement(&mut self) -> Result<ASTNode, String> {
+    fn parse_while_statement(&mut self) -> Result<ASTNodeKind, PlutoError> {
         self.consume(TokenKind::LParen, "Expected '(' after 'while'")?;
         let condition = self.parse_expression(0)?;
         self.consume(TokenKind::RParen, "Expected ')' after while condition")?;
         let body = self.parse_block_or_single_statement()?;
-        Ok(ASTNode::WhileStatement(Box::new(condition), Box::new(body)))
+        Ok(ASTNodeKind::WhileStatement(Box::new(condition), Box::new(body)))
     }
 
-    fn parse_return_statement(&mut self) -> Result<ASTNode, String> {
+    fn parse_return_statement(&mut self) -> Result<ASTNodeKind, PlutoError> {
         let expr = if self.peek_kind() == Some(&TokenKind::Semicolon) {
             None
         } else {
             Some(Box::new(self.parse_expression(0)?))
         };
         self.consume(TokenKind::Semicolon, "Expected ';' after return statement")?;
-        Ok(ASTNode::ReturnStatement(expr))
+        Ok(ASTNodeKind::ReturnStatement(expr))
     }
 
-    fn parse_function_declaration(&mut self) -> Result<ASTNode, String> {
+    fn parse_function_declaration(&mut self) -> Result<ASTNodeKind, PlutoError> {
         let name = if let Some(TokenKind::Identifier(id)) = self.peek_kind().cloned() {
             self.advance();
             id
         } else {
-            return Err("Expected identifier after 'fn'".into());
+            return Err(self.error("Expected identifier after 'fn'"));
         };
 
         self.consume(TokenKind::LParen, "Expected '(' after function name")?;
@@ -113,7 +184,7 @@ impl Parser {
                     };
                     params.push((param, default_value));
                 } else {
-                    return Err("Expected identifier in function parameters".into());
+                    return Err(self.error("Expected identifier in function parameters"));
                 }
                 if self.peek_kind() == Some(&TokenKind::RParen) {
                     break;
@@ -124,10 +195,10 @@ impl Parser {
 
         self.consume(TokenKind::RParen, "Expected ')' after function parameters")?;
         let body = self.parse_block_or_single_statement()?;
-        Ok(ASTNode::FunctionDeclaration(name, params, Box::new(body)))
+        Ok(ASTNodeKind::FunctionDeclaration(name, params, Rc::new(body)))
     }
 
-    fn parse_function_params(&mut self) -> Result<Vec<(String, Option<Box<ASTNode>>)>, String> {
+    fn parse_function_params(&mut self) -> Result<Params, PlutoError> {
         let mut params = Vec::new();
         if self.peek_kind() != Some(&TokenKind::RParen) {
             loop {
@@ -141,7 +212,7 @@ impl Parser {
                     };
                     params.push((param, default_value));
                 } else {
-                    return Err("Expected identifier in function parameters".into());
+                    return Err(self.error("Expected identifier in function parameters"));
                 }
                 if self.peek_kind() == Some(&TokenKind::RParen) {
                     break;
@@ -152,69 +223,322 @@ impl Parser {
         Ok(params)
     }
 
-    fn parse_variable_declaration(&mut self) -> Result<ASTNode, String> {
+    fn parse_variable_declaration(&mut self) -> Result<ASTNodeKind, PlutoError> {
         let name = if let Some(TokenKind::Identifier(id)) = self.peek_kind().cloned() {
             self.advance();
             id
         } else {
-            return Err("Expected identifier after 'let'".into());
+            return Err(self.error("Expected identifier after 'let'"));
         };
 
         if self.peek_kind() == Some(&TokenKind::Equal) {
             self.advance(); // '='
             let expr = self.parse_expression(0)?;
             self.consume(TokenKind::Semicolon, "Expected ';' after variable declaration")?;
-            Ok(ASTNode::VariableDeclaration(name, Some(Box::new(expr))))
+            Ok(ASTNodeKind::VariableDeclaration(name, Some(Box::new(expr))))
         } else {
             self.consume(TokenKind::Semicolon, "Expected ';' after variable declaration")?;
-            Ok(ASTNode::VariableDeclaration(name, None))
+            Ok(ASTNodeKind::VariableDeclaration(name, None))
         }
     }
 
-    fn parse_const_declaration(&mut self) -> Result<ASTNode, String> {
+    fn parse_const_declaration(&mut self) -> Result<ASTNodeKind, PlutoError> {
         let name = if let Some(TokenKind::Identifier(id)) = self.peek_kind().cloned() {
             self.advance();
             id
         } else {
-            return Err("Expected identifier after 'const'".into());
+            return Err(self.error("Expected identifier after 'const'"));
         };
 
         if self.peek_kind() == Some(&TokenKind::Equal) {
             self.advance(); // '='
             let expr = self.parse_expression(0)?;
             self.consume(TokenKind::Semicolon, "Expected ';' after constant declaration")?;
-            Ok(ASTNode::ConstDeclaration(name, Some(Box::new(expr))))
+            Ok(ASTNodeKind::ConstDeclaration(name, Some(Box::new(expr))))
         } else {
             self.consume(TokenKind::Semicolon, "Expected ';' after constant declaration")?;
-            Ok(ASTNode::ConstDeclaration(name, None))
+            Ok(ASTNodeKind::ConstDeclaration(name, None))
         }
     }
 
-    fn parse_if_statement(&mut self) -> Result<ASTNode, String> {
+    fn parse_if_statement(&mut self) -> Result<ASTNodeKind, PlutoError> {
         let condition = self.parse_expression(0)?;
         let then_branch = self.parse_block_or_single_statement()?;
         
         let mut else_branch = None;
         if self.match_kind(TokenKind::Else) {
+            let if_start = self.current;
             if self.match_kind(TokenKind::If) {
-                else_branch = Some(Box::new(self.parse_if_statement()?));
+                let kind = self.parse_if_statement()?;
+                else_branch = Some(Box::new(self.node(kind, if_start)));
             } else {
                 else_branch = Some(Box::new(self.parse_block_or_single_statement()?));
             }
         }
         
-        Ok(ASTNode::IfStatement(Box::new(condition), Box::new(then_branch), else_branch))
+        Ok(ASTNodeKind::IfStatement(Box::new(condition), Box::new(then_branch), else_branch))
+    }
+
+    fn parse_struct_declaration(&mut self) -> Result<ASTNodeKind, PlutoError> {
+        let name = match self.peek_kind().cloned() {
+            Some(TokenKind::Identifier(id)) => {
+                self.advance();
+                id
+            }
+            _ => return Err(self.error("Expected struct name after 'struct'")),
+        };
+        self.consume(TokenKind::LBrace, "Expected '{' after struct name")?;
+
+        let mut fields: Params = Vec::new();
+        while self.peek_kind() != Some(&TokenKind::RBrace) {
+            let field = match self.peek_kind().cloned() {
+                Some(TokenKind::Identifier(id)) => {
+                    self.advance();
+                    id
+                }
+                _ => return Err(self.error("Expected field name in struct declaration")),
+            };
+            if fields.iter().any(|(f, _)| *f == field) {
+                return Err(self.error(format!("Duplicate field '{}' in struct '{}'", field, name)));
+            }
+            let default_value = if self.match_kind(TokenKind::Equal) {
+                Some(Box::new(self.parse_expression(0)?))
+            } else {
+                None
+            };
+            fields.push((field, default_value));
+            if !self.match_kind(TokenKind::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenKind::RBrace, "Expected '}' after struct fields")?;
+        Ok(ASTNodeKind::StructDeclaration(name, fields))
+    }
+
+    fn parse_impl_block(&mut self) -> Result<ASTNodeKind, PlutoError> {
+        let name = match self.peek_kind().cloned() {
+            Some(TokenKind::Identifier(id)) => {
+                self.advance();
+                id
+            }
+            _ => return Err(self.error("Expected struct name after 'impl'")),
+        };
+        self.consume(TokenKind::LBrace, "Expected '{' after impl name")?;
+
+        let mut methods = Vec::new();
+        while self.peek_kind() != Some(&TokenKind::RBrace) {
+            let start = self.current;
+            self.consume(TokenKind::Fn, "Expected 'fn' in impl block")?;
+            let method = self.parse_function_declaration()?;
+            methods.push(self.documented(self.node(method, start), start));
+        }
+        self.consume(TokenKind::RBrace, "Expected '}' after impl block")?;
+        Ok(ASTNodeKind::ImplBlock(name, methods))
+    }
+
+    fn parse_import_statement(&mut self) -> Result<ASTNodeKind, PlutoError> {
+        let path = if let Some(TokenKind::StringLiteral(path)) = self.peek_kind().cloned() {
+            self.advance();
+            path
+        } else {
+            return Err(self.error("Expected module path string after 'import'"));
+        };
+
+        let mut alias = None;
+        if self.peek_kind() == Some(&TokenKind::Identifier("as".to_string())) {
+            self.advance(); // 'as'
+            if let Some(TokenKind::Identifier(name)) = self.peek_kind().cloned() {
+                self.advance();
+                alias = Some(name);
+            } else {
+                return Err(self.error("Expected identifier after 'as'"));
+            }
+        }
+        self.consume(TokenKind::Semicolon, "Expected ';' after import statement")?;
+        Ok(ASTNodeKind::ImportStatement(path, alias))
+    }
+
+    fn parse_from_import(&mut self) -> Result<ASTNodeKind, PlutoError> {
+        let path = if let TokenKind::StringLiteral(path) = self.advance().kind.clone() {
+            path
+        } else {
+            return Err(self.error("Expected module path string after 'from'"));
+        };
+        self.consume(TokenKind::Import, "Expected 'import' after module path")?;
+        self.consume(TokenKind::LBrace, "Expected '{' after 'import'")?;
+
+        let mut names = Vec::new();
+        loop {
+            if let Some(TokenKind::Identifier(name)) = self.peek_kind().cloned() {
+                self.advance();
+                names.push(name);
+            } else {
+                return Err(self.error("Expected identifier in import list"));
+            }
+            if self.peek_kind() == Some(&TokenKind::RBrace) {
+                break;
+            }
+            self.consume(TokenKind::Comma, "Expected ',' or '}' in import list")?;
+        }
+        self.consume(TokenKind::RBrace, "Expected '}' after import list")?;
+        self.consume(TokenKind::Semicolon, "Expected ';' after import statement")?;
+        Ok(ASTNodeKind::FromImport(path, names))
     }
 
-    fn parse_block_or_single_statement(&mut self) -> Result<ASTNode, String> {
+    /// A match arm pattern: alternatives separated by `|`.
+    fn parse_pattern(&mut self) -> Result<Pattern, PlutoError> {
+        let mut alternatives = vec![self.parse_single_pattern()?];
+        while self.match_kind(TokenKind::Pipe) {
+            alternatives.push(self.parse_single_pattern()?);
+        }
+        if alternatives.len() == 1 {
+            Ok(alternatives.pop().unwrap())
+        } else {
+            Ok(Pattern::Or(alternatives))
+        }
+    }
+
+    fn parse_single_pattern(&mut self) -> Result<Pattern, PlutoError> {
+        match self.peek_kind().cloned() {
+            Some(TokenKind::Underscore) => {
+                self.advance();
+                Ok(Pattern::Wildcard)
+            }
+            Some(TokenKind::Identifier(name)) if self.is_pattern_end(self.current + 1) => {
+                self.advance();
+                Ok(Pattern::Binding(name))
+            }
+            Some(TokenKind::LBracket) => {
+                self.advance();
+                self.parse_array_pattern()
+            }
+            Some(TokenKind::LBrace) => {
+                self.advance();
+                self.parse_map_pattern()
+            }
+            _ => {
+                let expr = self.parse_expression(PATTERN_PRECEDENCE)?;
+                match expr.kind {
+                    ASTNodeKind::Range(start, end, inclusive) => Ok(Pattern::Range(start, end, inclusive)),
+                    kind => Ok(Pattern::Value(Box::new(ASTNode::new(kind, expr.span)))),
+                }
+            }
+        }
+    }
+
+    /// Whether the token at `index` ends a pattern, making a lone identifier before it a binding.
+    fn is_pattern_end(&self, index: usize) -> bool {
+        matches!(
+            self.tokens.get(index).map(|t| &t.kind),
+            Some(TokenKind::ArrowFunc | TokenKind::If | TokenKind::Pipe | TokenKind::Comma | TokenKind::RBracket | TokenKind::RBrace)
+        )
+    }
+
+    fn parse_array_pattern(&mut self) -> Result<Pattern, PlutoError> {
+        let mut elements = Vec::new();
+        let mut rest = None;
+        while self.peek_kind() != Some(&TokenKind::RBracket) {
+            if self.match_kind(TokenKind::DotDot) {
+                if rest.is_some() {
+                    return Err(self.error("Only one '..' is allowed in an array pattern"));
+                }
+                rest = match self.peek_kind().cloned() {
+                    Some(TokenKind::Identifier(name)) => {
+                        self.advance();
+                        Some(Some(name))
+                    }
+                    _ => Some(None),
+                };
+            } else {
+                if rest.is_some() {
+                    return Err(self.error("'..' must be the last element of an array pattern"));
+                }
+                elements.push(self.parse_pattern()?);
+            }
+            if !self.match_kind(TokenKind::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenKind::RBracket, "Expected ']' after array pattern")?;
+        Ok(Pattern::Array(elements, rest))
+    }
+
+    fn parse_map_pattern(&mut self) -> Result<Pattern, PlutoError> {
+        let mut entries = Vec::new();
+        while self.peek_kind() != Some(&TokenKind::RBrace) {
+            let (key, shorthand) = match self.peek_kind().cloned() {
+                Some(TokenKind::Identifier(name)) => (name, true),
+                Some(TokenKind::StringLiteral(name)) => (name, false),
+                _ => return Err(self.error("Expected key in map pattern")),
+            };
+            self.advance();
+            let pattern = if self.match_kind(TokenKind::Colon) {
+                self.parse_pattern()?
+            } else if shorthand {
+                Pattern::Binding(key.clone())
+            } else {
+                return Err(self.error("Expected ':' after string key in map pattern"));
+            };
+            entries.push((key, pattern));
+            if !self.match_kind(TokenKind::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenKind::RBrace, "Expected '}' after map pattern")?;
+        Ok(Pattern::Map(entries))
+    }
+
+    fn parse_try_statement(&mut self) -> Result<ASTNodeKind, PlutoError> {
+        if self.peek_kind() != Some(&TokenKind::LBrace) {
+            return Err(self.error("Expected '{' after 'try'"));
+        }
+        let body = self.parse_block_or_single_statement()?;
+
+        let mut catch = None;
+        if self.match_kind(TokenKind::Catch) {
+            self.consume(TokenKind::LParen, "Expected '(' after 'catch'")?;
+            let name = if let Some(TokenKind::Identifier(id)) = self.peek_kind().cloned() {
+                self.advance();
+                id
+            } else {
+                return Err(self.error("Expected identifier in catch clause"));
+            };
+            self.consume(TokenKind::RParen, "Expected ')' after catch variable")?;
+            if self.peek_kind() != Some(&TokenKind::LBrace) {
+                return Err(self.error("Expected '{' after catch clause"));
+            }
+            catch = Some((name, Box::new(self.parse_block_or_single_statement()?)));
+        }
+
+        let mut finally = None;
+        if self.match_kind(TokenKind::Finally) {
+            if self.peek_kind() != Some(&TokenKind::LBrace) {
+                return Err(self.error("Expected '{' after 'finally'"));
+            }
+            finally = Some(Box::new(self.parse_block_or_single_statement()?));
+        }
+
+        if catch.is_none() && finally.is_none() {
+            return Err(self.error("Expected 'catch' or 'finally' after try block"));
+        }
+        Ok(ASTNodeKind::TryStatement(Box::new(body), catch, finally))
+    }
+
+    fn parse_block_or_single_statement(&mut self) -> Result<ASTNode, PlutoError> {
+        let start = self.current;
         if self.match_kind(TokenKind::LBrace) {
             let mut statements = Vec::new();
             while !self.match_kind(TokenKind::RBrace) && self.current < self.tokens.len() {
                 statements.push(self.parse_statement()?);
             }
-            Ok(ASTNode::Block(statements))
+            Ok(self.node(ASTNodeKind::Block(statements), start))
         } else {
-            self.parse_statement()
+            // `if (c) let x = 1;` declares `x` only for its own body
+            let statement = self.parse_statement()?;
+            if statement.is_declaration() {
+                return Ok(self.node(ASTNodeKind::Block(vec![statement]), start));
+            }
+            Ok(statement)
         }
     }
 
@@ -222,17 +546,17 @@ impl Parser {
     // ------------------   EXPRESSIONS  -------------------
     // -----------------------------------------------------
 
-    fn parse_expression(&mut self, min_prec: u8) -> Result<ASTNode, String> {
+    fn parse_expression(&mut self, min_prec: u8) -> Result<ASTNode, PlutoError> {
         let mut left = self.parse_primary()?;
 
-        if let ASTNode::AnonymousFunction(_, _) = left {
+        if let ASTNodeKind::AnonymousFunction(_, _) = left.kind {
             if self.peek_kind() == Some(&TokenKind::LParen) {
                 self.advance(); // '('
                 let mut args = Vec::new();
                 if self.peek_kind() != Some(&TokenKind::RParen) {
                     loop {
                         let mut arg_name = None;
-                        if let Some(&TokenKind::Identifier(ref name)) = self.peek_kind() {
+                        if let Some(TokenKind::Identifier(name)) = self.peek_kind() {
                             let next_token = self.tokens.get(self.current + 1).map(|t| &t.kind);
                             if next_token == Some(&TokenKind::Equal) {
                                 let name = name.clone();
@@ -250,36 +574,52 @@ impl Parser {
                     }
                 }
                 self.consume(TokenKind::RParen, "Expected ')' after arguments")?;
-                left = ASTNode::ImmediateInvocation(Box::new(left), args);
+                let span = self.extend(left.span);
+                left = ASTNode::new(ASTNodeKind::ImmediateInvocation(Box::new(left), args), span);
             }
             return Ok(left);
         }
 
         if self.peek_kind() == Some(&TokenKind::Equal) {
-            if let ASTNode::Identifier(ref name) = left {
+            if let ASTNodeKind::Identifier(ref name) = left.kind {
                 self.advance(); // '='
                 let right = self.parse_expression(0)?;
-                return Ok(ASTNode::Assignment(name.clone(), Box::new(right)));
+                let span = left.span.to(right.span);
+                return Ok(ASTNode::new(ASTNodeKind::Assignment(name.clone(), Box::new(right)), span));
             }
-            if let ASTNode::IndexAccess(array, index) = left {
+            let left_span = left.span;
+            if let ASTNodeKind::IndexAccess(array, index) = left.kind {
                 self.advance(); // '='
                 let right = self.parse_expression(0)?;
-                return Ok(ASTNode::AssignmentIndex(array, index, Box::new(right)));
+                let span = left_span.to(right.span);
+                return Ok(ASTNode::new(ASTNodeKind::AssignmentIndex(array, index, Box::new(right)), span));
+            }
+            if let ASTNodeKind::MemberAccess(object, field) = left.kind {
+                self.advance(); // '='
+                let right = self.parse_expression(0)?;
+                let span = left_span.to(right.span);
+                return Ok(ASTNode::new(ASTNodeKind::AssignmentMember(object, field, Box::new(right)), span));
             }
         }
 
         if let Some(kind) = self.peek_kind() {
             let op = match kind {
-                TokenKind::PlusEqual => Some("+="),
-                TokenKind::MinusEqual => Some("-="),
-                TokenKind::StarEqual => Some("*="),
-                TokenKind::SlashEqual => Some("/="),
+                TokenKind::PlusEqual => Some(BinaryOp::Add),
+                TokenKind::MinusEqual => Some(BinaryOp::Sub),
+                TokenKind::StarEqual => Some(BinaryOp::Mul),
+                TokenKind::SlashEqual => Some(BinaryOp::Div),
+                TokenKind::AmpersandEqual => Some(BinaryOp::BitAnd),
+                TokenKind::PipeEqual => Some(BinaryOp::BitOr),
+                TokenKind::CaretEqual => Some(BinaryOp::BitXor),
+                TokenKind::ShiftLeftEqual => Some(BinaryOp::Shl),
+                TokenKind::ShiftRightEqual => Some(BinaryOp::Shr),
                 _ => None,
             };
-            if let Some(op_str) = op {
+            if let Some(op) = op {
                 self.advance();
                 let right = self.parse_expression(0)?;
-                return Ok(ASTNode::AssignmentOp(op_str.to_string(), Box::new(left), Box::new(right)));
+                let span = left.span.to(right.span);
+                return Ok(ASTNode::new(ASTNodeKind::AssignmentOp(op, Box::new(left), Box::new(right)), span));
             }
         }
 
@@ -287,53 +627,84 @@ impl Parser {
             match kind {
                 TokenKind::DotDot => {
                     self.advance();
-                    let right = self.parse_expression(0)?;
-                    return Ok(ASTNode::Range(Box::new(left), Box::new(right), false));
+                    let right = self.parse_expression(min_prec)?;
+                    let span = left.span.to(right.span);
+                    return Ok(ASTNode::new(ASTNodeKind::Range(Box::new(left), Box::new(right), false), span));
                 }
                 TokenKind::DotDotEqual => {
                     self.advance();
-                    let right = self.parse_expression(0)?;
-                    return Ok(ASTNode::Range(Box::new(left), Box::new(right), true));
+                    let right = self.parse_expression(min_prec)?;
+                    let span = left.span.to(right.span);
+                    return Ok(ASTNode::new(ASTNodeKind::Range(Box::new(left), Box::new(right), true), span));
                 }
                 _ => {}
             }
         }
 
-        while let Some(op_prec) = self.current_precedence() {
+        while let Some((op, op_prec)) = self.current_operator() {
             if op_prec < min_prec {
                 break;
             }
-            let op_token = self.advance().clone();
+            self.advance();
             let right = self.parse_expression(op_prec + 1)?;
-            left = ASTNode::BinaryExpression(
-                Box::new(left),
-                op_token.kind.to_string(),
-                Box::new(right),
+            let span = left.span.to(right.span);
+            left = ASTNode::new(
+                ASTNodeKind::BinaryExpression(
+                    Box::new(left),
+                    op,
+                    Box::new(right),
+                ),
+                span,
             );
         }
 
         Ok(left)
     }
 
-    fn current_precedence(&self) -> Option<u8> {
-        match self.peek_kind()? {
-            TokenKind::Or | TokenKind::Elvis => Some(1),
-            TokenKind::And => Some(2),
-            TokenKind::EqualsEqual | TokenKind::NotEqual => Some(3),
-            TokenKind::LessThan | TokenKind::GreaterThan | TokenKind::LessThanEqual | TokenKind::GreaterThanEqual => Some(4),
-            TokenKind::Plus | TokenKind::Minus => Some(5),
-            TokenKind::Star | TokenKind::Slash | TokenKind::Percent => Some(6),
-            _ => None,
-        }
+    /// The infix operator at the current token and its precedence.
+    fn current_operator(&self) -> Option<(BinaryOp, u8)> {
+        Some(match self.peek_kind()? {
+            TokenKind::Or => (BinaryOp::Or, 1),
+            TokenKind::Elvis => (BinaryOp::Elvis, 1),
+            TokenKind::And => (BinaryOp::And, 2),
+            TokenKind::Pipe => (BinaryOp::BitOr, 3),
+            TokenKind::Caret => (BinaryOp::BitXor, 4),
+            TokenKind::Ampersand => (BinaryOp::BitAnd, 5),
+            TokenKind::EqualsEqual => (BinaryOp::Eq, 6),
+            TokenKind::NotEqual => (BinaryOp::Ne, 6),
+            TokenKind::LessThan => (BinaryOp::Lt, 7),
+            TokenKind::GreaterThan => (BinaryOp::Gt, 7),
+            TokenKind::LessThanEqual => (BinaryOp::Le, 7),
+            TokenKind::GreaterThanEqual => (BinaryOp::Ge, 7),
+            TokenKind::ShiftLeft => (BinaryOp::Shl, 8),
+            TokenKind::ShiftRight => (BinaryOp::Shr, 8),
+            TokenKind::Plus => (BinaryOp::Add, 9),
+            TokenKind::Minus => (BinaryOp::Sub, 9),
+            TokenKind::Star => (BinaryOp::Mul, 10),
+            TokenKind::Slash => (BinaryOp::Div, 10),
+            TokenKind::Percent => (BinaryOp::Rem, 10),
+            _ => return None,
+        })
     }
 
-    fn parse_primary(&mut self) -> Result<ASTNode, String> {
-        let mut node = match self.advance().kind.clone() {
-            TokenKind::Number(n)            => ASTNode::NumberLiteral(n),
-            TokenKind::Float(f)             => ASTNode::FloatLiteral(f),
-            TokenKind::Boolean(b)          => ASTNode::BooleanLiteral(b),
-            TokenKind::StringLiteral(s)  => ASTNode::StringLiteral(s),
-            TokenKind::Underscore => ASTNode::Identifier("_".to_string()),
+    fn parse_primary(&mut self) -> Result<ASTNode, PlutoError> {
+        let start = self.current;
+        let kind = match self.advance().kind.clone() {
+            TokenKind::Number(n)            => ASTNodeKind::NumberLiteral(n),
+            TokenKind::Float(f)             => ASTNodeKind::FloatLiteral(f),
+            TokenKind::Boolean(b)          => ASTNodeKind::BooleanLiteral(b),
+            TokenKind::StringLiteral(s)  => ASTNodeKind::StringLiteral(s),
+            TokenKind::InterpolatedString(parts) => {
+                let mut nodes = Vec::new();
+                for part in parts {
+                    nodes.push(Box::new(match part {
+                        StringPart::Text(text) => self.node(ASTNodeKind::StringLiteral(text), start),
+                        StringPart::Code(tokens) => self.parse_interpolation(tokens)?,
+                    }));
+                }
+                ASTNodeKind::InterpolatedString(nodes)
+            }
+            TokenKind::Underscore => ASTNodeKind::Identifier("_".to_string()),
             TokenKind::Match => {
                 let expr = self.parse_expression(0)?;
                 self.consume(TokenKind::LBrace, "Expected '{' after match expression")?;
@@ -342,40 +713,44 @@ impl Parser {
                 let mut has_default = false;
 
                 while !self.match_kind(TokenKind::RBrace) {
-                    let pattern = self.parse_expression(0)?;
-                    if let ASTNode::Identifier(ref name) = pattern {
-                        if name == "_" {
-                            if has_default {
-                                return Err("Multiple default patterns in match expression".into());
-                            }
-                            has_default = true;
+                    let pattern = self.parse_pattern()?;
+                    let guard = if self.match_kind(TokenKind::If) {
+                        Some(Box::new(self.parse_expression(0)?))
+                    } else {
+                        None
+                    };
+                    if pattern == Pattern::Wildcard && guard.is_none() {
+                        if has_default {
+                            return Err(self.error("Multiple default patterns in match expression"));
                         }
+                        has_default = true;
                     }
 
                     self.consume(TokenKind::ArrowFunc, "Expected '->' after match pattern")?;
                     
                     let result = if self.peek_kind() == Some(&TokenKind::LBrace) {
                         let block = self.parse_block_or_single_statement()?;
-                        if !self.peek_kind().map_or(false, |k| k == &TokenKind::RBrace) {
+                        if self.peek_kind() != Some(&TokenKind::RBrace) {
                             self.consume(TokenKind::Comma, "Expected ',' after match arm block")?;
                         }
                         block
                     } else {
                         let expr = self.parse_expression(0)?;
-                        if !self.peek_kind().map_or(false, |k| k == &TokenKind::RBrace) {
+                        if self.peek_kind() != Some(&TokenKind::RBrace) {
                             self.consume(TokenKind::Comma, "Expected ',' after match arm")?;
                         }
                         expr
                     };
                     
-                    arms.push((Box::new(pattern), Box::new(result)));
+                    arms.push((pattern, guard, Box::new(result)));
                 }
 
-                let match_expr = ASTNode::MatchExpression(Box::new(expr), arms);
-                ASTNode::ImmediateInvocation(
-                    Box::new(ASTNode::AnonymousFunction(
-                        vec![],
-                        Box::new(match_expr)
+                let span = self.span_from(start);
+                let match_expr = ASTNode::new(ASTNodeKind::MatchExpression(Box::new(expr), arms), span);
+                ASTNodeKind::ImmediateInvocation(
+                    Box::new(ASTNode::new(
+                        ASTNodeKind::AnonymousFunction(vec![], Rc::new(match_expr)),
+                        span,
                     )),
                     vec![]
                 )
@@ -393,7 +768,7 @@ impl Parser {
                     }
                 }
                 self.consume(TokenKind::RBracket, "Expected ']' after array literal")?;
-                ASTNode::ArrayLiteral(elements)
+                ASTNodeKind::ArrayLiteral(elements)
             }
             TokenKind::Identifier(s)     => {
                 if self.peek_kind() == Some(&TokenKind::LParen) {
@@ -402,7 +777,7 @@ impl Parser {
                     if self.peek_kind() != Some(&TokenKind::RParen) {
                         loop {
                             let mut arg_name = None;
-                            if let Some(&TokenKind::Identifier(ref name)) = self.peek_kind() {
+                            if let Some(TokenKind::Identifier(name)) = self.peek_kind() {
                                 let next_token = self.tokens.get(self.current + 1).map(|t| &t.kind);
                                 if next_token == Some(&TokenKind::Equal) {
                                     let name = name.clone();
@@ -420,9 +795,9 @@ impl Parser {
                         }
                     }
                     self.consume(TokenKind::RParen, "Expected ')' after arguments")?;
-                    ASTNode::FunctionCall(s, args)
+                    ASTNodeKind::FunctionCall(s, args)
                 } else {
-                    ASTNode::Identifier(s)
+                    ASTNodeKind::Identifier(s)
                 }
             }
             TokenKind::LParen => {
@@ -469,19 +844,17 @@ impl Parser {
                     self.consume(TokenKind::RParen, "Expected ')' after function parameters")?;
                     self.consume(TokenKind::ArrowFunc, "Expected '->' after function parameters")?;
                     let body = if self.peek_kind() == Some(&TokenKind::LBrace) {
-                        let block = self.parse_block_or_single_statement()?;
-                        block
+                        self.parse_block_or_single_statement()?
                     } else {
-                        let expr = self.parse_expression(0)?;
-                        expr
+                        self.parse_expression(0)?
                     };
-                    let func = ASTNode::AnonymousFunction(params, Box::new(body));
-                    return Ok(func);
+                    let func = ASTNodeKind::AnonymousFunction(params, Rc::new(body));
+                    return Ok(self.node(func, start));
                 } else {
                     self.current = start_pos;
                     let expr = self.parse_expression(0)?;
                     self.consume(TokenKind::RParen, "Expected ')' after expression")?;
-                    return Ok(expr);
+                    expr.kind
                 }
             }
             TokenKind::QuestionMark => {
@@ -490,13 +863,17 @@ impl Parser {
                 let then_branch = self.parse_expression(0)?;
                 self.consume(TokenKind::Colon, "Expected ':' after then-branch in ternary expression")?;
                 let else_branch = self.parse_expression(0)?;
-                ASTNode::TernaryExpression(Box::new(condition), Box::new(then_branch), Box::new(else_branch))
+                ASTNodeKind::TernaryExpression(Box::new(condition), Box::new(then_branch), Box::new(else_branch))
             }
             TokenKind::Not => {
                 let expr = self.parse_primary()?;
-                ASTNode::UnaryExpression("!".to_string(), Box::new(expr))
+                ASTNodeKind::UnaryExpression(UnaryOp::Not, Box::new(expr))
             }
-            TokenKind::Null => ASTNode::NullLiteral,
+            TokenKind::Tilde => {
+                let expr = self.parse_primary()?;
+                ASTNodeKind::UnaryExpression(UnaryOp::BitNot, Box::new(expr))
+            }
+            TokenKind::Null => ASTNodeKind::NullLiteral,
             TokenKind::LBrace => {
                 // hashmap
                 let mut pairs = Vec::new();
@@ -505,7 +882,7 @@ impl Parser {
                         let key = match self.advance().kind.clone() {
                             TokenKind::StringLiteral(s) => s,
                             TokenKind::Identifier(s) => s,
-                            other => return Err(format!("Expected string or identifier as key, got {:?}", other)),
+                            other => return Err(self.error(format!("Expected string or identifier as key, got {:?}", other))),
                         };
                         self.consume(TokenKind::Colon, "Expected ':' after key in hash map literal")?;
                         let value = self.parse_expression(0)?;
@@ -517,16 +894,19 @@ impl Parser {
                     }
                 }
                 self.consume(TokenKind::RBrace, "Expected '}' after hash map literal")?;
-                ASTNode::HashMapLiteral(pairs)
+                ASTNodeKind::HashMapLiteral(pairs)
             }
             TokenKind::While => {
-                return self.parse_while_statement();
+                let kind = self.parse_while_statement()?;
+                return Ok(self.node(kind, start));
             }
             TokenKind::For => {
-                return self.parse_for_statement();
+                let kind = self.parse_for_statement()?;
+                return Ok(self.node(kind, start));
             }
-            other => return Err(format!("Unexpected token: {:?}", other)),
+            other => return Err(self.error(format!("Unexpected token: {:?}", other))),
         };
+        let mut node = self.node(kind, start);
 
         loop {
             if self.peek_kind() == Some(&TokenKind::Dot) {
@@ -535,7 +915,7 @@ impl Parser {
                 let member_name = if let TokenKind::Identifier(ref s) = member_token.kind {
                     s.clone()
                 } else {
-                    return Err("Expected identifier after '.'".into());
+                    return Err(self.error("Expected identifier after '.'"));
                 };
                 if self.peek_kind() == Some(&TokenKind::LParen) {
                     self.advance(); // '('
@@ -545,12 +925,12 @@ impl Parser {
                             let mut arg_name = None;
                             let next_token = self.peek_kind().cloned();
                             let next_next_token = self.tokens.get(self.current + 1).map(|t| &t.kind);
-                            if let Some(TokenKind::Identifier(name)) = next_token {
-                                if next_next_token == Some(&TokenKind::Equal) {
-                                    self.advance(); // consume identifier
-                                    self.advance(); // consume equals
-                                    arg_name = Some(name);
-                                }
+                            if let Some(TokenKind::Identifier(name)) = next_token
+                                && next_next_token == Some(&TokenKind::Equal)
+                            {
+                                self.advance(); // consume identifier
+                                self.advance(); // consume equals
+                                arg_name = Some(name);
                             }
                             let arg = self.parse_expression(0)?;
                             args.push((arg_name, Box::new(arg)));
@@ -561,22 +941,27 @@ impl Parser {
                         }
                     }
                     self.consume(TokenKind::RParen, "Expected ')' after arguments")?;
-                    node = ASTNode::MethodCall(Box::new(node), member_name, args);
+                    let span = self.extend(node.span);
+                    node = ASTNode::new(ASTNodeKind::MethodCall(Box::new(node), member_name, args), span);
                 } else {
-                    node = ASTNode::MemberAccess(Box::new(node), member_name);
+                    let span = self.extend(node.span);
+                    node = ASTNode::new(ASTNodeKind::MemberAccess(Box::new(node), member_name), span);
                 }
             } else if self.peek_kind() == Some(&TokenKind::LBracket) {
                 self.advance(); // '['
                 let index_expr = self.parse_expression(0)?;
                 self.consume(TokenKind::RBracket, "Expected ']' after index")?;
-                node = ASTNode::IndexAccess(Box::new(node), Box::new(index_expr));
+                let span = self.extend(node.span);
+                node = ASTNode::new(ASTNodeKind::IndexAccess(Box::new(node), Box::new(index_expr)), span);
             } else if self.peek_kind() == Some(&TokenKind::PlusPlus) {
                 self.advance();
-                node = ASTNode::PostfixUnaryExpression("++".to_string(), Box::new(node));
+                let span = self.extend(node.span);
+                node = ASTNode::new(ASTNodeKind::PostfixUnaryExpression(PostfixOp::Increment, Box::new(node)), span);
                 continue;
             } else if self.peek_kind() == Some(&TokenKind::MinusMinus) {
                 self.advance();
-                node = ASTNode::PostfixUnaryExpression("--".to_string(), Box::new(node));
+                let span = self.extend(node.span);
+                node = ASTNode::new(ASTNodeKind::PostfixUnaryExpression(PostfixOp::Decrement, Box::new(node)), span);
                 continue;
             } else {
                 break;
@@ -586,6 +971,17 @@ impl Parser {
         Ok(node)
     }
 
+    /// Parses the tokens of one `${}` in a string literal.
+    fn parse_interpolation(&self, tokens: Vec<Token>) -> Result<ASTNode, PlutoError> {
+        let mut parser = Parser::for_file(tokens, self.file);
+        let expr = parser.parse_expression(0)?;
+        if parser.peek_kind() != Some(&TokenKind::EOF) {
+            parser.advance();
+            return Err(parser.error("Expected '}' after expression in string"));
+        }
+        Ok(expr)
+    }
+
     // -----------------------------------------------------
     // ------------------   HELPERS      -------------------
     // -----------------------------------------------------
@@ -599,11 +995,11 @@ impl Parser {
         }
     }
 
-    fn consume(&mut self, kind: TokenKind, msg: &str) -> Result<(), String> {
+    fn consume(&mut self, kind: TokenKind, msg: &str) -> Result<(), PlutoError> {
         if self.match_kind(kind.clone()) {
             Ok(())
         } else {
-            Err(msg.into())
+            Err(self.error(msg))
         }
     }
 
@@ -617,7 +1013,29 @@ impl Parser {
         tok
     }
 
-    fn parse_for_statement(&mut self) -> Result<ASTNode, String> {
+    fn span_from(&self, start: usize) -> Span {
+        let (begin, line) = self.tokens.get(start).map_or((0, 0), |t| (t.position, t.line));
+        let end = self.tokens.get(self.current.saturating_sub(1)).map_or(begin, |t| t.end);
+        Span::new(begin, end.max(begin), line).in_file(self.file)
+    }
+
+    fn extend(&self, span: Span) -> Span {
+        let end = self.tokens.get(self.current.saturating_sub(1)).map_or(span.end, |t| t.end);
+        Span::new(span.start, end.max(span.end), span.line).in_file(span.file)
+    }
+
+    fn node(&self, kind: ASTNodeKind, start: usize) -> ASTNode {
+        ASTNode::new(kind, self.span_from(start))
+    }
+
+    fn parse_for_statement(&mut self) -> Result<ASTNodeKind, PlutoError> {
+        if let Some(bindings) = self.parse_for_in_bindings() {
+            self.consume(TokenKind::In, "Expected 'in' after loop variables")?;
+            let iterable = self.parse_expression(0)?;
+            let body = self.parse_block_or_single_statement()?;
+            return Ok(ASTNodeKind::ForInStatement(bindings, Box::new(iterable), Box::new(body)));
+        }
+
         self.consume(TokenKind::LParen, "Expected '(' after 'for'")?;
         
         let initializer = if self.peek_kind() != Some(&TokenKind::Semicolon) {
@@ -643,15 +1061,53 @@ impl Parser {
         self.consume(TokenKind::RParen, "Expected ')' after for-loop increment")?;
 
         let body = self.parse_block_or_single_statement()?;
-        Ok(ASTNode::ForStatement(initializer, condition, increment, Box::new(body)))
+        Ok(ASTNodeKind::ForStatement(initializer, condition, increment, Box::new(body)))
+    }
+
+    /// Consumes `x` or `(a, b, ...)` when followed by `in`; otherwise leaves
+    /// the tokens alone so the C-style form can be parsed.
+    fn parse_for_in_bindings(&mut self) -> Option<Vec<String>> {
+        let kind_at = |pos: usize| self.tokens.get(pos).map(|t| &t.kind);
+        let mut names = Vec::new();
+        let mut pos = self.current;
+
+        if let Some(TokenKind::Identifier(name)) = kind_at(pos) {
+            names.push(name.clone());
+            pos += 1;
+        } else if kind_at(pos) == Some(&TokenKind::LParen) {
+            pos += 1;
+            loop {
+                let Some(TokenKind::Identifier(name)) = kind_at(pos) else {
+                    return None;
+                };
+                names.push(name.clone());
+                pos += 1;
+                match kind_at(pos) {
+                    Some(TokenKind::Comma) => pos += 1,
+                    Some(TokenKind::RParen) => {
+                        pos += 1;
+                        break;
+                    }
+                    _ => return None,
+                }
+            }
+        } else {
+            return None;
+        }
+
+        if kind_at(pos) != Some(&TokenKind::In) {
+            return None;
+        }
+        self.current = pos;
+        Some(names)
     }
 
-    fn parse_program(&mut self) -> Result<ASTNode, String> {
+    fn parse_program(&mut self) -> Result<ASTNode, PlutoError> {
         let mut statements = Vec::new();
         while self.current < self.tokens.len() && self.tokens[self.current].kind != TokenKind::EOF {
             let stmt = self.parse_statement()?;
             statements.push(stmt);
         }
-        Ok(ASTNode::Program(statements))
+        Ok(self.node(ASTNodeKind::Program(statements), 0))
     }
 }
@@ -1,7 +1,5 @@
-use crate::lexer::tokenizer::tokenize;
-use crate::parser::parser::Parser;
-use crate::evaluator::evaluator::Evaluator;
-use crate::utils::colors::{self, blue, green, red, bold};
+use pluto_lang::Interpreter;
+use pluto_lang::utils::colors::{self, blue, green, red, bold};
 use std::io::{self, Write};
 
 const HELP_TEXT: &str = r#"
@@ -36,7 +34,7 @@ fn parse_command(cmd: &str) -> Command {
     }
 }
 
-fn handle_special_command(cmd: &str, env: &mut Evaluator) -> bool {
+fn handle_special_command(cmd: &str, env: &mut Interpreter) -> bool {
     match parse_command(cmd) {
         Command::Help => {
             println!("{}", blue(HELP_TEXT));
@@ -52,7 +50,7 @@ fn handle_special_command(cmd: &str, env: &mut Evaluator) -> bool {
             std::process::exit(0);
         }
         Command::Reset => {
-            *env = Evaluator::new();
+            env.reset();
             println!("{}", green("Environment reset."));
             true
         }
@@ -60,28 +58,21 @@ fn handle_special_command(cmd: &str, env: &mut Evaluator) -> bool {
     }
 }
 
-fn evaluate_input(input: &str, env: &mut Evaluator) {
-    let tokens = tokenize(input);
-    let mut parser = Parser::new(tokens, input.to_string());
-    
-    match parser.parse() {
-        Ok(ast) => match env.evaluate_ast(ast) {
-            Ok(val) => {
-                let s = val.to_string();
-                if !s.is_empty() {
-                    println!("{}", blue(&s));
-                }
+fn evaluate_input(input: &str, env: &mut Interpreter) {
+    match env.run_source(input) {
+        Ok(val) => {
+            let s = val.to_string();
+            if !s.is_empty() {
+                println!("{}", blue(&s));
             }
-            Err(e) => println!("{}{}Error: {}{}", colors::BOLD, colors::RED, colors::RESET, &e.to_string()),
-        },
-        Err(e) => println!("{}", e),
+        }
+        Err(e) => println!("{}", e.render(input)),
     }
 }
 
-pub fn repl() {
+pub fn repl(mut env: Interpreter) {
     print_welcome_message();
 
-    let mut env = Evaluator::new();
     let mut input_buffer = String::new();
     let mut brace_count = 0;
     let mut paren_count = 0;
@@ -100,8 +91,10 @@ pub fn repl() {
         match io::stdin().read_line(&mut line) {
             Ok(_) => {
                 let line_trimmed = line.trim_end();
-                
-                if line_trimmed.is_empty() && !input_buffer.is_empty() {
+                // Inside a `"""` string, lines are kept until the closing quotes
+                let in_string = input_buffer.matches("\"\"\"").count() % 2 == 1;
+
+                if line_trimmed.is_empty() && !input_buffer.is_empty() && !in_string {
                     let input = input_buffer.clone();
                     input_buffer.clear();
                     brace_count = 0;
@@ -110,7 +103,7 @@ pub fn repl() {
                     if !handle_special_command(&input, &mut env) {
                         evaluate_input(&input, &mut env);
                     }
-                } else if line_trimmed.starts_with(':') {
+                } else if line_trimmed.starts_with(':') && !in_string {
                     if !handle_special_command(line_trimmed, &mut env) {
                         println!("{}", red("Unknown command. Type :help for available commands."));
                     }
@@ -130,7 +123,8 @@ pub fn repl() {
                     }
                     input_buffer.push_str(line_trimmed);
 
-                    if brace_count == 0 && paren_count == 0 && !line_trimmed.ends_with('\\') {
+                    let in_string = input_buffer.matches("\"\"\"").count() % 2 == 1;
+                    if brace_count == 0 && paren_count == 0 && !in_string && !line_trimmed.ends_with('\\') {
                         let input = input_buffer.clone();
                         input_buffer.clear();
                         
@@ -0,0 +1,415 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::error::error::PlutoError;
+use crate::evaluator::evaluator::Scope as EnvScope;
+use crate::evaluator::module_loader::import_binding;
+use crate::parser::ast::{ASTNode, ASTNodeKind, Params, Pattern, Slot, Span};
+
+/// What the resolver knows about one runtime scope.
+#[derive(Default)]
+struct Scope {
+    /// Names declared so far, with their slot and whether they are `const`.
+    declared: HashMap<String, (usize, bool)>,
+    /// Names this scope's own statements declare further down.
+    pending: HashSet<String>,
+    /// The parameter scope of a function. Code inside a function may run
+    /// after the enclosing scopes are complete, so `pending` stops applying.
+    function: bool,
+    /// The bindings of an or-pattern, whose layout depends on the alternative
+    /// that matched. Its names get no slot and are looked up by name.
+    by_name: bool,
+}
+
+/// Annotates variable references and declarations with the `Slot` they
+/// will occupy at runtime, mirroring every scope the evaluator pushes.
+///
+/// The outermost scopes are the frames the program will run in, builtins
+/// and earlier scripts' globals included, and a program's own top-level names
+/// take the next slots in declaration order. Names the resolver cannot see
+/// (e.g. a local declared after a closure that uses it) keep `slot: None`
+/// and are looked up by name.
+struct Resolver {
+    scopes: Vec<Scope>,
+}
+
+/// Resolves a parsed program or expression in place, for running in `env`.
+/// Fails on reading or assigning a local before its declaration and on
+/// assigning to a `const`.
+pub fn resolve(ast: &mut ASTNode, env: &[EnvScope]) -> Result<(), PlutoError> {
+    let mut scopes: Vec<Scope> = env
+        .iter()
+        .map(|frame| Scope {
+            declared: frame
+                .borrow()
+                .iter()
+                .enumerate()
+                .map(|(index, (name, (_, is_const)))| (name.clone(), (index, *is_const)))
+                .collect(),
+            ..Scope::default()
+        })
+        .collect();
+    if scopes.is_empty() {
+        scopes.push(Scope::default());
+    }
+    let mut resolver = Resolver { scopes };
+    resolver.node(ast)
+}
+
+impl Resolver {
+    fn push_scope(&mut self, statements: &[ASTNode]) {
+        self.scopes.push(Scope {
+            pending: declared_names(statements).into_iter().collect(),
+            ..Scope::default()
+        });
+    }
+
+    fn declare(&mut self, name: &str, is_const: bool) -> Option<Slot> {
+        let scope = self.scopes.last_mut().unwrap();
+        scope.pending.remove(name);
+        let next = scope.declared.len();
+        let entry = scope.declared.entry(name.to_string()).or_insert((next, is_const));
+        entry.1 = is_const;
+        (!scope.by_name).then_some(Slot { depth: 0, index: entry.0 })
+    }
+
+    /// The slot of a visible `name`, if it has one, and whether it is `const`.
+    fn lookup(&self, name: &str, span: Span) -> Result<Option<(Option<Slot>, bool)>, PlutoError> {
+        let mut crossed_function = false;
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if let Some(&(index, is_const)) = scope.declared.get(name) {
+                return Ok(Some(((!scope.by_name).then_some(Slot { depth, index }), is_const)));
+            }
+            if !crossed_function && scope.pending.contains(name) {
+                return Err(PlutoError::name(format!("Variable '{}' used before its declaration", name)).with_span(span));
+            }
+            crossed_function |= scope.function;
+        }
+        Ok(None)
+    }
+
+    fn read(&self, name: &str, span: Span) -> Result<Option<Slot>, PlutoError> {
+        Ok(self.lookup(name, span)?.and_then(|(slot, _)| slot))
+    }
+
+    fn write(&self, name: &str, span: Span) -> Result<Option<Slot>, PlutoError> {
+        match self.lookup(name, span)? {
+            Some((_, true)) => Err(PlutoError::syntax(format!("Cannot assign to constant '{}'", name)).with_span(span)),
+            found => Ok(found.and_then(|(slot, _)| slot)),
+        }
+    }
+
+    /// The initializer runs before the name exists, so it cannot refer to it.
+    fn declaration(&mut self, name: &str, init: &mut Option<Box<ASTNode>>, is_const: bool) -> Result<Option<Slot>, PlutoError> {
+        if let Some(init) = init {
+            self.node(init)?;
+        }
+        Ok(self.declare(name, is_const))
+    }
+
+    /// Parameters go in a scope of their own. Default values are evaluated
+    /// where the function is called, so they are left to runtime lookup.
+    /// A freshly parsed body is not shared yet, so `make_mut` does not copy it.
+    fn function(&mut self, params: &Params, body: &mut Rc<ASTNode>) -> Result<(), PlutoError> {
+        self.scopes.push(Scope {
+            function: true,
+            ..Scope::default()
+        });
+        for (name, _) in params {
+            self.declare(name, false);
+        }
+        self.node(Rc::make_mut(body))?;
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn args(&mut self, args: &mut [(Option<String>, Box<ASTNode>)]) -> Result<(), PlutoError> {
+        for (_, arg) in args {
+            self.node(arg)?;
+        }
+        Ok(())
+    }
+
+    /// Value and range patterns are evaluated before the arm's scope exists.
+    fn pattern(&mut self, pattern: &mut Pattern, names: &mut Vec<String>) -> Result<(), PlutoError> {
+        match pattern {
+            Pattern::Wildcard => {}
+            Pattern::Binding(name) => names.push(name.clone()),
+            Pattern::Value(expr) => self.node(expr)?,
+            Pattern::Range(start, end, _) => {
+                self.node(start)?;
+                self.node(end)?;
+            }
+            Pattern::Array(elements, rest) => {
+                for element in elements {
+                    self.pattern(element, names)?;
+                }
+                if let Some(Some(name)) = rest {
+                    names.push(name.clone());
+                }
+            }
+            Pattern::Map(entries) => {
+                for (_, entry) in entries {
+                    self.pattern(entry, names)?;
+                }
+            }
+            Pattern::Or(alternatives) => {
+                for alternative in alternatives {
+                    self.pattern(alternative, names)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn node(&mut self, node: &mut ASTNode) -> Result<(), PlutoError> {
+        let ASTNode { kind, span, slot, .. } = node;
+        let span = *span;
+        match kind {
+            // top-level names get their slots up front, so functions above a
+            // declaration reach the global by slot; reading one early is left
+            // to runtime, since an earlier script may have defined it
+            ASTNodeKind::Program(statements) => {
+                let scope = self.scopes.last_mut().unwrap();
+                for name in declared_names(statements) {
+                    let next = scope.declared.len();
+                    scope.declared.entry(name).or_insert((next, false));
+                }
+                for stmt in statements {
+                    self.node(stmt)?;
+                }
+            }
+
+            // like the evaluator, only a block that declares something gets a scope
+            ASTNodeKind::Block(statements) if statements.iter().any(ASTNode::is_declaration) => {
+                self.push_scope(statements);
+                for stmt in statements.iter_mut() {
+                    self.node(stmt)?;
+                }
+                self.scopes.pop();
+            }
+
+            ASTNodeKind::Block(statements) => {
+                for stmt in statements.iter_mut() {
+                    self.node(stmt)?;
+                }
+            }
+
+            ASTNodeKind::VariableDeclaration(name, init) => *slot = self.declaration(name, init, false)?,
+
+            ASTNodeKind::ConstDeclaration(name, init) => *slot = self.declaration(name, init, true)?,
+
+            ASTNodeKind::Assignment(name, value) => {
+                self.node(value)?;
+                *slot = self.write(name, span)?;
+            }
+
+            ASTNodeKind::Identifier(name) => *slot = self.read(name, span)?,
+
+            ASTNodeKind::PostfixUnaryExpression(_, target) if let ASTNodeKind::Identifier(name) = &target.kind => {
+                target.slot = self.write(name, target.span)?;
+            }
+
+            ASTNodeKind::AssignmentOp(_, target, value) if let ASTNodeKind::Identifier(name) = &target.kind => {
+                self.node(value)?;
+                target.slot = self.write(name, target.span)?;
+            }
+
+            ASTNodeKind::FunctionDeclaration(name, params, body) => {
+                *slot = self.declare(name, false);
+                self.function(params, body)?;
+            }
+
+            ASTNodeKind::AnonymousFunction(params, body) => self.function(params, body)?,
+
+            ASTNodeKind::FunctionCall(name, args) => {
+                *slot = self.read(name, span)?;
+                self.args(args)?;
+            }
+
+            ASTNodeKind::ImmediateInvocation(func, args) => {
+                self.node(func)?;
+                self.args(args)?;
+            }
+
+            ASTNodeKind::MethodCall(object, _, args) => {
+                self.node(object)?;
+                self.args(args)?;
+            }
+
+            ASTNodeKind::ForStatement(init, condition, increment, body) => {
+                self.push_scope(init.as_deref().map(std::slice::from_ref).unwrap_or_default());
+                for part in [init, condition, increment].into_iter().flatten() {
+                    self.node(part)?;
+                }
+                self.node(body)?;
+                self.scopes.pop();
+            }
+
+            ASTNodeKind::ForInStatement(bindings, iterable, body) => {
+                self.node(iterable)?;
+                self.scopes.push(Scope::default());
+                for name in bindings.iter() {
+                    self.declare(name, false);
+                }
+                self.node(body)?;
+                self.scopes.pop();
+            }
+
+            ASTNodeKind::MatchExpression(subject, arms) => {
+                self.node(subject)?;
+                for (pattern, guard, result) in arms {
+                    let mut names = Vec::new();
+                    self.pattern(pattern, &mut names)?;
+                    self.scopes.push(Scope {
+                        by_name: has_alternatives(pattern),
+                        ..Scope::default()
+                    });
+                    for name in &names {
+                        self.declare(name, false);
+                    }
+                    if let Some(guard) = guard {
+                        self.node(guard)?;
+                    }
+                    self.node(result)?;
+                    self.scopes.pop();
+                }
+            }
+
+            ASTNodeKind::TryStatement(body, catch, finally) => {
+                self.node(body)?;
+                if let Some((name, handler)) = catch {
+                    self.scopes.push(Scope::default());
+                    self.declare(name, false);
+                    self.node(handler)?;
+                    self.scopes.pop();
+                }
+                if let Some(finally) = finally {
+                    self.node(finally)?;
+                }
+            }
+
+            ASTNodeKind::ImportStatement(path, alias) => {
+                *slot = self.declare(&import_binding(path, alias.as_ref()), false);
+            }
+
+            ASTNodeKind::FromImport(_, names) => {
+                for name in names.iter() {
+                    self.declare(name, false);
+                }
+            }
+
+            // field defaults, like parameter defaults, are evaluated by the caller
+            ASTNodeKind::StructDeclaration(name, _) => *slot = self.declare(name, false),
+
+            // methods capture the current scopes but are not declared in them
+            ASTNodeKind::ImplBlock(_, methods) => {
+                for method in methods {
+                    if let ASTNodeKind::FunctionDeclaration(_, params, body) = &mut method.kind {
+                        self.function(params, body)?;
+                    }
+                }
+            }
+
+            ASTNodeKind::BinaryExpression(left, _, right)
+            | ASTNodeKind::IndexAccess(left, right)
+            | ASTNodeKind::Range(left, right, _)
+            | ASTNodeKind::AssignmentOp(_, left, right) => {
+                self.node(left)?;
+                self.node(right)?;
+            }
+
+            ASTNodeKind::UnaryExpression(_, operand)
+            | ASTNodeKind::PostfixUnaryExpression(_, operand)
+            | ASTNodeKind::MemberAccess(operand, _)
+            | ASTNodeKind::ThrowStatement(operand) => self.node(operand)?,
+
+            ASTNodeKind::ReturnStatement(value) => {
+                if let Some(value) = value {
+                    self.node(value)?;
+                }
+            }
+
+            ASTNodeKind::AssignmentMember(object, _, value) => {
+                self.node(object)?;
+                self.node(value)?;
+            }
+
+            ASTNodeKind::AssignmentIndex(target, index, value) => {
+                self.node(index)?;
+                self.node(value)?;
+                match &target.kind {
+                    ASTNodeKind::Identifier(name) => target.slot = self.write(name, target.span)?,
+                    _ => self.node(target)?,
+                }
+            }
+
+            ASTNodeKind::IfStatement(condition, then_branch, else_branch) => {
+                self.node(condition)?;
+                self.node(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.node(else_branch)?;
+                }
+            }
+
+            ASTNodeKind::TernaryExpression(condition, then_branch, else_branch) => {
+                self.node(condition)?;
+                self.node(then_branch)?;
+                self.node(else_branch)?;
+            }
+
+            ASTNodeKind::WhileStatement(condition, body) => {
+                self.node(condition)?;
+                self.node(body)?;
+            }
+
+            ASTNodeKind::ArrayLiteral(elements) | ASTNodeKind::InterpolatedString(elements) => {
+                for element in elements {
+                    self.node(element)?;
+                }
+            }
+
+            ASTNodeKind::HashMapLiteral(pairs) => {
+                for (_, value) in pairs {
+                    self.node(value)?;
+                }
+            }
+
+            ASTNodeKind::NumberLiteral(_)
+            | ASTNodeKind::FloatLiteral(_)
+            | ASTNodeKind::NullLiteral
+            | ASTNodeKind::StringLiteral(_)
+            | ASTNodeKind::BooleanLiteral(_)
+            | ASTNodeKind::Break
+            | ASTNodeKind::Continue => {}
+        }
+        Ok(())
+    }
+}
+
+/// Names that `statements` declare directly in their own scope, in order.
+fn declared_names(statements: &[ASTNode]) -> Vec<String> {
+    let mut names = Vec::new();
+    for stmt in statements {
+        match &stmt.kind {
+            ASTNodeKind::VariableDeclaration(name, _)
+            | ASTNodeKind::ConstDeclaration(name, _)
+            | ASTNodeKind::FunctionDeclaration(name, _, _)
+            | ASTNodeKind::StructDeclaration(name, _) => names.push(name.clone()),
+            ASTNodeKind::ImportStatement(path, alias) => names.push(import_binding(path, alias.as_ref())),
+            ASTNodeKind::FromImport(_, imported) => names.extend(imported.iter().cloned()),
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Whether `pattern` contains an or-pattern, whose bindings may come in any order.
+fn has_alternatives(pattern: &Pattern) -> bool {
+    match pattern {
+        Pattern::Or(_) => true,
+        Pattern::Array(elements, _) => elements.iter().any(has_alternatives),
+        Pattern::Map(entries) => entries.iter().any(|(_, entry)| has_alternatives(entry)),
+        _ => false,
+    }
+}
@@ -0,0 +1,35 @@
+use crate::parser::ast::Span;
+use crate::utils::colors::{YELLOW, RED, RESET, BOLD, GREY};
+use crate::utils::unicode::char_width;
+
+pub fn format_message(label: &str, msg: &str) -> String {
+    format!("{}{}{}:{} {}", BOLD, RED, label, RESET, msg)
+}
+
+/// 1-based line and column of the char offset `position`.
+pub fn line_col(source: &str, position: usize) -> (usize, usize) {
+    source.chars().take(position).fold((1, 1), |(line, column), c| {
+        if c == '\n' { (line + 1, 1) } else { (line, column + 1) }
+    })
+}
+
+pub fn format_diagnostic(source: &str, file: Option<&str>, label: &str, msg: &str, span: Span) -> String {
+    let mut error = format_message(label, msg);
+    let (line_number, column) = line_col(source, span.start);
+    let file = file.map_or(String::new(), |f| format!("{}:", f));
+
+    error.push_str(&format!("\n{}At line -> {}{}{}:{}{}{}\n\n", BOLD, GREY, file, line_number, GREY, column, RESET));
+    if let Some(line) = source.lines().nth(line_number - 1) {
+        let line_len = line.chars().count();
+        let count = span.end.saturating_sub(span.start).clamp(1, line_len.saturating_sub(column - 1).max(1));
+        // Pad and underline by display width so the carets sit under wide
+        // characters and after tabs.
+        let indent: String = line.chars().take(column - 1)
+            .map(|c| if c == '\t' { "\t".to_string() } else { " ".repeat(char_width(c)) })
+            .collect();
+        let width = line.chars().skip(column - 1).take(count).map(char_width).sum::<usize>().max(1);
+        error.push_str(&format!("{}{}{:>4}{} {}{}{} {}{}\n", BOLD, GREY, line_number, RESET, YELLOW, "|", RESET, line, RESET));
+        error.push_str(&format!("     {}{}{} {}{}{}\n{}", YELLOW, "|", RESET, indent, RED, "^".repeat(width), RESET));
+    }
+    error
+}
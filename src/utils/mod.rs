@@ -1,2 +1,4 @@
 pub mod args;
-pub mod colors;
\ No newline at end of file
+pub mod colors;
+pub mod diagnostics;
+pub mod unicode;
\ No newline at end of file
@@ -0,0 +1,67 @@
+/// Terminal columns `c` takes up: two for East Asian wide characters and
+/// emoji, none for combining marks and joiners, one otherwise.
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036F | 0x200B..=0x200F | 0x20D0..=0x20FF | 0xFE00..=0xFE0F | 0xFE20..=0xFE2F => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// Whether `c` attaches to the character before it instead of starting a
+/// new grapheme: combining marks, variation selectors and skin tones.
+fn extends(c: char) -> bool {
+    matches!(c as u32,
+        0x0300..=0x036F
+        | 0x1AB0..=0x1AFF
+        | 0x1DC0..=0x1DFF
+        | 0x200C
+        | 0x20D0..=0x20FF
+        | 0xFE00..=0xFE0F
+        | 0xFE20..=0xFE2F
+        | 0x1F3FB..=0x1F3FF
+        | 0xE0020..=0xE007F
+        | 0xE0100..=0xE01EF)
+}
+
+fn regional_indicator(c: char) -> bool {
+    matches!(c as u32, 0x1F1E6..=0x1F1FF)
+}
+
+/// Splits `s` into user-perceived characters. Combining marks, variation
+/// selectors and skin tones stay with their base, zero-width joiners glue
+/// emoji sequences together, flags pair up and `\r\n` counts as one.
+pub fn graphemes(s: &str) -> Vec<String> {
+    let mut clusters: Vec<String> = Vec::new();
+    let mut joined = false;
+    let mut flag_open = false;
+    for c in s.chars() {
+        let attach = match clusters.last() {
+            None => false,
+            Some(last) => {
+                joined
+                    || extends(c)
+                    || c == '\u{200D}'
+                    || (c == '\n' && last.ends_with('\r') && last.len() == 1)
+                    || (regional_indicator(c) && flag_open)
+            }
+        };
+        flag_open = regional_indicator(c) && !(attach && flag_open);
+        joined = c == '\u{200D}';
+        match clusters.last_mut() {
+            Some(last) if attach => last.push(c),
+            _ => clusters.push(c.to_string()),
+        }
+    }
+    clusters
+}
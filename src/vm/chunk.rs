@@ -0,0 +1,228 @@
+use std::rc::Rc;
+
+use crate::error::error::PlutoError;
+use crate::evaluator::evaluator::Value;
+use crate::parser::ast::{ASTNode, BinaryOp, Params, PostfixOp, Slot, Span, UnaryOp};
+
+/// One VM instruction. Operands are indices into the owning `Chunk`'s tables
+/// or absolute jump targets.
+#[derive(Debug, Clone, Copy)]
+pub enum Op {
+    Constant(usize),
+    Pop,
+    /// Drops the value under the top of the stack.
+    Nip,
+    /// Pushes a copy of the top value.
+    Dup,
+    GetVar(usize),
+    /// Pushes a copy of a register.
+    GetLocal(usize),
+    DefineVar(usize),
+    /// Fails if the current scope already has the variable `const` is about to declare.
+    CheckConst(usize),
+    DefineConst(usize),
+    SetVar(usize),
+    /// Stores a copy of the top value in a register, declaring or assigning it.
+    SetLocal(usize),
+    /// `SetLocal` followed by `Pop`.
+    StoreLocal(usize),
+    Binary(BinaryOp),
+    /// `name++` / `name--`.
+    Postfix { op: PostfixOp, name: usize },
+    PostfixLocal { op: PostfixOp, register: usize },
+    /// `name op= value`, with the value on the stack.
+    AssignOp { op: BinaryOp, name: usize },
+    AssignOpLocal { op: BinaryOp, register: usize },
+    Unary(UnaryOp),
+    PushScope,
+    PopScope,
+    Jump(usize),
+    /// Pops a condition; jumps when it is not truthy.
+    JumpIfFalsy(usize),
+    /// Looks at the left operand of `&&`, `||` or `?:`; when it decides the
+    /// result, replaces it with the result and jumps past the right operand.
+    ShortCircuit(BinaryOp, usize),
+    LoopStart { break_to: usize, continue_to: usize },
+    LoopEnd,
+    Break,
+    Continue,
+    Return,
+    /// Looks up a function variable and pushes it, failing if it is not callable.
+    Callee(usize),
+    /// Like `Callee`, for a function in a register named `names[name]`.
+    CalleeLocal { register: usize, name: usize },
+    /// Calls the callee below `args` arguments, named `names[name]`; argument
+    /// names come from `arg_names[names]`.
+    Call { name: usize, names: usize, args: usize },
+    /// Like `Call` followed by `Return`, but a user function is left to the
+    /// caller to run in place of the current one.
+    TailCall { name: usize, names: usize, args: usize },
+    /// Fails unless the top value is a user function, which `Invoke` then calls.
+    Invocable,
+    /// Like `Call`, for a function that has no name to show in the call stack.
+    Invoke { names: usize, args: usize },
+    /// Pushes a closure over the current scopes for `functions[n]`.
+    Closure(usize),
+    /// Pushes a new struct type, `structs[n]`.
+    Struct(usize),
+    /// Adds the methods of `impls[n]` to their struct.
+    Impl(usize),
+    /// Binds a module, or names from it, as `imports[n]` says, in the current scope.
+    Import(usize),
+    FromImport(usize),
+    /// Pops this many values into an array.
+    Array(usize),
+    /// Pops a value for each of `keys[n]` into a map.
+    Map(usize),
+    /// Pops the bounds of a range; `true` includes the end.
+    Range(bool),
+    /// Pops an index and the value it indexes.
+    Index,
+    /// Pops an index into a variable's value, without copying the value.
+    IndexVar(usize),
+    IndexLocal(usize),
+    /// Replaces the top value with its member `names[name]`; `names[object]`
+    /// is the receiver's source, for the error message.
+    Member { name: usize, object: usize },
+    /// Calls method `names[method]` on the receiver below `args` arguments.
+    MethodCall { method: usize, names: usize, args: usize },
+    /// Pops a value and an index, stores the value at that index of a
+    /// variable's array or map, and pushes the value back.
+    SetIndexVar(usize),
+    SetIndexLocal(usize),
+    /// Pops a container and stores the value below it at the index below
+    /// that, leaving the value and then the updated container.
+    SetIndex,
+    /// Pops an index, a container and a value; pushes the container with
+    /// the value stored at the index.
+    WriteIndex,
+    /// Pops a struct and a value; pushes the struct with its field
+    /// `names[name]` set to the value.
+    SetField(usize),
+    /// Pops a value and fails with it as the error.
+    Throw,
+    /// Fails with `errors[n]`.
+    Fail(usize),
+    /// Pops an iterable and starts a loop over its items, like `LoopStart`.
+    Iterate { break_to: usize, continue_to: usize },
+    /// Pushes a scope binding `bindings[n]` to the loop's next item, or
+    /// leaves the loop when there is none.
+    IterNext(usize),
+    /// Like `IterNext`, binding the loop variables to the registers from `register` on.
+    IterNextLocal { bindings: usize, register: usize },
+    /// Pops this many values and pushes their `Display` forms joined into a string.
+    Concat(usize),
+    /// Pops a value and pushes whether it equals the value under it, which
+    /// a match arm is testing.
+    MatchValue,
+    /// Pops the bounds of a range pattern and pushes whether the value under them falls in it.
+    MatchRange(bool),
+    /// Pushes whether the top value is an array of `len` items, or at least
+    /// that many with `rest`.
+    MatchArray { len: usize, rest: bool },
+    /// Pushes whether the top value is a map or a struct.
+    MatchMap,
+    /// Pushes item `n` of the array on top.
+    Element(usize),
+    /// Pushes the items of the array on top from `n` on, as an array.
+    Rest(usize),
+    /// Pushes the entry or field `names[n]` of the map or struct on top and
+    /// `true`, or only `false` when there is none.
+    Field(usize),
+    /// Starts collecting the bindings of a pattern that are kept in a frame.
+    BeginBindings,
+    /// Pops a value into the bindings being collected as `names[n]`.
+    Bind(usize),
+    /// Adds the bindings of a matching alternative to the enclosing pattern's.
+    MergeBindings,
+    /// Forgets the bindings of a pattern that did not match.
+    DropBindings,
+    /// Pushes the bindings of a matching pattern as a scope.
+    PushBindings,
+    /// Enters a `try`: a failure goes to `catch_to`, or to `finally_to`
+    /// without a `catch`, as does anything else that leaves it early.
+    Try { catch_to: Option<usize>, finally_to: usize },
+    /// Leaves a `try` body or `catch` block normally, pushing the completion
+    /// code that lets `EndFinally` carry on after it.
+    EndTry,
+    /// Pops the completion left before the `finally` block ran, and carries
+    /// on with it: continues, returns, breaks out of a loop or fails.
+    EndFinally,
+}
+
+/// A function literal or declaration, compiled into the closures it makes.
+#[derive(Debug)]
+pub struct Function {
+    pub params: Params,
+    pub body: Rc<ASTNode>,
+    pub doc: Option<Rc<str>>,
+    /// For each scope the chunk has entered around it, outermost first,
+    /// whether it is a frame. The others get an empty frame in the closure,
+    /// so its slots count every scope.
+    pub frames: Vec<bool>,
+}
+
+/// Compiled code for a program or a function body.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    /// Source span of each instruction, for error locations.
+    pub spans: Vec<Span>,
+    pub constants: Vec<Value>,
+    /// Variables kept in frames, with slots counting only the frames that
+    /// exist at runtime, not the scopes whose variables live in registers.
+    pub variables: Vec<(String, Option<Slot>)>,
+    /// Registers a run of the chunk needs for the variables of its other scopes.
+    pub registers: usize,
+    /// Whether a function's parameters are kept in a frame rather than in
+    /// the first registers.
+    pub frame_params: bool,
+    pub arg_names: Vec<Vec<Option<String>>>,
+    /// Member and method names, and receiver sources.
+    pub names: Vec<String>,
+    /// Loop variables of `for ... in` loops.
+    pub bindings: Vec<Vec<String>>,
+    /// Keys of map literals.
+    pub keys: Vec<Vec<String>>,
+    pub functions: Vec<Function>,
+    /// Struct names and fields.
+    pub structs: Vec<(String, Params)>,
+    /// Struct names and their methods, as indices into `functions`.
+    pub impls: Vec<(String, Vec<(String, usize)>)>,
+    /// Module paths and the names bound from them.
+    pub imports: Vec<(String, Vec<String>)>,
+    pub errors: Vec<PlutoError>,
+}
+
+impl Chunk {
+    pub fn emit(&mut self, op: Op, span: Span) -> usize {
+        self.code.push(op);
+        self.spans.push(span);
+        self.code.len() - 1
+    }
+
+    pub fn constant(&mut self, value: Value) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    pub fn variable(&mut self, name: &str, slot: Option<Slot>) -> usize {
+        match self.variables.iter().position(|(n, s)| n == name && *s == slot) {
+            Some(i) => i,
+            None => {
+                self.variables.push((name.to_string(), slot));
+                self.variables.len() - 1
+            }
+        }
+    }
+
+    pub fn name(&mut self, name: &str) -> usize {
+        match self.names.iter().position(|n| n == name) {
+            Some(i) => i,
+            None => {
+                self.names.push(name.to_string());
+                self.names.len() - 1
+            }
+        }
+    }
+}
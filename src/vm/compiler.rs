@@ -0,0 +1,1119 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::error::error::PlutoError;
+use crate::evaluator::evaluator::Value;
+use crate::evaluator::module_loader::import_binding;
+use crate::parser::ast::{ASTNode, ASTNodeKind, ASTNodeTrait, BinaryOp, MatchArm, Params, Pattern, Slot};
+use crate::vm::chunk::{Chunk, Function, Op};
+
+/// A scope of the code being compiled, one for each the resolver saw.
+struct Level {
+    /// Kept as a runtime `Frame`; otherwise its variables live in registers.
+    frame: bool,
+    /// The registers of its variables, by resolver index.
+    registers: HashMap<usize, usize>,
+    /// Registers holding a `const`, which only a write-back can reach.
+    consts: HashSet<usize>,
+}
+
+/// Where a variable is stored while the chunk runs.
+#[derive(Clone, Copy)]
+enum Place {
+    Register(usize),
+    /// In a frame, through `Chunk::variables[n]`.
+    Frame(usize),
+}
+
+/// Where the bindings of a match arm go.
+enum Bindings {
+    /// Registers, by name.
+    Registers(HashMap<String, usize>),
+    /// A frame pushed once the pattern matches.
+    Frame,
+}
+
+/// Translates an AST into a `Chunk`. Every node leaves exactly one value on
+/// the stack, like `Evaluator::eval` returns one.
+///
+/// The variables of the scopes a chunk enters live in its registers. Only
+/// the scopes it runs in (the globals, or a function's closure) are frames,
+/// plus any scope that code outside the chunk finds variables in by name or
+/// by slot: a scope declaring a name that a function created in the chunk,
+/// or a default value anywhere, refers to.
+pub struct Compiler {
+    chunk: Chunk,
+    /// Compiling a function body, where `return f(x)` is a tail call.
+    function: bool,
+    /// The scopes entered so far, innermost last.
+    levels: Vec<Level>,
+    /// Names whose scopes must be frames.
+    shared: HashSet<String>,
+    /// The furthest forward jump target so far.
+    target: usize,
+    /// The `try` bodies and `catch` blocks being compiled, which a tail call
+    /// would leave before the `finally` block runs.
+    tries: usize,
+}
+
+impl Compiler {
+    fn new(function: bool, node: &ASTNode, defaults: &HashSet<String>) -> Self {
+        let mut shared = defaults.clone();
+        captured(node, &mut shared);
+        Compiler { chunk: Chunk::default(), function, levels: Vec::new(), shared, target: 0, tries: 0 }
+    }
+
+    /// Compiles a program. `defaults` are the names that default values
+    /// refer to; see `default_names`.
+    pub fn compile(node: &ASTNode, defaults: &HashSet<String>) -> Chunk {
+        let mut compiler = Compiler::new(false, node, defaults);
+        compiler.node(node);
+        compiler.chunk
+    }
+
+    /// Compiles a function body. Its parameters come first, in registers
+    /// `0..params.len()` unless `frame_params` is set.
+    pub fn compile_function(params: &Params, body: &ASTNode, defaults: &HashSet<String>) -> Chunk {
+        let mut compiler = Compiler::new(true, body, defaults);
+        let mut names = HashSet::new();
+        let repeated = !params.iter().all(|(name, _)| names.insert(name));
+        let frame = repeated || compiler.shares(params.iter().map(|(name, _)| name));
+        compiler.chunk.frame_params = compiler.enter(frame);
+        if !compiler.chunk.frame_params {
+            for index in 0..params.len() {
+                compiler.local(0, index);
+            }
+        }
+        compiler.node(body);
+        compiler.chunk
+    }
+
+    fn emit(&mut self, op: Op, node: &ASTNode) -> usize {
+        self.chunk.emit(op, node.span)
+    }
+
+    fn here(&self) -> usize {
+        self.chunk.code.len()
+    }
+
+    /// Discards the value of a statement. An assignment to a register then
+    /// stores its value instead of copying it, unless a jump lands on the `Pop`.
+    fn pop(&mut self, stmt: &ASTNode) {
+        match self.chunk.code.last() {
+            Some(&Op::SetLocal(register)) if self.target < self.here() => {
+                *self.chunk.code.last_mut().unwrap() = Op::StoreLocal(register);
+            }
+            _ => {
+                self.emit(Op::Pop, stmt);
+            }
+        }
+    }
+
+    /// Notes that a jump lands on `target`.
+    fn mark(&mut self, target: usize) {
+        self.target = self.target.max(target);
+    }
+
+    fn patch(&mut self, at: usize, target: usize) {
+        self.mark(target);
+        self.chunk.code[at] = match self.chunk.code[at] {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpIfFalsy(_) => Op::JumpIfFalsy(target),
+            Op::ShortCircuit(op, _) => Op::ShortCircuit(op, target),
+            Op::LoopStart { continue_to, .. } => Op::LoopStart { break_to: target, continue_to },
+            op => op,
+        };
+    }
+
+    fn constant(&mut self, value: Value, node: &ASTNode) {
+        let index = self.chunk.constant(value);
+        self.emit(Op::Constant(index), node);
+    }
+
+    fn fail(&mut self, error: PlutoError, node: &ASTNode) {
+        self.chunk.errors.push(error);
+        let index = self.chunk.errors.len() - 1;
+        self.emit(Op::Fail(index), node);
+    }
+
+    /// Whether a scope declaring `names` must be a frame.
+    fn shares<'a>(&self, mut names: impl Iterator<Item = &'a String>) -> bool {
+        names.any(|name| self.shared.contains(name))
+    }
+
+    /// Enters a scope, which is a frame if `frame` is set. Returns whether it is.
+    fn enter(&mut self, frame: bool) -> bool {
+        self.levels.push(Level { frame, registers: HashMap::new(), consts: HashSet::new() });
+        frame
+    }
+
+    /// Leaves the innermost scope, returning whether it was a frame.
+    fn leave(&mut self) -> bool {
+        self.levels.pop().unwrap().frame
+    }
+
+    /// The register of the variable `index` of the scope `depth` levels out.
+    fn local(&mut self, depth: usize, index: usize) -> usize {
+        let level = self.levels.len() - 1 - depth;
+        let next = self.chunk.registers;
+        let register = *self.levels[level].registers.entry(index).or_insert(next);
+        if register == next {
+            self.chunk.registers += 1;
+        }
+        register
+    }
+
+    /// Where the variable `name`, resolved to `slot`, is stored.
+    fn place(&mut self, name: &str, slot: Option<Slot>) -> Place {
+        let Some(slot) = slot else {
+            return Place::Frame(self.chunk.variable(name, None));
+        };
+        let levels = self.levels.len();
+        if slot.depth < levels && !self.levels[levels - 1 - slot.depth].frame {
+            return Place::Register(self.local(slot.depth, slot.index));
+        }
+        // scopes in registers are not on the evaluator's stack
+        let skipped = self.levels[levels.saturating_sub(slot.depth)..].iter().filter(|level| !level.frame).count();
+        let slot = Slot { depth: slot.depth - skipped, index: slot.index };
+        Place::Frame(self.chunk.variable(name, Some(slot)))
+    }
+
+    /// Declares `name` with the value on top of the stack, leaving it there.
+    fn define(&mut self, name: &str, slot: Option<Slot>, constant: bool, node: &ASTNode) {
+        let op = match self.place(name, slot) {
+            Place::Register(register) => {
+                let consts = &mut self.levels.last_mut().unwrap().consts;
+                match constant {
+                    true => consts.insert(register),
+                    false => consts.remove(&register),
+                };
+                Op::SetLocal(register)
+            }
+            Place::Frame(variable) if constant => Op::DefineConst(variable),
+            Place::Frame(variable) => Op::DefineVar(variable),
+        };
+        self.emit(op, node);
+    }
+
+    /// Pushes a closure for a function literal or declaration.
+    fn closure(&mut self, params: &Params, body: &Rc<ASTNode>, node: &ASTNode) {
+        let index = self.function(params, body, node);
+        self.emit(Op::Closure(index), node);
+    }
+
+    fn function(&mut self, params: &Params, body: &Rc<ASTNode>, node: &ASTNode) -> usize {
+        self.chunk.functions.push(Function {
+            params: params.clone(),
+            body: Rc::clone(body),
+            doc: node.doc.clone(),
+            frames: self.levels.iter().map(|level| level.frame).collect(),
+        });
+        self.chunk.functions.len() - 1
+    }
+
+    fn node(&mut self, node: &ASTNode) {
+        match &node.kind {
+            ASTNodeKind::NumberLiteral(n) => self.constant(Value::Number(*n), node),
+            ASTNodeKind::FloatLiteral(f) => self.constant(Value::Float(*f), node),
+            ASTNodeKind::StringLiteral(s) => self.constant(Value::String(s.clone()), node),
+            ASTNodeKind::InterpolatedString(parts) => {
+                for part in parts {
+                    self.node(part);
+                }
+                self.emit(Op::Concat(parts.len()), node);
+            }
+            ASTNodeKind::BooleanLiteral(b) => self.constant(Value::Bool(*b), node),
+            ASTNodeKind::NullLiteral => self.constant(Value::Null, node),
+
+            ASTNodeKind::Identifier(name) => {
+                let op = match self.place(name, node.slot) {
+                    Place::Register(register) => Op::GetLocal(register),
+                    Place::Frame(variable) => Op::GetVar(variable),
+                };
+                self.emit(op, node);
+            }
+
+            ASTNodeKind::Program(statements) => {
+                if statements.is_empty() {
+                    self.constant(Value::Null, node);
+                }
+                for (i, stmt) in statements.iter().enumerate() {
+                    self.node(stmt);
+                    if i + 1 < statements.len() {
+                        self.pop(stmt);
+                    }
+                }
+            }
+
+            ASTNodeKind::Block(statements) => {
+                let scoped = statements.iter().any(ASTNode::is_declaration);
+                if scoped && self.enter(self.needs_frame(statements)) {
+                    self.emit(Op::PushScope, node);
+                }
+                for stmt in statements {
+                    self.node(stmt);
+                    self.pop(stmt);
+                }
+                if scoped && self.leave() {
+                    self.emit(Op::PopScope, node);
+                }
+                self.constant(Value::Null, node);
+            }
+
+            ASTNodeKind::VariableDeclaration(name, init) => {
+                match init {
+                    Some(init) => self.node(init),
+                    None => self.constant(Value::Null, node),
+                }
+                self.define(name, node.slot, false, node);
+            }
+
+            // a register is never declared twice: that makes its scope a frame
+            ASTNodeKind::ConstDeclaration(name, init) => {
+                if let Place::Frame(variable) = self.place(name, node.slot) {
+                    self.emit(Op::CheckConst(variable), node);
+                }
+                match init {
+                    Some(init) => self.node(init),
+                    None => self.constant(Value::Null, node),
+                }
+                self.define(name, node.slot, true, node);
+            }
+
+            ASTNodeKind::Assignment(name, value) => {
+                self.node(value);
+                let op = match self.place(name, node.slot) {
+                    Place::Register(register) => Op::SetLocal(register),
+                    Place::Frame(variable) => Op::SetVar(variable),
+                };
+                self.emit(op, node);
+            }
+
+            ASTNodeKind::FunctionDeclaration(name, params, body) => {
+                self.closure(params, body, node);
+                self.define(name, node.slot, false, node);
+                self.pop(node);
+                self.constant(Value::Null, node);
+            }
+
+            ASTNodeKind::AnonymousFunction(params, body) => self.closure(params, body, node),
+
+            ASTNodeKind::StructDeclaration(name, fields) => {
+                self.chunk.structs.push((name.clone(), fields.clone()));
+                self.emit(Op::Struct(self.chunk.structs.len() - 1), node);
+                self.define(name, node.slot, false, node);
+                self.pop(node);
+                self.constant(Value::Null, node);
+            }
+
+            ASTNodeKind::ImplBlock(name, methods) => {
+                let mut functions = Vec::new();
+                for method in methods {
+                    if let ASTNodeKind::FunctionDeclaration(method_name, params, body) = &method.kind {
+                        functions.push((method_name.clone(), self.function(params, body, method)));
+                    }
+                }
+                self.chunk.impls.push((name.clone(), functions));
+                self.emit(Op::Impl(self.chunk.impls.len() - 1), node);
+                self.constant(Value::Null, node);
+            }
+
+            ASTNodeKind::ImportStatement(path, alias) => {
+                self.chunk.imports.push((path.clone(), Vec::new()));
+                self.emit(Op::Import(self.chunk.imports.len() - 1), node);
+                self.define(&import_binding(path, alias.as_ref()), node.slot, false, node);
+                self.pop(node);
+                self.constant(Value::Null, node);
+            }
+
+            // its names have no slots, so its scope is a frame
+            ASTNodeKind::FromImport(path, names) => {
+                self.chunk.imports.push((path.clone(), names.clone()));
+                self.emit(Op::FromImport(self.chunk.imports.len() - 1), node);
+                self.constant(Value::Null, node);
+            }
+
+            ASTNodeKind::BinaryExpression(left, op @ (BinaryOp::And | BinaryOp::Or | BinaryOp::Elvis), right) => {
+                self.node(left);
+                let to_end = self.emit(Op::ShortCircuit(*op, 0), node);
+                self.node(right);
+                self.emit(Op::Binary(*op), node);
+                let end = self.here();
+                self.patch(to_end, end);
+            }
+
+            ASTNodeKind::BinaryExpression(left, op, right) => {
+                self.node(left);
+                self.node(right);
+                self.emit(Op::Binary(*op), node);
+            }
+
+            ASTNodeKind::UnaryExpression(op, operand) => {
+                self.node(operand);
+                self.emit(Op::Unary(*op), node);
+            }
+
+            ASTNodeKind::PostfixUnaryExpression(op, operand) => {
+                let ASTNodeKind::Identifier(name) = &operand.kind else {
+                    return self.fail(PlutoError::type_error("Postfix unary operation only supported on variables"), node);
+                };
+                let op = match self.place(name, operand.slot) {
+                    Place::Register(register) => Op::PostfixLocal { op: *op, register },
+                    Place::Frame(name) => Op::Postfix { op: *op, name },
+                };
+                self.emit(op, node);
+            }
+
+            ASTNodeKind::AssignmentOp(op, target, value) => match &target.kind {
+                ASTNodeKind::Identifier(name) => {
+                    self.node(value);
+                    let op = match self.place(name, target.slot) {
+                        Place::Register(register) => Op::AssignOpLocal { op: *op, register },
+                        Place::Frame(name) => Op::AssignOp { op: *op, name },
+                    };
+                    self.emit(op, node);
+                }
+                // the field is read, then written back through `object` evaluated again
+                ASTNodeKind::MemberAccess(object, field) => {
+                    self.node(target);
+                    self.node(value);
+                    self.emit(Op::Binary(*op), node);
+                    self.emit(Op::Dup, node);
+                    self.node(object);
+                    let field = self.chunk.name(field);
+                    self.emit(Op::SetField(field), node);
+                    self.write_back(object, node);
+                }
+                _ => self.fail(PlutoError::type_error("Assignment operator only supported on variables"), node),
+            },
+
+            ASTNodeKind::AssignmentMember(object, field, value) => {
+                self.node(value);
+                self.emit(Op::Dup, node);
+                self.node(object);
+                let field = self.chunk.name(field);
+                self.emit(Op::SetField(field), node);
+                self.write_back(object, node);
+            }
+
+            // a variable's container is updated where it is stored; any
+            // other is updated on a copy, written back if it is a field or
+            // an element itself
+            ASTNodeKind::AssignmentIndex(target, index, value) => {
+                self.node(index);
+                self.node(value);
+                if let ASTNodeKind::Identifier(name) = &target.kind {
+                    let op = match self.place(name, target.slot) {
+                        Place::Register(register) => Op::SetIndexLocal(register),
+                        Place::Frame(variable) => Op::SetIndexVar(variable),
+                    };
+                    self.emit(op, node);
+                    return;
+                }
+                self.node(target);
+                self.emit(Op::SetIndex, node);
+                match target.kind {
+                    ASTNodeKind::IndexAccess(..) | ASTNodeKind::MemberAccess(..) => self.write_back(target, node),
+                    _ => {
+                        self.emit(Op::Pop, node);
+                    }
+                }
+            }
+
+            ASTNodeKind::TernaryExpression(condition, then_branch, else_branch) => {
+                self.node(condition);
+                let to_else = self.emit(Op::JumpIfFalsy(0), node);
+                self.node(then_branch);
+                let to_end = self.emit(Op::Jump(0), node);
+                let else_start = self.here();
+                self.patch(to_else, else_start);
+                self.node(else_branch);
+                let end = self.here();
+                self.patch(to_end, end);
+            }
+
+            ASTNodeKind::IfStatement(condition, then_branch, else_branch) => {
+                self.node(condition);
+                let to_else = self.emit(Op::JumpIfFalsy(0), node);
+                self.node(then_branch);
+                let to_end = self.emit(Op::Jump(0), node);
+                let else_start = self.here();
+                self.patch(to_else, else_start);
+                match else_branch {
+                    Some(else_branch) => self.node(else_branch),
+                    None => self.constant(Value::Null, node),
+                }
+                let end = self.here();
+                self.patch(to_end, end);
+            }
+
+            ASTNodeKind::WhileStatement(condition, body) => {
+                // the loop's value is its body's last value
+                self.constant(Value::Null, node);
+                let start = self.here() + 1;
+                let loop_start = self.emit(Op::LoopStart { break_to: 0, continue_to: start }, node);
+                self.node(condition);
+                let to_exit = self.emit(Op::JumpIfFalsy(0), node);
+                self.node(body);
+                self.emit(Op::Nip, node);
+                self.emit(Op::Jump(start), node);
+                let exit = self.emit(Op::LoopEnd, node);
+                self.patch(to_exit, exit);
+                self.patch(loop_start, exit);
+            }
+
+            ASTNodeKind::ForStatement(init, condition, increment, body) => {
+                let frame = self.needs_frame(init.as_deref().map(std::slice::from_ref).unwrap_or_default());
+                if self.enter(frame) {
+                    self.emit(Op::PushScope, node);
+                }
+                if let Some(init) = init {
+                    self.node(init);
+                    self.pop(node);
+                }
+                let loop_start = self.emit(Op::LoopStart { break_to: 0, continue_to: 0 }, node);
+                let start = self.here();
+                let to_exit = condition.as_ref().map(|condition| {
+                    self.node(condition);
+                    self.emit(Op::JumpIfFalsy(0), node)
+                });
+                self.node(body);
+                self.emit(Op::Pop, node);
+                let continue_to = self.here();
+                if let Some(increment) = increment {
+                    self.node(increment);
+                    self.pop(node);
+                }
+                self.emit(Op::Jump(start), node);
+                let exit = self.emit(Op::LoopEnd, node);
+                if let Some(to_exit) = to_exit {
+                    self.patch(to_exit, exit);
+                }
+                self.chunk.code[loop_start] = Op::LoopStart { break_to: exit, continue_to };
+                if self.leave() {
+                    self.emit(Op::PopScope, node);
+                }
+                self.constant(Value::Bool(true), node);
+            }
+
+            ASTNodeKind::ForInStatement(bindings, iterable, body) => {
+                self.node(iterable);
+                self.chunk.bindings.push(bindings.clone());
+                let index = self.chunk.bindings.len() - 1;
+                let mut names = HashSet::new();
+                let repeated = !bindings.iter().all(|name| names.insert(name));
+                let next = match self.enter(repeated || self.shares(bindings.iter())) {
+                    true => Op::IterNext(index),
+                    false => {
+                        let register = self.chunk.registers;
+                        for binding in 0..bindings.len() {
+                            self.local(0, binding);
+                        }
+                        Op::IterNextLocal { bindings: index, register }
+                    }
+                };
+                let start = self.here() + 1;
+                let iterate = self.emit(Op::Iterate { break_to: 0, continue_to: start }, node);
+                self.emit(next, node);
+                self.node(body);
+                self.emit(Op::Pop, node);
+                if self.leave() {
+                    self.emit(Op::PopScope, node);
+                }
+                self.emit(Op::Jump(start), node);
+                let exit = self.emit(Op::LoopEnd, node);
+                self.chunk.code[iterate] = Op::Iterate { break_to: exit, continue_to: start };
+                self.constant(Value::Null, node);
+            }
+
+            ASTNodeKind::MatchExpression(subject, arms) => self.match_expression(subject, arms, node),
+
+            ASTNodeKind::TryStatement(body, catch, finally) => {
+                let at = self.emit(Op::Try { catch_to: None, finally_to: 0 }, node);
+                self.tries += 1;
+                self.node(body);
+                self.emit(Op::EndTry, node);
+                let catch_to = catch.as_ref().map(|(name, handler)| {
+                    let to_finally = self.emit(Op::Jump(0), node);
+                    let catch_to = self.here();
+                    // the error is on the stack, to become the only variable of the catch scope
+                    let shared = self.shares(std::iter::once(name));
+                    if self.enter(shared) {
+                        self.emit(Op::PushScope, node);
+                        let variable = self.chunk.variable(name, None);
+                        self.emit(Op::DefineVar(variable), node);
+                        self.emit(Op::Pop, node);
+                    } else {
+                        let register = self.local(0, 0);
+                        self.emit(Op::StoreLocal(register), node);
+                    }
+                    self.node(handler);
+                    if self.leave() {
+                        self.emit(Op::PopScope, node);
+                    }
+                    self.emit(Op::EndTry, node);
+                    let finally_to = self.here();
+                    self.patch(to_finally, finally_to);
+                    catch_to
+                });
+                self.tries -= 1;
+                let finally_to = self.here();
+                if let Some(finally) = finally {
+                    self.node(finally);
+                    self.emit(Op::Pop, node);
+                }
+                self.emit(Op::EndFinally, node);
+                self.mark(finally_to);
+                self.chunk.code[at] = Op::Try { catch_to, finally_to };
+            }
+
+            ASTNodeKind::ThrowStatement(value) => {
+                self.node(value);
+                self.emit(Op::Throw, node);
+            }
+
+            ASTNodeKind::ReturnStatement(Some(call))
+                if self.function && self.tries == 0 && let ASTNodeKind::FunctionCall(name, args) = &call.kind =>
+            {
+                let (name, names) = self.call_operands(name, args, call);
+                self.emit(Op::TailCall { name, names, args: args.len() }, call);
+            }
+
+            ASTNodeKind::ReturnStatement(value) => {
+                match value {
+                    Some(value) => self.node(value),
+                    None => self.constant(Value::Null, node),
+                }
+                self.emit(Op::Return, node);
+            }
+
+            ASTNodeKind::Break => {
+                self.emit(Op::Break, node);
+            }
+
+            ASTNodeKind::Continue => {
+                self.emit(Op::Continue, node);
+            }
+
+            ASTNodeKind::FunctionCall(name, args) => {
+                let (name, names) = self.call_operands(name, args, node);
+                self.emit(Op::Call { name, names, args: args.len() }, node);
+            }
+
+            // the callee is checked before the arguments are evaluated
+            ASTNodeKind::ImmediateInvocation(func, args) => {
+                self.node(func);
+                self.emit(Op::Invocable, node);
+                let names = self.arguments(args);
+                self.emit(Op::Invoke { names, args: args.len() }, node);
+            }
+
+            ASTNodeKind::ArrayLiteral(elements) => {
+                for element in elements {
+                    self.node(element);
+                }
+                self.emit(Op::Array(elements.len()), node);
+            }
+
+            ASTNodeKind::HashMapLiteral(pairs) => {
+                for (_, value) in pairs {
+                    self.node(value);
+                }
+                self.chunk.keys.push(pairs.iter().map(|(key, _)| key.clone()).collect());
+                self.emit(Op::Map(self.chunk.keys.len() - 1), node);
+            }
+
+            ASTNodeKind::Range(start, end, inclusive) => {
+                self.node(start);
+                self.node(end);
+                self.emit(Op::Range(*inclusive), node);
+            }
+
+            // a variable's array is indexed where it is stored, not copied,
+            // when the index cannot change the variable first
+            ASTNodeKind::IndexAccess(container, index)
+                if let ASTNodeKind::Identifier(name) = &container.kind && only_reads(index) =>
+            {
+                self.node(index);
+                let op = match self.place(name, container.slot) {
+                    Place::Register(register) => Op::IndexLocal(register),
+                    Place::Frame(variable) => Op::IndexVar(variable),
+                };
+                self.emit(op, node);
+            }
+
+            ASTNodeKind::IndexAccess(container, index) => {
+                self.node(container);
+                self.node(index);
+                self.emit(Op::Index, node);
+            }
+
+            ASTNodeKind::MemberAccess(object, member) => {
+                self.node(object);
+                let name = self.chunk.name(member);
+                let object = self.chunk.name(&object.to_string());
+                self.emit(Op::Member { name, object }, node);
+            }
+
+            ASTNodeKind::MethodCall(object, method, args) => {
+                self.node(object);
+                let names = self.arguments(args);
+                let method = self.chunk.name(method);
+                self.emit(Op::MethodCall { method, names, args: args.len() }, node);
+            }
+        }
+    }
+
+    /// Stores the value on top of the stack into `target`, which a field or
+    /// element was just set on, popping it. Like `Evaluator::write_back`,
+    /// containers along the way are evaluated again.
+    fn write_back(&mut self, target: &ASTNode, node: &ASTNode) {
+        match &target.kind {
+            ASTNodeKind::Identifier(name) => match self.place(name, target.slot) {
+                Place::Register(register) if self.is_const(register) => {
+                    self.fail(PlutoError::runtime(format!("Cannot assign to constant '{}'", name)), node);
+                }
+                Place::Register(register) => {
+                    self.emit(Op::StoreLocal(register), node);
+                }
+                Place::Frame(variable) => {
+                    self.emit(Op::SetVar(variable), node);
+                    self.emit(Op::Pop, node);
+                }
+            },
+            ASTNodeKind::MemberAccess(object, field) => {
+                self.node(object);
+                let field = self.chunk.name(field);
+                self.emit(Op::SetField(field), node);
+                self.write_back(object, node);
+            }
+            ASTNodeKind::IndexAccess(object, index) => {
+                self.node(object);
+                self.node(index);
+                self.emit(Op::WriteIndex, node);
+                self.write_back(object, node);
+            }
+            _ => self.fail(PlutoError::runtime("Invalid assignment target"), node),
+        }
+    }
+
+    fn is_const(&self, register: usize) -> bool {
+        self.levels.iter().any(|level| level.consts.contains(&register))
+    }
+
+    /// Tries the arms in order on a copy of the subject, which stays under
+    /// them on the stack until one matches.
+    fn match_expression(&mut self, subject: &ASTNode, arms: &[MatchArm], node: &ASTNode) {
+        self.node(subject);
+        let mut to_end = Vec::new();
+        for (pattern, guard, result) in arms {
+            let mut names = Vec::new();
+            pattern_names(pattern, &mut names);
+            // or-pattern bindings are looked up by name
+            let frame = has_alternatives(pattern) || self.shares(names.iter());
+
+            // the pattern's values are evaluated outside the arm's scope, so
+            // its registers are set aside until the pattern matches
+            let (bindings, level) = match frame {
+                true => {
+                    self.emit(Op::BeginBindings, node);
+                    (Bindings::Frame, None)
+                }
+                false => {
+                    self.enter(false);
+                    let mut registers = HashMap::new();
+                    for name in names {
+                        let index = registers.len();
+                        registers.entry(name).or_insert_with(|| self.local(0, index));
+                    }
+                    (Bindings::Registers(registers), self.levels.pop())
+                }
+            };
+            self.emit(Op::Dup, node);
+            let mut fails = Vec::new();
+            self.pattern(pattern, 1, &bindings, &mut fails, node);
+
+            match level {
+                Some(level) => self.levels.push(level),
+                None => {
+                    self.enter(true);
+                    self.emit(Op::PushBindings, node);
+                }
+            }
+            let to_guard_fail = guard.as_ref().map(|guard| {
+                self.node(guard);
+                self.emit(Op::JumpIfFalsy(0), node)
+            });
+            self.node(result);
+            if self.leave() {
+                self.emit(Op::PopScope, node);
+            }
+            self.emit(Op::Nip, node);
+            to_end.push(self.emit(Op::Jump(0), node));
+
+            let mut to_next = Vec::new();
+            if let Some(to_guard_fail) = to_guard_fail {
+                match frame {
+                    true => {
+                        let guard_fail = self.here();
+                        self.patch(to_guard_fail, guard_fail);
+                        self.emit(Op::PopScope, node);
+                        to_next.push(self.emit(Op::Jump(0), node));
+                    }
+                    false => to_next.push(to_guard_fail),
+                }
+            }
+            self.unwind(&fails, 0, node);
+            if frame && !fails.is_empty() {
+                self.emit(Op::DropBindings, node);
+            }
+            let next = self.here();
+            for jump in to_next {
+                self.patch(jump, next);
+            }
+        }
+        self.emit(Op::Pop, node);
+        self.constant(Value::Null, node);
+        let end = self.here();
+        for jump in to_end {
+            self.patch(jump, end);
+        }
+    }
+
+    /// Tests the value on top of the stack, `depth` values above the arm's
+    /// subject, against `pattern`, and pops it. A failing test jumps with
+    /// the tested value still on the stack; `fails` gets the jump and how
+    /// many values it leaves above the subject.
+    fn pattern(&mut self, pattern: &Pattern, depth: usize, bindings: &Bindings, fails: &mut Vec<(usize, usize)>, node: &ASTNode) {
+        match pattern {
+            Pattern::Wildcard => {
+                self.emit(Op::Pop, node);
+            }
+            Pattern::Binding(name) => self.bind(name, bindings, node),
+            Pattern::Value(expr) => {
+                self.node(expr);
+                self.emit(Op::MatchValue, node);
+                self.test(depth, fails, node);
+                self.emit(Op::Pop, node);
+            }
+            Pattern::Range(start, end, inclusive) => {
+                self.node(start);
+                self.node(end);
+                self.emit(Op::MatchRange(*inclusive), node);
+                self.test(depth, fails, node);
+                self.emit(Op::Pop, node);
+            }
+            Pattern::Array(elements, rest) => {
+                self.emit(Op::MatchArray { len: elements.len(), rest: rest.is_some() }, node);
+                self.test(depth, fails, node);
+                for (i, element) in elements.iter().enumerate() {
+                    self.emit(Op::Element(i), node);
+                    self.pattern(element, depth + 1, bindings, fails, node);
+                }
+                if let Some(Some(name)) = rest {
+                    self.emit(Op::Rest(elements.len()), node);
+                    self.bind(name, bindings, node);
+                }
+                self.emit(Op::Pop, node);
+            }
+            Pattern::Map(entries) => {
+                self.emit(Op::MatchMap, node);
+                self.test(depth, fails, node);
+                for (key, entry) in entries {
+                    let key = self.chunk.name(key);
+                    self.emit(Op::Field(key), node);
+                    self.test(depth, fails, node);
+                    self.pattern(entry, depth + 1, bindings, fails, node);
+                }
+                self.emit(Op::Pop, node);
+            }
+            // each alternative binds into a scratch scope of its own
+            Pattern::Or(alternatives) => {
+                let mut to_end = Vec::new();
+                for alternative in alternatives {
+                    self.emit(Op::BeginBindings, node);
+                    self.emit(Op::Dup, node);
+                    let mut alternative_fails = Vec::new();
+                    self.pattern(alternative, depth + 1, bindings, &mut alternative_fails, node);
+                    self.emit(Op::MergeBindings, node);
+                    self.emit(Op::Pop, node);
+                    to_end.push(self.emit(Op::Jump(0), node));
+                    self.unwind(&alternative_fails, depth, node);
+                    self.emit(Op::DropBindings, node);
+                }
+                fails.push((self.emit(Op::Jump(0), node), depth));
+                let end = self.here();
+                for jump in to_end {
+                    self.patch(jump, end);
+                }
+            }
+        }
+    }
+
+    /// Jumps to a failure when the test just emitted pushed `false`.
+    fn test(&mut self, depth: usize, fails: &mut Vec<(usize, usize)>, node: &ASTNode) {
+        fails.push((self.emit(Op::JumpIfFalsy(0), node), depth));
+    }
+
+    /// Pops the value on top of the stack into the binding `name`.
+    fn bind(&mut self, name: &str, bindings: &Bindings, node: &ASTNode) {
+        let op = match bindings {
+            Bindings::Registers(registers) => Op::StoreLocal(registers[name]),
+            Bindings::Frame => Op::Bind(self.chunk.name(name)),
+        };
+        self.emit(op, node);
+    }
+
+    /// Lands the failed tests in `fails`, popping what each left above
+    /// `keep` values, deepest first.
+    fn unwind(&mut self, fails: &[(usize, usize)], keep: usize, node: &ASTNode) {
+        let Some(deepest) = fails.iter().map(|&(_, depth)| depth).max() else {
+            return;
+        };
+        let start = self.here();
+        for _ in keep..deepest {
+            self.emit(Op::Pop, node);
+        }
+        for &(jump, depth) in fails {
+            self.patch(jump, start + deepest - depth);
+        }
+    }
+
+    /// Whether a block declaring `statements` must be a frame: a `const`
+    /// redeclaring a name needs one to detect it at runtime, a `from` import
+    /// binds names without slots, and a shared name is found in one.
+    fn needs_frame(&self, statements: &[ASTNode]) -> bool {
+        let mut declared = HashSet::new();
+        statements.iter().any(|stmt| match &stmt.kind {
+            ASTNodeKind::ConstDeclaration(name, _) => !declared.insert(name) || self.shared.contains(name),
+            ASTNodeKind::VariableDeclaration(name, _)
+            | ASTNodeKind::FunctionDeclaration(name, _, _)
+            | ASTNodeKind::StructDeclaration(name, _) => {
+                declared.insert(name);
+                self.shared.contains(name)
+            }
+            ASTNodeKind::ImportStatement(path, alias) => self.shared.contains(&import_binding(path, alias.as_ref())),
+            ASTNodeKind::FromImport(..) => true,
+            _ => false,
+        })
+    }
+
+    /// Pushes the callee and arguments of a call.
+    fn call_operands(&mut self, name: &str, args: &[(Option<String>, Box<ASTNode>)], node: &ASTNode) -> (usize, usize) {
+        let callee = match self.place(name, node.slot) {
+            Place::Register(register) => Op::CalleeLocal { register, name: self.chunk.name(name) },
+            Place::Frame(variable) => Op::Callee(variable),
+        };
+        self.emit(callee, node);
+        let names = self.arguments(args);
+        (self.chunk.name(name), names)
+    }
+
+    /// Pushes the arguments of a call, returning the index of their names.
+    fn arguments(&mut self, args: &[(Option<String>, Box<ASTNode>)]) -> usize {
+        for (_, arg) in args {
+            self.node(arg);
+        }
+        self.chunk.arg_names.push(args.iter().map(|(name, _)| name.clone()).collect());
+        self.chunk.arg_names.len() - 1
+    }
+}
+
+/// Adds the names that default values of parameters and fields in `node`
+/// refer to. Defaults are evaluated in the caller's scopes, looked up by
+/// name, so any scope declaring one of them must be a frame.
+pub fn default_names(node: &ASTNode, names: &mut HashSet<String>) {
+    match &node.kind {
+        ASTNodeKind::FunctionDeclaration(_, params, _)
+        | ASTNodeKind::AnonymousFunction(params, _)
+        | ASTNodeKind::StructDeclaration(_, params) => {
+            for (_, default) in params {
+                if let Some(default) = default {
+                    referenced(default, names);
+                }
+            }
+        }
+        _ => {}
+    }
+    children(node, &mut |child| default_names(child, names));
+}
+
+/// Adds the names that functions created in `node` refer to, and the
+/// structs `impl` blocks look up by name.
+fn captured(node: &ASTNode, names: &mut HashSet<String>) {
+    match &node.kind {
+        ASTNodeKind::FunctionDeclaration(..) | ASTNodeKind::AnonymousFunction(..) => referenced(node, names),
+        ASTNodeKind::ImplBlock(name, _) => {
+            names.insert(name.clone());
+            referenced(node, names);
+        }
+        _ => children(node, &mut |child| captured(child, names)),
+    }
+}
+
+/// Adds every name read, assigned or called in `node`.
+fn referenced(node: &ASTNode, names: &mut HashSet<String>) {
+    match &node.kind {
+        ASTNodeKind::Identifier(name) | ASTNodeKind::Assignment(name, _) | ASTNodeKind::FunctionCall(name, _) => {
+            names.insert(name.clone());
+        }
+        _ => {}
+    }
+    children(node, &mut |child| referenced(child, names));
+}
+
+/// Calls `f` on each node directly inside `node`, including function
+/// bodies, default values and the expressions in match patterns.
+fn children(node: &ASTNode, f: &mut dyn FnMut(&ASTNode)) {
+    let params = |params: &Params, f: &mut dyn FnMut(&ASTNode)| {
+        for (_, default) in params {
+            if let Some(default) = default {
+                f(default);
+            }
+        }
+    };
+    match &node.kind {
+        ASTNodeKind::Program(statements) | ASTNodeKind::Block(statements) | ASTNodeKind::ImplBlock(_, statements) => {
+            statements.iter().for_each(f)
+        }
+        ASTNodeKind::VariableDeclaration(_, value) | ASTNodeKind::ConstDeclaration(_, value) | ASTNodeKind::ReturnStatement(value) => {
+            if let Some(value) = value {
+                f(value);
+            }
+        }
+        ASTNodeKind::Assignment(_, operand)
+        | ASTNodeKind::UnaryExpression(_, operand)
+        | ASTNodeKind::PostfixUnaryExpression(_, operand)
+        | ASTNodeKind::MemberAccess(operand, _)
+        | ASTNodeKind::ThrowStatement(operand) => f(operand),
+        ASTNodeKind::BinaryExpression(left, _, right)
+        | ASTNodeKind::AssignmentOp(_, left, right)
+        | ASTNodeKind::IndexAccess(left, right)
+        | ASTNodeKind::Range(left, right, _)
+        | ASTNodeKind::AssignmentMember(left, _, right)
+        | ASTNodeKind::WhileStatement(left, right) => {
+            f(left);
+            f(right);
+        }
+        ASTNodeKind::AssignmentIndex(target, index, value) | ASTNodeKind::TernaryExpression(target, index, value) => {
+            f(target);
+            f(index);
+            f(value);
+        }
+        ASTNodeKind::InterpolatedString(parts) | ASTNodeKind::ArrayLiteral(parts) => parts.iter().for_each(|part| f(part)),
+        ASTNodeKind::HashMapLiteral(pairs) => pairs.iter().for_each(|(_, value)| f(value)),
+        ASTNodeKind::FunctionDeclaration(_, parameters, body) | ASTNodeKind::AnonymousFunction(parameters, body) => {
+            params(parameters, f);
+            f(body);
+        }
+        ASTNodeKind::StructDeclaration(_, fields) => params(fields, f),
+        ASTNodeKind::FunctionCall(_, args) => args.iter().for_each(|(_, arg)| f(arg)),
+        ASTNodeKind::ImmediateInvocation(callee, args) | ASTNodeKind::MethodCall(callee, _, args) => {
+            f(callee);
+            args.iter().for_each(|(_, arg)| f(arg));
+        }
+        ASTNodeKind::IfStatement(condition, then_branch, else_branch) => {
+            f(condition);
+            f(then_branch);
+            if let Some(else_branch) = else_branch {
+                f(else_branch);
+            }
+        }
+        ASTNodeKind::ForStatement(init, condition, increment, body) => {
+            for part in [init, condition, increment].into_iter().flatten() {
+                f(part);
+            }
+            f(body);
+        }
+        ASTNodeKind::ForInStatement(_, iterable, body) => {
+            f(iterable);
+            f(body);
+        }
+        ASTNodeKind::MatchExpression(subject, arms) => {
+            f(subject);
+            for (pattern, guard, result) in arms {
+                pattern_nodes(pattern, f);
+                if let Some(guard) = guard {
+                    f(guard);
+                }
+                f(result);
+            }
+        }
+        ASTNodeKind::TryStatement(body, catch, finally) => {
+            f(body);
+            if let Some((_, handler)) = catch {
+                f(handler);
+            }
+            if let Some(finally) = finally {
+                f(finally);
+            }
+        }
+        ASTNodeKind::NumberLiteral(_)
+        | ASTNodeKind::FloatLiteral(_)
+        | ASTNodeKind::NullLiteral
+        | ASTNodeKind::StringLiteral(_)
+        | ASTNodeKind::BooleanLiteral(_)
+        | ASTNodeKind::Identifier(_)
+        | ASTNodeKind::ImportStatement(..)
+        | ASTNodeKind::FromImport(..)
+        | ASTNodeKind::Break
+        | ASTNodeKind::Continue => {}
+    }
+}
+
+/// Whether evaluating `node` only reads variables, never assigning or calling anything.
+fn only_reads(node: &ASTNode) -> bool {
+    match &node.kind {
+        ASTNodeKind::NumberLiteral(_)
+        | ASTNodeKind::FloatLiteral(_)
+        | ASTNodeKind::StringLiteral(_)
+        | ASTNodeKind::BooleanLiteral(_)
+        | ASTNodeKind::NullLiteral
+        | ASTNodeKind::Identifier(_) => true,
+        ASTNodeKind::BinaryExpression(left, _, right) | ASTNodeKind::IndexAccess(left, right) => only_reads(left) && only_reads(right),
+        ASTNodeKind::UnaryExpression(_, operand) | ASTNodeKind::MemberAccess(operand, _) => only_reads(operand),
+        _ => false,
+    }
+}
+
+/// Calls `f` on the expressions of value and range patterns in `pattern`.
+fn pattern_nodes(pattern: &Pattern, f: &mut dyn FnMut(&ASTNode)) {
+    match pattern {
+        Pattern::Wildcard | Pattern::Binding(_) => {}
+        Pattern::Value(expr) => f(expr),
+        Pattern::Range(start, end, _) => {
+            f(start);
+            f(end);
+        }
+        Pattern::Array(elements, _) | Pattern::Or(elements) => elements.iter().for_each(|element| pattern_nodes(element, f)),
+        Pattern::Map(entries) => entries.iter().for_each(|(_, entry)| pattern_nodes(entry, f)),
+    }
+}
+
+/// The names `pattern` binds, in the order the resolver declares them.
+fn pattern_names(pattern: &Pattern, names: &mut Vec<String>) {
+    match pattern {
+        Pattern::Wildcard | Pattern::Value(_) | Pattern::Range(..) => {}
+        Pattern::Binding(name) => names.push(name.clone()),
+        Pattern::Array(elements, rest) => {
+            for element in elements {
+                pattern_names(element, names);
+            }
+            if let Some(Some(name)) = rest {
+                names.push(name.clone());
+            }
+        }
+        Pattern::Map(entries) => entries.iter().for_each(|(_, entry)| pattern_names(entry, names)),
+        Pattern::Or(alternatives) => alternatives.iter().for_each(|alternative| pattern_names(alternative, names)),
+    }
+}
+
+/// Whether `pattern` contains an or-pattern, whose bindings may come in any order.
+fn has_alternatives(pattern: &Pattern) -> bool {
+    match pattern {
+        Pattern::Or(_) => true,
+        Pattern::Array(elements, _) => elements.iter().any(has_alternatives),
+        Pattern::Map(entries) => entries.iter().any(|(_, entry)| has_alternatives(entry)),
+        _ => false,
+    }
+}
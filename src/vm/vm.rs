@@ -0,0 +1,798 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::{Rc, Weak};
+
+use crate::error::error::PlutoError;
+use crate::evaluator::evaluator::{
+    in_range_pattern, pattern_field, range, set_field, set_index, thrown, EvalResult, Evaluator, PlutoMethod, Scope, TailCall,
+    Value,
+};
+use crate::evaluator::ordered_map::OrderedMap;
+use crate::evaluator::scope::Frame;
+use crate::evaluator::structs::StructDef;
+use crate::parser::ast::{ASTNode, BinaryOp, Params, Span};
+use crate::vm::chunk::{Chunk, Function, Op};
+use crate::vm::compiler::{default_names, Compiler};
+
+/// Where `break` and `continue` go inside the innermost running loop.
+struct LoopRecord {
+    stack_len: usize,
+    scope_len: usize,
+    break_to: usize,
+    continue_to: usize,
+    /// The items left to visit, in a `for ... in` loop.
+    items: Option<Box<dyn Iterator<Item = Value>>>,
+}
+
+/// A running `try`, and what to unwind to when something leaves it early.
+struct Handler {
+    stack_len: usize,
+    scope_len: usize,
+    loops_len: usize,
+    bindings_len: usize,
+    /// Where a failure goes, until the `catch` block starts.
+    catch_to: Option<usize>,
+    finally_to: usize,
+}
+
+/// How a `try` body or `catch` block ended, kept on the stack under the
+/// value it ended with while the `finally` block runs.
+const NORMAL: i64 = 0;
+const RETURN: i64 = 1;
+const BREAK: i64 = 2;
+const CONTINUE: i64 = 3;
+const ERROR: i64 = 4;
+
+/// Where a running chunk is.
+#[derive(Default)]
+struct State {
+    ip: usize,
+    loops: Vec<LoopRecord>,
+    handlers: Vec<Handler>,
+}
+
+/// Runs compiled chunks against an `Evaluator`'s environment. Function bodies
+/// are compiled on their first call and cached for as long as the function lives.
+#[derive(Default)]
+pub struct Vm {
+    functions: HashMap<*const ASTNode, (Weak<ASTNode>, Rc<Chunk>)>,
+    /// Cache size at which dead functions are next swept out.
+    sweep_at: usize,
+    /// Names default values refer to, in every program run so far.
+    defaults: HashSet<String>,
+    /// The operand stack, shared by the running chunks.
+    stack: Vec<Value>,
+    /// The registers of the running chunks, each using those from its base on.
+    registers: Vec<Value>,
+    /// Bindings of the match patterns being tested, innermost last.
+    bindings: Vec<Frame>,
+    /// Stands in a closure's scopes for each scope whose variables were in
+    /// registers; the closure never looks anything up in it.
+    placeholder: Scope,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Vm::default()
+    }
+
+    /// The VM counterpart of `Evaluator::evaluate_ast`.
+    pub fn run(&mut self, evaluator: &mut Evaluator, ast: &ASTNode) -> Result<Value, PlutoError> {
+        let known = self.defaults.len();
+        default_names(ast, &mut self.defaults);
+        // cached functions may keep one of the new names in a register
+        if self.defaults.len() > known {
+            self.functions.clear();
+        }
+        let chunk = Compiler::compile(ast, &self.defaults);
+        let base = self.registers.len();
+        self.registers.resize(base + chunk.registers, Value::Null);
+        let result = self.run_chunk(evaluator, &chunk, base);
+        self.registers.truncate(base);
+        match result.map_err(|e| evaluator.locate(e))? {
+            EvalResult::Value(val) | EvalResult::Return(val) => Ok(val),
+            EvalResult::Break => Err(PlutoError::syntax("Unexpected 'break' outside of loop")),
+            EvalResult::Continue => Err(PlutoError::syntax("Unexpected 'continue' outside of loop")),
+        }
+    }
+
+    /// Runs `chunk` with its registers from `base` on.
+    fn run_chunk(&mut self, evaluator: &mut Evaluator, chunk: &Chunk, base: usize) -> Result<EvalResult, PlutoError> {
+        let base_scopes = evaluator.env_stack.len();
+        let base_stack = self.stack.len();
+        let base_bindings = self.bindings.len();
+        let result = self.execute(evaluator, chunk, base);
+        evaluator.env_stack.truncate(base_scopes);
+        self.stack.truncate(base_stack);
+        self.bindings.truncate(base_bindings);
+        result
+    }
+
+    /// Runs the chunk's code, sending failures, and anything else that
+    /// leaves a `try` early, to the innermost running `try`.
+    fn execute(&mut self, evaluator: &mut Evaluator, chunk: &Chunk, base: usize) -> Result<EvalResult, PlutoError> {
+        let mut state = State::default();
+        loop {
+            let result = self.run_ops(evaluator, chunk, base, &mut state).map_err(|e| e.with_span(chunk.spans[state.ip - 1]));
+            let (payload, code) = match result {
+                Ok(EvalResult::Value(value)) => return Ok(EvalResult::Value(value)),
+                result if state.handlers.is_empty() => return result,
+                Ok(EvalResult::Return(value)) => (value, RETURN),
+                Ok(EvalResult::Break) => (Value::Null, BREAK),
+                Ok(EvalResult::Continue) => (Value::Null, CONTINUE),
+                Err(err) => (Value::Error(Box::new(err)), ERROR),
+            };
+            let handler = state.handlers.last_mut().unwrap();
+            self.stack.truncate(handler.stack_len);
+            evaluator.env_stack.truncate(handler.scope_len);
+            state.loops.truncate(handler.loops_len);
+            self.bindings.truncate(handler.bindings_len);
+            match handler.catch_to.take() {
+                Some(catch_to) if code == ERROR => {
+                    self.stack.push(payload);
+                    state.ip = catch_to;
+                }
+                _ => {
+                    self.stack.push(payload);
+                    self.stack.push(Value::Number(code));
+                    state.ip = handler.finally_to;
+                    state.handlers.pop();
+                }
+            }
+        }
+    }
+
+    /// Runs ops from `state.ip` until the chunk ends or something leaves it
+    /// or a running `try` early.
+    fn run_ops(&mut self, evaluator: &mut Evaluator, chunk: &Chunk, base: usize, state: &mut State) -> Result<EvalResult, PlutoError> {
+        let State { ip, loops, handlers } = state;
+        while *ip < chunk.code.len() {
+            let op = chunk.code[*ip];
+            let at = *ip;
+            *ip += 1;
+            // Control flow raised by a call or a `finally` block, handled below.
+            let signal = match op {
+                Op::Constant(index) => {
+                    self.stack.push(chunk.constants[index].clone());
+                    continue;
+                }
+                Op::Pop => {
+                    self.stack.pop();
+                    continue;
+                }
+                Op::Nip => {
+                    let top = self.stack.pop().unwrap();
+                    *self.stack.last_mut().unwrap() = top;
+                    continue;
+                }
+                Op::Dup => {
+                    self.stack.push(self.stack.last().unwrap().clone());
+                    continue;
+                }
+                Op::GetVar(variable) => {
+                    let (name, slot) = &chunk.variables[variable];
+                    let value = evaluator
+                        .with_variable(name, *slot, |(val, _)| val.clone())
+                        .ok_or_else(|| PlutoError::name(format!("Undefined variable '{}'", name)))?;
+                    self.stack.push(value);
+                    continue;
+                }
+                Op::GetLocal(register) => {
+                    self.stack.push(self.registers[base + register].clone());
+                    continue;
+                }
+                Op::DefineVar(variable) => {
+                    let value = self.stack.last().unwrap().clone();
+                    evaluator.current_env_mut().insert(chunk.variables[variable].0.clone(), (value, false));
+                    continue;
+                }
+                Op::CheckConst(variable) => {
+                    let name = &chunk.variables[variable].0;
+                    if evaluator.current_env_mut().contains_key(name) {
+                        return Err(PlutoError::runtime(format!("Variable '{}' already declared", name)));
+                    }
+                    continue;
+                }
+                Op::DefineConst(variable) => {
+                    let value = self.stack.last().unwrap().clone();
+                    evaluator.current_env_mut().insert(chunk.variables[variable].0.clone(), (value, true));
+                    continue;
+                }
+                Op::SetVar(variable) => {
+                    let (name, slot) = &chunk.variables[variable];
+                    let value = self.stack.last().unwrap().clone();
+                    evaluator.assign_variable(name, *slot, value)?;
+                    continue;
+                }
+                Op::SetLocal(register) => {
+                    self.registers[base + register] = self.stack.last().unwrap().clone();
+                    continue;
+                }
+                Op::StoreLocal(register) => {
+                    self.registers[base + register] = self.stack.pop().unwrap();
+                    continue;
+                }
+                Op::Binary(op) => {
+                    let right = self.stack.pop().unwrap();
+                    let left = self.stack.last_mut().unwrap();
+                    if let (Value::Number(a), Value::Number(b)) = (&*left, &right)
+                        && let Some(result) = number_binary(*a, op, *b)
+                    {
+                        *left = result;
+                    } else {
+                        let left = self.stack.pop().unwrap();
+                        self.stack.push(evaluator.eval_binary(left, op, right)?);
+                    }
+                    continue;
+                }
+                Op::Postfix { op, name } => {
+                    let (name, slot) = &chunk.variables[name];
+                    let old = evaluator.postfix_update(op, name, *slot)?;
+                    self.stack.push(old);
+                    continue;
+                }
+                Op::PostfixLocal { op, register } => {
+                    let variable = &mut self.registers[base + register];
+                    let new_val = evaluator.postfix_value(op, variable)?;
+                    let old = std::mem::replace(variable, new_val);
+                    self.stack.push(old);
+                    continue;
+                }
+                Op::AssignOp { op, name } => {
+                    let (name, slot) = &chunk.variables[name];
+                    let value = self.stack.pop().unwrap();
+                    let new_val = evaluator.compound_assign(op, name, *slot, value)?;
+                    self.stack.push(new_val);
+                    continue;
+                }
+                Op::AssignOpLocal { op, register } => {
+                    let value = self.stack.pop().unwrap();
+                    let variable = &mut self.registers[base + register];
+                    let new_val = evaluator.compound_value(op, variable, value)?;
+                    self.stack.push(new_val);
+                    continue;
+                }
+                Op::Index => {
+                    let index = self.stack.pop().unwrap();
+                    let container = self.stack.pop().unwrap();
+                    self.stack.push(Evaluator::index(&container, index)?);
+                    continue;
+                }
+                Op::IndexVar(variable) => {
+                    let (name, slot) = &chunk.variables[variable];
+                    let index = self.stack.pop().unwrap();
+                    let value = evaluator
+                        .with_variable(name, *slot, |(container, _)| Evaluator::index(container, index))
+                        .ok_or_else(|| PlutoError::name(format!("Undefined variable '{}'", name)))?;
+                    self.stack.push(value?);
+                    continue;
+                }
+                Op::IndexLocal(register) => {
+                    let index = self.stack.pop().unwrap();
+                    let value = Evaluator::index(&self.registers[base + register], index)?;
+                    self.stack.push(value);
+                    continue;
+                }
+                Op::Member { name, object } => {
+                    let value = self.stack.pop().unwrap();
+                    let member = Evaluator::member(&value, &chunk.names[name], || chunk.names[object].clone())?;
+                    self.stack.push(member);
+                    continue;
+                }
+                Op::MethodCall { method, names, args } => {
+                    let values = self.stack.split_off(self.stack.len() - args);
+                    let receiver = self.stack.pop().unwrap();
+                    let method = &chunk.names[method];
+                    // a module's functions are called like functions, with named arguments
+                    let export = match &receiver {
+                        Value::Module(frame) => frame.borrow().value(method),
+                        _ => None,
+                    };
+                    if let Some(func @ (Value::BuiltInFunction(_) | Value::UserFunction { .. } | Value::StructType(_))) = export {
+                        let args = chunk.arg_names[names].iter().cloned().zip(values).collect();
+                        match self.call(evaluator, func, args, Some((method, chunk.spans[at])))? {
+                            EvalResult::Value(value) => {
+                                self.stack.push(value);
+                                continue;
+                            }
+                            signal => signal,
+                        }
+                    } else {
+                        let value = receiver.call_method(evaluator, method, values)?;
+                        self.stack.push(value);
+                        continue;
+                    }
+                }
+                Op::Concat(count) => {
+                    let parts = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(Value::String(parts.iter().map(|v| v.to_string()).collect()));
+                    continue;
+                }
+                Op::Unary(op) => {
+                    let value = self.stack.pop().unwrap();
+                    self.stack.push(evaluator.eval_unary(op, value)?);
+                    continue;
+                }
+                Op::PushScope => {
+                    evaluator.push_scope(Frame::new());
+                    continue;
+                }
+                Op::PopScope => {
+                    evaluator.env_stack.pop();
+                    continue;
+                }
+                Op::Jump(target) => {
+                    *ip = target;
+                    continue;
+                }
+                Op::JumpIfFalsy(target) => {
+                    let truthy = match self.stack.pop().unwrap() {
+                        Value::Bool(b) => b,
+                        condition => evaluator.is_truthy(&condition),
+                    };
+                    if !truthy {
+                        *ip = target;
+                    }
+                    continue;
+                }
+                Op::ShortCircuit(op, target) => {
+                    let left = self.stack.last_mut().unwrap();
+                    if let Some(result) = evaluator.short_circuit(op, left) {
+                        *left = result;
+                        *ip = target;
+                    }
+                    continue;
+                }
+                Op::LoopStart { break_to, continue_to } => {
+                    loops.push(LoopRecord {
+                        stack_len: self.stack.len(),
+                        scope_len: evaluator.env_stack.len(),
+                        break_to,
+                        continue_to,
+                        items: None,
+                    });
+                    continue;
+                }
+                Op::Iterate { break_to, continue_to } => {
+                    let iterable = self.stack.pop().unwrap();
+                    let items = Evaluator::iterate(iterable)?;
+                    loops.push(LoopRecord {
+                        stack_len: self.stack.len(),
+                        scope_len: evaluator.env_stack.len(),
+                        break_to,
+                        continue_to,
+                        items: Some(items),
+                    });
+                    continue;
+                }
+                Op::IterNext(bindings) => {
+                    let record = loops.last_mut().unwrap();
+                    match record.items.as_mut().unwrap().next() {
+                        Some(item) => {
+                            let scope = Evaluator::bind_loop_variables(&chunk.bindings[bindings], item)?;
+                            evaluator.push_scope(scope);
+                        }
+                        None => *ip = record.break_to,
+                    }
+                    continue;
+                }
+                Op::IterNextLocal { bindings, register } => {
+                    let record = loops.last_mut().unwrap();
+                    match record.items.as_mut().unwrap().next() {
+                        Some(item) => {
+                            let names = &chunk.bindings[bindings];
+                            let start = base + register;
+                            if names.len() == 1 {
+                                self.registers[start] = item;
+                            } else {
+                                let values = Evaluator::unpack_loop_item(names.len(), item)?;
+                                for (offset, value) in values.into_iter().enumerate() {
+                                    self.registers[start + offset] = value;
+                                }
+                            }
+                        }
+                        None => *ip = record.break_to,
+                    }
+                    continue;
+                }
+                Op::LoopEnd => {
+                    loops.pop();
+                    continue;
+                }
+                Op::Return => EvalResult::Return(self.stack.pop().unwrap()),
+                Op::Break => EvalResult::Break,
+                Op::Continue => EvalResult::Continue,
+                Op::Callee(variable) => {
+                    let (name, slot) = &chunk.variables[variable];
+                    let func = evaluator.with_variable(name, *slot, |(val, _)| val.clone());
+                    self.stack.push(callable(func, name)?);
+                    continue;
+                }
+                Op::CalleeLocal { register, name } => {
+                    let func = self.registers[base + register].clone();
+                    self.stack.push(callable(Some(func), &chunk.names[name])?);
+                    continue;
+                }
+                Op::Call { name, names, args } => {
+                    let values = self.stack.split_off(self.stack.len() - args);
+                    let func = self.stack.pop().unwrap();
+                    let args = chunk.arg_names[names].iter().cloned().zip(values).collect();
+                    match self.call(evaluator, func, args, Some((&chunk.names[name], chunk.spans[at])))? {
+                        EvalResult::Value(value) => {
+                            self.stack.push(value);
+                            continue;
+                        }
+                        signal => signal,
+                    }
+                }
+                Op::TailCall { name, names, args } => {
+                    let values = self.stack.split_off(self.stack.len() - args);
+                    let func = self.stack.pop().unwrap();
+                    let args = chunk.arg_names[names].iter().cloned().zip(values).collect();
+                    let name = &chunk.names[name];
+                    if let Value::UserFunction { .. } = func {
+                        evaluator.tail_call = Some(TailCall { func, args, name: name.clone(), call_site: chunk.spans[at] });
+                        return Ok(EvalResult::Return(Value::Null));
+                    }
+                    match self.call(evaluator, func, args, Some((name, chunk.spans[at])))? {
+                        EvalResult::Value(value) => EvalResult::Return(value),
+                        signal => signal,
+                    }
+                }
+                Op::Invocable => {
+                    if !matches!(self.stack.last(), Some(Value::UserFunction { .. })) {
+                        return Err(PlutoError::type_error("Cannot invoke a non-function value"));
+                    }
+                    continue;
+                }
+                Op::Invoke { names, args } => {
+                    let values = self.stack.split_off(self.stack.len() - args);
+                    let func = self.stack.pop().unwrap();
+                    let args = chunk.arg_names[names].iter().cloned().zip(values).collect();
+                    match self.call(evaluator, func, args, None)? {
+                        EvalResult::Value(value) => {
+                            self.stack.push(value);
+                            continue;
+                        }
+                        signal => signal,
+                    }
+                }
+                Op::Closure(function) => {
+                    let closure = self.closure(evaluator, &chunk.functions[function]);
+                    self.stack.push(closure);
+                    continue;
+                }
+                Op::Struct(index) => {
+                    let (name, fields) = &chunk.structs[index];
+                    self.stack.push(Value::StructType(Rc::new(StructDef::new(name.clone(), fields.clone()))));
+                    continue;
+                }
+                Op::Impl(index) => {
+                    let (name, methods) = &chunk.impls[index];
+                    let def = evaluator.impl_target(name)?;
+                    for (method, function) in methods {
+                        let closure = self.closure(evaluator, &chunk.functions[*function]);
+                        def.methods.borrow_mut().insert(method.clone(), closure);
+                    }
+                    continue;
+                }
+                Op::Import(index) => {
+                    let module = evaluator.import_module(&chunk.imports[index].0, chunk.spans[at])?;
+                    self.stack.push(module);
+                    continue;
+                }
+                Op::FromImport(index) => {
+                    let (path, names) = &chunk.imports[index];
+                    evaluator.eval_from_import(path, names, chunk.spans[at])?;
+                    continue;
+                }
+                Op::Array(count) => {
+                    let items = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(Value::Array(items));
+                    continue;
+                }
+                Op::Map(keys) => {
+                    let keys = &chunk.keys[keys];
+                    let values = self.stack.split_off(self.stack.len() - keys.len());
+                    let mut map = OrderedMap::new();
+                    for (key, value) in keys.iter().zip(values) {
+                        map.insert(key.clone(), value);
+                    }
+                    self.stack.push(Value::HashMapV(map));
+                    continue;
+                }
+                Op::Range(inclusive) => {
+                    let end = self.stack.pop().unwrap();
+                    let start = self.stack.pop().unwrap();
+                    self.stack.push(range(start, end, inclusive)?);
+                    continue;
+                }
+                Op::SetIndexVar(variable) => {
+                    let (name, slot) = &chunk.variables[variable];
+                    let value = self.stack.pop().unwrap();
+                    let index = self.stack.pop().unwrap();
+                    evaluator.assign_variable_index(name, *slot, index, value.clone())?;
+                    self.stack.push(value);
+                    continue;
+                }
+                Op::SetIndexLocal(register) => {
+                    let value = self.stack.pop().unwrap();
+                    let index = self.stack.pop().unwrap();
+                    set_index(&mut self.registers[base + register], index, value.clone())?;
+                    self.stack.push(value);
+                    continue;
+                }
+                Op::SetIndex => {
+                    let mut container = self.stack.pop().unwrap();
+                    let value = self.stack.pop().unwrap();
+                    let index = self.stack.pop().unwrap();
+                    set_index(&mut container, index, value.clone())?;
+                    self.stack.push(value);
+                    self.stack.push(container);
+                    continue;
+                }
+                Op::WriteIndex => {
+                    let index = self.stack.pop().unwrap();
+                    let mut container = self.stack.pop().unwrap();
+                    let value = self.stack.pop().unwrap();
+                    set_index(&mut container, index, value)?;
+                    self.stack.push(container);
+                    continue;
+                }
+                Op::SetField(field) => {
+                    let mut container = self.stack.pop().unwrap();
+                    let value = self.stack.pop().unwrap();
+                    set_field(&mut container, &chunk.names[field], value)?;
+                    self.stack.push(container);
+                    continue;
+                }
+                Op::Throw => return Err(thrown(self.stack.pop().unwrap())),
+                Op::Fail(error) => return Err(chunk.errors[error].clone()),
+                Op::MatchValue => {
+                    let expected = self.stack.pop().unwrap();
+                    let value = self.stack.last().unwrap().clone();
+                    // values of unrelated types simply don't match
+                    let equal = matches!(evaluator.eval_binary(value, BinaryOp::Eq, expected), Ok(Value::Bool(true)));
+                    self.stack.push(Value::Bool(equal));
+                    continue;
+                }
+                Op::MatchRange(inclusive) => {
+                    let end = self.stack.pop().unwrap();
+                    let start = self.stack.pop().unwrap();
+                    let within = in_range_pattern(self.stack.last().unwrap(), start, end, inclusive)?;
+                    self.stack.push(Value::Bool(within));
+                    continue;
+                }
+                Op::MatchArray { len, rest } => {
+                    let fits = match self.stack.last().unwrap() {
+                        Value::Array(items) if rest => items.len() >= len,
+                        Value::Array(items) => items.len() == len,
+                        _ => false,
+                    };
+                    self.stack.push(Value::Bool(fits));
+                    continue;
+                }
+                Op::MatchMap => {
+                    let fits = matches!(self.stack.last().unwrap(), Value::HashMapV(_) | Value::Struct { .. });
+                    self.stack.push(Value::Bool(fits));
+                    continue;
+                }
+                Op::Element(index) => {
+                    let Some(Value::Array(items)) = self.stack.last() else { unreachable!() };
+                    self.stack.push(items[index].clone());
+                    continue;
+                }
+                Op::Rest(from) => {
+                    let Some(Value::Array(items)) = self.stack.last() else { unreachable!() };
+                    self.stack.push(Value::Array(items[from..].to_vec()));
+                    continue;
+                }
+                Op::Field(key) => {
+                    match pattern_field(self.stack.last().unwrap(), &chunk.names[key]) {
+                        Some(field) => {
+                            self.stack.push(field);
+                            self.stack.push(Value::Bool(true));
+                        }
+                        None => self.stack.push(Value::Bool(false)),
+                    }
+                    continue;
+                }
+                Op::BeginBindings => {
+                    self.bindings.push(Frame::new());
+                    continue;
+                }
+                Op::Bind(name) => {
+                    let value = self.stack.pop().unwrap();
+                    self.bindings.last_mut().unwrap().insert(chunk.names[name].clone(), (value, false));
+                    continue;
+                }
+                Op::MergeBindings => {
+                    let matched = self.bindings.pop().unwrap();
+                    self.bindings.last_mut().unwrap().extend(matched);
+                    continue;
+                }
+                Op::DropBindings => {
+                    self.bindings.pop();
+                    continue;
+                }
+                Op::PushBindings => {
+                    let bindings = self.bindings.pop().unwrap();
+                    evaluator.push_scope(bindings);
+                    continue;
+                }
+                Op::Try { catch_to, finally_to } => {
+                    handlers.push(Handler {
+                        stack_len: self.stack.len(),
+                        scope_len: evaluator.env_stack.len(),
+                        loops_len: loops.len(),
+                        bindings_len: self.bindings.len(),
+                        catch_to,
+                        finally_to,
+                    });
+                    continue;
+                }
+                Op::EndTry => {
+                    handlers.pop();
+                    self.stack.push(Value::Number(NORMAL));
+                    continue;
+                }
+                Op::EndFinally => {
+                    let Some(Value::Number(code)) = self.stack.pop() else { unreachable!() };
+                    let payload = self.stack.pop().unwrap();
+                    match code {
+                        NORMAL => {
+                            self.stack.push(payload);
+                            continue;
+                        }
+                        RETURN => EvalResult::Return(payload),
+                        BREAK => EvalResult::Break,
+                        CONTINUE => EvalResult::Continue,
+                        _ => {
+                            let Value::Error(err) = payload else { unreachable!() };
+                            return Err(*err);
+                        }
+                    }
+                }
+            };
+
+            // a `try` inside the innermost loop sees its `break` first
+            let inside_try = handlers.last().is_some_and(|handler| handler.loops_len == loops.len());
+            match (signal, loops.last()) {
+                (EvalResult::Break, Some(record)) if !inside_try => {
+                    self.stack.truncate(record.stack_len);
+                    evaluator.env_stack.truncate(record.scope_len);
+                    *ip = record.break_to;
+                }
+                (EvalResult::Continue, Some(record)) if !inside_try => {
+                    self.stack.truncate(record.stack_len);
+                    evaluator.env_stack.truncate(record.scope_len);
+                    *ip = record.continue_to;
+                }
+                (signal, _) => return Ok(signal),
+            }
+        }
+
+        Ok(EvalResult::Value(self.stack.pop().unwrap_or(Value::Null)))
+    }
+
+    /// A closure for `function` over the current scopes, with the placeholder
+    /// for each scope around it that was kept in registers.
+    fn closure(&self, evaluator: &Evaluator, function: &Function) -> Value {
+        let frames = function.frames.iter().filter(|&&frame| frame).count();
+        let (outer, inner) = evaluator.env_stack.split_at(evaluator.env_stack.len() - frames);
+        let mut inner = inner.iter();
+        let mut env = outer.to_vec();
+        for &frame in &function.frames {
+            let scope = if frame { inner.next().unwrap() } else { &self.placeholder };
+            env.push(Rc::clone(scope));
+        }
+        evaluator.closure_in(env, &function.params, &function.body, function.doc.clone())
+    }
+
+    /// Runs user functions on the VM; everything else goes through `Evaluator::apply`.
+    /// A tail call left by the body runs here, in place of the returning function.
+    /// `frame` names the call in error call stacks.
+    fn call(
+        &mut self,
+        evaluator: &mut Evaluator,
+        func: Value,
+        args: Vec<(Option<String>, Value)>,
+        frame: Option<(&str, Span)>,
+    ) -> Result<EvalResult, PlutoError> {
+        let mut call = (func, args, frame.map(|(name, span)| (name.to_string(), span)));
+        loop {
+            let (func, args, frame) = call;
+            let Value::UserFunction { params, body, env, .. } = func else {
+                return evaluator.apply(func, args, frame.as_ref().map(|(name, span)| (name.as_str(), *span)));
+            };
+            let chunk = self.function_chunk(&params, &body);
+
+            // defaults are evaluated in the caller's scopes, before the switch
+            let base = self.registers.len();
+            let local = if chunk.frame_params {
+                Some(evaluator.bind_arguments(&params, args, "parameter")?)
+            } else if args.len() == params.len() && args.iter().all(|(name, _)| name.is_none()) {
+                self.registers.extend(args.into_iter().map(|(_, value)| value));
+                None
+            } else {
+                let bound = evaluator.bind_arguments(&params, args, "parameter")?;
+                self.registers.extend(bound.into_iter().map(|(_, (value, _))| value));
+                None
+            };
+            self.registers.resize(base + chunk.registers, Value::Null);
+
+            let caller_env = std::mem::replace(&mut evaluator.env_stack, env);
+            let caller_in_function = std::mem::replace(&mut evaluator.in_function, true);
+            if let Some(local) = local {
+                evaluator.push_scope(local);
+            }
+            let result = evaluator.enter_call().and_then(|_| {
+                let result = self.run_chunk(evaluator, &chunk, base);
+                evaluator.leave_call();
+                result
+            });
+            self.registers.truncate(base);
+            evaluator.env_stack = caller_env;
+            evaluator.in_function = caller_in_function;
+
+            let result = result.map_err(|e| match &frame {
+                Some((name, call_site)) => e.with_frame(name, *call_site),
+                None => e,
+            })?;
+            if let Some(tail) = evaluator.tail_call.take() {
+                call = (tail.func, tail.args, Some((tail.name, tail.call_site)));
+                continue;
+            }
+            return match result {
+                EvalResult::Return(value) => Ok(EvalResult::Value(value)),
+                other => Ok(other),
+            };
+        }
+    }
+
+    fn function_chunk(&mut self, params: &Params, body: &Rc<ASTNode>) -> Rc<Chunk> {
+        let key = Rc::as_ptr(body);
+        if let Some((_, chunk)) = self.functions.get(&key) {
+            return Rc::clone(chunk);
+        }
+        if self.functions.len() >= self.sweep_at {
+            self.functions.retain(|_, (body, _)| body.strong_count() > 0);
+            self.sweep_at = (self.functions.len() * 2).max(64);
+        }
+        let chunk = Rc::new(Compiler::compile_function(params, body, &self.defaults));
+        self.functions.insert(key, (Rc::downgrade(body), Rc::clone(&chunk)));
+        chunk
+    }
+}
+
+/// `Evaluator::eval_binary` on two `Number`s, when the result is a plain
+/// `Number` or `Bool`; `None` leaves overflow and errors to the general case.
+#[inline]
+fn number_binary(a: i64, op: BinaryOp, b: i64) -> Option<Value> {
+    let number = match op {
+        BinaryOp::Add => a.checked_add(b)?,
+        BinaryOp::Sub => a.checked_sub(b)?,
+        BinaryOp::Mul => a.checked_mul(b)?,
+        BinaryOp::Div if b != 0 => a.checked_div(b)?,
+        BinaryOp::Rem if b != 0 => a.checked_rem(b).unwrap_or(0),
+        BinaryOp::BitAnd => a & b,
+        BinaryOp::BitOr => a | b,
+        BinaryOp::BitXor => a ^ b,
+        BinaryOp::Eq => return Some(Value::Bool(a == b)),
+        BinaryOp::Ne => return Some(Value::Bool(a != b)),
+        BinaryOp::Lt => return Some(Value::Bool(a < b)),
+        BinaryOp::Gt => return Some(Value::Bool(a > b)),
+        BinaryOp::Le => return Some(Value::Bool(a <= b)),
+        BinaryOp::Ge => return Some(Value::Bool(a >= b)),
+        _ => return None,
+    };
+    Some(Value::Number(number))
+}
+
+/// The function a call is about to make, or why `name` cannot be called.
+fn callable(func: Option<Value>, name: &str) -> Result<Value, PlutoError> {
+    match func {
+        Some(func @ (Value::BuiltInFunction(_) | Value::UserFunction { .. } | Value::StructType(_))) => Ok(func),
+        Some(_) => Err(PlutoError::type_error(format!("'{}' is not a function", name))),
+        None => Err(PlutoError::name(format!("Unknown function '{}'", name))),
+    }
+}
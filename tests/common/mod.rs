@@ -0,0 +1,50 @@
+//! Helpers shared by the integration tests. Every program runs on both
+//! engines, and the tests fail unless the two agree exactly, errors included.
+
+#![allow(dead_code)]
+
+use pluto_lang::{Engine, ErrorKind, Interpreter, PlutoError};
+
+pub fn interpreter(engine: Engine) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_engine(engine);
+    interpreter
+}
+
+/// Runs `source` on the tree walker and the VM and returns the printed form
+/// of its last value, or the error both engines raised.
+pub fn run(source: &str) -> Result<String, PlutoError> {
+    let tree = interpreter(Engine::TreeWalker).run_source(source).map(|v| v.to_string());
+    let vm = interpreter(Engine::Vm).run_source(source).map(|v| v.to_string());
+    assert_eq!(tree, vm, "the engines disagree on:\n{}", source);
+    tree
+}
+
+/// The printed value of a program that must succeed.
+pub fn eval(source: &str) -> String {
+    run(source).unwrap_or_else(|e| panic!("{}: {}\nin:\n{}", e.kind, e.message, source))
+}
+
+/// The error of a program that must fail.
+pub fn error(source: &str) -> PlutoError {
+    match run(source) {
+        Ok(value) => panic!("expected an error, got {}\nin:\n{}", value, source),
+        Err(e) => e,
+    }
+}
+
+/// Asserts that `source` fails with `kind` and a message containing `message`.
+pub fn assert_error(source: &str, kind: ErrorKind, message: &str) {
+    let e = error(source);
+    assert_eq!(e.kind, kind, "wrong kind for '{}' in:\n{}", e.message, source);
+    assert!(e.message.contains(message), "'{}' does not mention '{}'", e.message, message);
+}
+
+/// The 1-based (line, column) an error points at.
+pub fn location(e: &PlutoError, source: &str) -> (usize, usize) {
+    let start = e.span.expect("error without a span").start;
+    let before: Vec<char> = source.chars().take(start).collect();
+    let line = before.iter().filter(|&&c| c == '\n').count() + 1;
+    let column = before.iter().rev().take_while(|&&c| c != '\n').count() + 1;
+    (line, column)
+}
@@ -0,0 +1,209 @@
+mod common;
+
+use std::path::Path;
+use std::rc::Rc;
+
+use common::interpreter;
+use pluto_lang::{Engine, ErrorKind, Interpreter, Overflow, PlutoError, Value};
+
+const ENGINES: [Engine; 2] = [Engine::TreeWalker, Engine::Vm];
+
+#[test]
+fn globals_persist_between_runs() {
+    for engine in ENGINES {
+        let mut pluto = interpreter(engine);
+        pluto.run_source("let total = 40; fn add(x) { return total + x; }").unwrap();
+        pluto.run_source("total = add(2);").unwrap();
+        assert_eq!(pluto.get_global("total").unwrap().to_string(), "42");
+        assert_eq!(pluto.eval_expr("total * 2").unwrap().to_string(), "84");
+
+        pluto.set_global("name", Value::String("host".to_string()));
+        assert_eq!(pluto.run_source("\"hi \" + name;").unwrap().to_string(), "hi host");
+
+        pluto.reset();
+        assert!(pluto.get_global("total").is_none());
+        assert!(pluto.get_global("print").is_some());
+    }
+}
+
+#[test]
+fn top_level_return_ends_the_script() {
+    for engine in ENGINES {
+        let value = interpreter(engine).run_source("let x = 1; return x + 1; x = 10;").unwrap();
+        assert_eq!(value.to_string(), "2");
+    }
+}
+
+#[test]
+fn registered_functions_can_call_back_into_pluto() {
+    for engine in ENGINES {
+        let mut pluto = interpreter(engine);
+        pluto.register_fn("twice", |evaluator, args| {
+            let f = args.first().cloned().unwrap_or(Value::Null);
+            let once = evaluator.call_value(&f, vec![Value::Number(1)])?;
+            evaluator.call_value(&f, vec![once])
+        });
+        pluto.register_fn("fail", |_, _| Err(PlutoError::argument("bad input")));
+        assert_eq!(pluto.run_source("twice((x) -> x * 10);").unwrap().to_string(), "100");
+
+        let e = pluto.run_source("let r = \"\"; try { fail(); } catch (e) { r = e.kind; } r;").unwrap();
+        assert_eq!(e.to_string(), "ArgumentError");
+    }
+}
+
+#[test]
+fn overflow_can_raise_instead_of_promoting() {
+    for engine in ENGINES {
+        let mut pluto = interpreter(engine);
+        pluto.set_overflow(Overflow::Error);
+        let e = pluto.run_source("9223372036854775807 + 1;").unwrap_err();
+        assert_eq!(e.kind, ErrorKind::Runtime);
+        assert_eq!(e.message, "Integer overflow");
+    }
+}
+
+#[test]
+fn scripts_import_relative_to_their_directory() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let source = "
+        import \"modules/geometry.po\" as geo;
+        from \"modules/numbers.po\" import { clamp, lerp };
+        [geo.area(h=4, w=3), geo.clamped_area(20, 2), clamp(0 - 5, 0, 10), lerp(0, 10, 0.5), geo.UNIT];";
+    for engine in ENGINES {
+        let mut pluto = interpreter(engine);
+        let value = pluto.run_script(source, &dir.join("main.po")).unwrap();
+        assert_eq!(value.to_string(), "[12, 20, 0, 5, 1]");
+
+        let e = pluto
+            .run_script("from \"modules/numbers.po\" import { missing };", &dir.join("main.po"))
+            .unwrap_err();
+        assert_eq!(e.kind, ErrorKind::Import);
+    }
+}
+
+#[test]
+fn module_state_stays_live() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let source = "
+        import \"modules/counter.po\" as c;
+        import \"modules/counter.po\" as again;
+        let before = c.count;
+        c.bump();
+        c.bump();
+        [before, c.count, again.count, again.bump(), c.count];";
+    for engine in ENGINES {
+        let mut pluto = interpreter(engine);
+        let value = pluto.run_script(source, &dir.join("main.po")).unwrap();
+        assert_eq!(value.to_string(), "[0, 2, 2, 3, 3]");
+    }
+}
+
+#[test]
+fn max_depth_raises_a_catchable_error() {
+    let source = "fn down(n) { return 1 + down(n + 1); }";
+    for engine in ENGINES {
+        let mut pluto = interpreter(engine);
+        pluto.set_max_depth(10);
+        pluto.run_source(source).unwrap();
+        let e = pluto.run_source("down(0);").unwrap_err();
+        assert_eq!(e.kind, ErrorKind::StackOverflow);
+        assert_eq!(e.message, "Stack overflow: maximum call depth of 10 exceeded");
+        let caught = pluto.run_source("let k = \"\"; try { down(0); } catch (e) { k = e.kind; } k;").unwrap();
+        assert_eq!(caught.to_string(), "StackOverflowError");
+    }
+}
+
+#[test]
+fn recursion_to_the_default_limit_fails_without_crashing() {
+    // runs on a test thread, with Rust's default 2 MiB stack
+    let source = "fn down(n) { return 1 + down(n + 1); }";
+    for engine in ENGINES {
+        let mut pluto = interpreter(engine);
+        pluto.run_source(source).unwrap();
+        for call in ["down(0);", "[0].map((n) -> down(n));", "fn up(n) { return [n].map((m) -> up(m + 1)); } up(0);"] {
+            let e = pluto.run_source(call).unwrap_err();
+            assert_eq!(e.kind, ErrorKind::StackOverflow, "{}", call);
+        }
+        let caught = pluto.run_source("let k = \"\"; try { down(0); } catch (e) { k = e.kind; } k;").unwrap();
+        assert_eq!(caught.to_string(), "StackOverflowError");
+    }
+}
+
+#[test]
+fn max_stack_allows_the_full_depth_on_a_larger_thread() {
+    let deep = std::thread::Builder::new().stack_size(256 * 1024 * 1024).spawn(|| {
+        for engine in ENGINES {
+            let mut pluto = interpreter(engine);
+            pluto.set_max_stack(192 * 1024 * 1024);
+            pluto.run_source("fn depth(n) { return ? n == 0 -> 0 : 1 + depth(n - 1); }").unwrap();
+            assert_eq!(pluto.run_source("depth(999);").unwrap().to_string(), "999");
+            let e = pluto.run_source("depth(1000);").unwrap_err();
+            assert_eq!(e.message, "Stack overflow: maximum call depth of 1000 exceeded");
+        }
+    });
+    deep.unwrap().join().unwrap();
+}
+
+#[test]
+fn a_default_interpreter_uses_the_tree_walker() {
+    let mut pluto = Interpreter::default();
+    assert_eq!(pluto.run_source("[1, 2].map((x) -> x + 1);").unwrap().to_string(), "[2, 3]");
+}
+
+#[test]
+fn closures_from_one_declaration_share_their_body() {
+    for engine in ENGINES {
+        let mut pluto = interpreter(engine);
+        pluto.run_source("fn make(n) { return (x) -> x + n; } let a = make(1); let b = make(2);").unwrap();
+        let (Some(Value::UserFunction { body: a, .. }), Some(Value::UserFunction { body: b, .. })) =
+            (pluto.get_global("a"), pluto.get_global("b"))
+        else {
+            panic!("expected two closures");
+        };
+        assert!(Rc::ptr_eq(&a, &b));
+        assert_eq!(pluto.run_source("[a(10), b(10)];").unwrap().to_string(), "[11, 12]");
+    }
+}
+
+#[test]
+fn scopes_captured_by_closures_are_freed() {
+    let make = "fn make() { let c = 0; fn inc() { c += 1; return c; } return inc; } let f = make(); f();";
+    let captured = |pluto: &Interpreter| {
+        let Some(Value::UserFunction { env, .. }) = pluto.get_global("f") else {
+            panic!("expected a closure");
+        };
+        Rc::downgrade(env.last().unwrap())
+    };
+    for engine in ENGINES {
+        // freed while the program runs, once enough cycles pile up
+        let mut pluto = interpreter(engine);
+        pluto.run_source(make).unwrap();
+        let frame = captured(&pluto);
+        pluto.run_source("f = null; for i in 0..2000 { let g = make(); g(); }").unwrap();
+        assert!(frame.upgrade().is_none(), "a frame kept alive by its own closure was not freed");
+
+        // and when the interpreter goes away
+        let mut pluto = interpreter(engine);
+        pluto.run_source(make).unwrap();
+        let frame = captured(&pluto);
+        drop(pluto);
+        assert!(frame.upgrade().is_none());
+    }
+}
+
+#[test]
+fn closures_in_use_survive_collection() {
+    for engine in ENGINES {
+        let source = "
+            fn make(n) { let c = n; fn inc() { c += 1; return c; } return inc; }
+            let kept = make(100);
+            let counters = [];
+            for i in 0..3000 {
+                let counter = make(i);
+                counter();
+                if (i % 1000 == 0) { counters = counters.push(counter); }
+            }
+            [kept(), kept(), counters.map((f) -> f())];";
+        assert_eq!(interpreter(engine).run_source(source).unwrap().to_string(), "[101, 102, [2, 1002, 2002]]");
+    }
+}
@@ -0,0 +1,35 @@
+//! Runs `tests/features.po`, the tour of the language, end to end on both engines.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+use common::interpreter;
+use pluto_lang::Engine;
+
+/// The tour recurses deeper than a test thread's default stack allows.
+const STACK_SIZE: usize = 1 << 30;
+
+#[test]
+fn the_feature_tour_runs_to_completion() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/features.po");
+    let source = fs::read_to_string(&path).unwrap();
+    for engine in [Engine::TreeWalker, Engine::Vm] {
+        let (source, path) = (source.clone(), path.clone());
+        let result = thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                let mut pluto = interpreter(engine);
+                pluto.set_max_stack(STACK_SIZE / 2);
+                pluto.run_script(&source, &path).map(|_| ()).map_err(|e| e.to_string())
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        if let Err(e) = result {
+            panic!("features.po failed on {:?}:\n{}", engine, e);
+        }
+    }
+}
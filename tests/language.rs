@@ -0,0 +1,269 @@
+mod common;
+
+use common::{assert_error, error, eval, location};
+use pluto_lang::ErrorKind;
+
+#[test]
+fn arithmetic_follows_precedence() {
+    assert_eq!(eval("1 + 2 * 3 - 4 / 2;"), "5");
+    assert_eq!(eval("(1 + 2) * 3 % 5;"), "4");
+    assert_eq!(eval("7.0 / 2;"), "3.5");
+    assert_eq!(eval("\"a\" + 1 + 2;"), "a12");
+}
+
+#[test]
+fn variables_constants_and_scopes() {
+    assert_eq!(eval("let x = 1; if (true) { let x = 2; x = 3; } x;"), "1");
+    assert_eq!(eval("let x = 1; if (true) { x = 5; } x;"), "5");
+    assert_error("const c = 1; c = 2;", ErrorKind::Syntax, "Cannot assign to constant 'c'");
+    assert_error("if (true) { print(y); let y = 1; }", ErrorKind::Name, "used before its declaration");
+}
+
+#[test]
+fn runtime_errors_carry_spans() {
+    let source = "let a = [1, 2];\nlet b = a[7];";
+    let e = error(source);
+    assert_eq!(e.kind, ErrorKind::Index);
+    assert_eq!(location(&e, source), (2, 9));
+
+    let source = "fn f() {\n    return missing;\n}\nf();";
+    let e = error(source);
+    assert_eq!(e.kind, ErrorKind::Name);
+    assert_eq!(location(&e, source), (2, 12));
+    let stack = e.call_stack.expect("no call stack");
+    assert_eq!(stack[0].function, "f");
+}
+
+#[test]
+fn error_columns_count_characters() {
+    let source = "let s = \"日本語\"; print(oops);";
+    assert_eq!(location(&error(source), source), (1, 22));
+
+    let source = "let s = \"héllo\";\nlet x = ;";
+    assert_eq!(location(&error(source), source), (2, 9));
+
+    let source = "let s = \"日本語日本語\";\nlet t = (1 +";
+    let e = error(source);
+    assert_eq!(e.kind, ErrorKind::Syntax);
+    assert_eq!(location(&e, source), (2, 13));
+}
+
+#[test]
+fn try_catch_finally_and_throw() {
+    assert_eq!(eval("let r = 0; try { [1][5]; } catch (e) { r = e.kind; } r;"), "IndexError");
+    assert_eq!(eval("let r = \"\"; try { throw \"boom\"; } catch (e) { r = e.message; } finally { r += \"!\"; } r;"), "boom!");
+    assert_error("throw \"uncaught\";", ErrorKind::User, "uncaught");
+    assert_eq!(eval("fn f() { let log = []; for (let i = 0; i < 4; i++) { try { if (i == 1) { continue; } if (i == 3) { break; } log = log.push(i); } finally { log = log.push(\"f${i}\"); } } return log; } f();"), "[0, f0, f1, 2, f2, f3]");
+    assert_eq!(eval("let n = 0; fn f() { try { return 1; } finally { n = 5; } } [f(), n];"), "[1, 5]");
+    assert_eq!(eval("fn f() { try { try { throw \"a\"; } finally { 1; } } catch (e) { return e.message; } } f();"), "a");
+    assert_eq!(eval("fn f() { let g = null; try { [][1]; } catch (e) { g = () -> e.kind; } return g(); } f();"), "IndexError");
+}
+
+#[test]
+fn for_in_loops() {
+    assert_eq!(eval("let t = 0; for x in [1, 2, 3] { t += x; } t;"), "6");
+    assert_eq!(eval("let t = 0; for i in 0..=4 { t += i; } t;"), "10");
+    assert_eq!(eval("let s = \"\"; for c in \"abc\" { s = c + s; } s;"), "cba");
+    assert_eq!(eval("let s = \"\"; for (k, v) in {\"b\": 1, \"a\": 2} { s += k + v; } s;"), "b1a2");
+    assert_eq!(eval("let t = 0; for i in 0..10 { if (i == 3) { continue; } if (i == 5) { break; } t += i; } t;"), "7");
+}
+
+#[test]
+fn loops_and_access_inside_functions() {
+    assert_eq!(eval("fn first(xs, p) { for x in xs { if (p(x)) { return x; } } return null; } first(0..100, (x) -> x * x > 50);"), "8");
+    assert_eq!(eval("fn pairs() { let out = []; for i in 0..3 { for j in 0..3 { if (j > i) { break; } if (j == 1) { continue; } out = out.push([i, j]); } } return out; } pairs();"), "[[0, 0], [1, 0], [2, 0], [2, 2]]");
+    assert_eq!(eval("fn sum(m) { let t = 0; for (k, v) in m { t += v * k.len(); } return t; } sum({\"a\": 1, \"bb\": 2});"), "5");
+    assert_eq!(eval("fn f(s) { return [s[1], s[0..2], s.to_upper().len(), Math.sqrt(16)]; } f(\"xyz\");"), "[y, xy, 3, 4]");
+    assert_eq!(eval("struct P { x, y } fn norm(p) { return p.x * p.x + p.y * p.y; } norm(P(3, 4));"), "25");
+    assert_eq!(eval("fn f() { const k = 2; return k * 3; } [f(), f()];"), "[6, 6]");
+    assert_eq!(eval("let n = 0; fn bump() { n += 1; return n; } fn f() { const c = 1; const c = bump(); } try { f(); } catch (e) {} n;"), "0");
+
+    let source = "fn f(p) {\n    return p.z;\n}\nf(Math);";
+    let e = error(source);
+    assert_eq!(e.message, "No such member 'z' for 'p'");
+    assert_eq!(location(&e, source), (2, 12));
+    let source = "fn f(xs) {\n    for (a, b) in xs { }\n}\nf([[1, 2], 3]);";
+    let e = error(source);
+    assert_eq!(e.kind, ErrorKind::Type);
+    assert_eq!(location(&e, source), (2, 5));
+    assert_error("fn f() { for x in 5 { } } f();", ErrorKind::Type, "Cannot iterate over Number");
+    assert_error("fn f() { return \"s\".nope(); } f();", ErrorKind::Attribute, "nope");
+}
+
+#[test]
+fn ranges_are_lazy() {
+    assert_eq!(eval("(0..1000000000).len();"), "1000000000");
+    assert_eq!(eval("(0..10)[3];"), "3");
+    assert_eq!(eval("(10..0).step(0 - 3).to_array();"), "[10, 7, 4, 1]");
+    assert_eq!(eval("(1..4).map((x) -> x * 10);"), "[10, 20, 30]");
+}
+
+#[test]
+fn structs_and_methods() {
+    let source = "
+        struct Point { x, y = 0 }
+        impl Point {
+            fn sum(self) { return self.x + self.y; }
+        }
+        let p = Point(3, y = 4);
+        p.x = 10;
+        p.sum();";
+    assert_eq!(eval(source), "14");
+    assert_error("struct P { x } let p = P(1); p.z;", ErrorKind::Attribute, "z");
+}
+
+#[test]
+fn match_patterns() {
+    let source = "
+        fn describe(v) {
+            return match v {
+                0 -> \"zero\",
+                1 | 2 -> \"small\",
+                3..10 -> \"medium\",
+                [first, ..rest] -> rest.len() + 1,
+                {name} -> name,
+                n if n < 0 -> \"negative\",
+                _ -> \"other\",
+            };
+        }
+        [describe(0), describe(2), describe(5), describe([1, 2]), describe({\"name\": \"x\"}), describe(0 - 4), describe(99)];";
+    assert_eq!(eval(source), "[zero, small, medium, 2, x, negative, other]");
+    assert_eq!(eval("fn f() { let t = 0; for (let i = 0; i < 10; i++) { match i { 5 -> { break; }, x if x % 2 == 0 -> { continue; }, _ -> { t += i; } }; } return t; } f();"), "4");
+    assert_eq!(eval("fn f(v) { let g = match v { [x] -> () -> x, _ -> () -> 0 }; return g(); } [f([7]), f(1)];"), "[7, 0]");
+}
+
+#[test]
+fn closures_share_live_bindings() {
+    let counter = "
+        fn make() {
+            let c = 0;
+            return () -> { c += 1; return c; };
+        }
+        let inc = make();
+        inc(); inc();
+        inc();";
+    assert_eq!(eval(counter), "3");
+    assert_eq!(eval("let x = 1; let get = () -> x; x = 2; get();"), "2");
+}
+
+#[test]
+fn deep_tail_recursion_runs_in_constant_stack() {
+    assert_eq!(eval("fn loop(n, acc) { if (n == 0) { return acc; } return loop(n - 1, acc + 1); } loop(100000, 0);"), "100000");
+}
+
+#[test]
+fn integer_overflow_promotes_to_bigint() {
+    assert_eq!(eval("9223372036854775807 + 1;"), "9223372036854775808");
+    assert_eq!(eval("let f = 1; for i in 1..=25 { f *= i; } f;"), "15511210043330985984000000");
+    assert_eq!(eval("(1 << 70) >> 68;"), "4");
+    assert_eq!(eval("(2 * 9223372036854775807) / 2 == 9223372036854775807;"), "true");
+}
+
+#[test]
+fn string_interpolation() {
+    assert_eq!(eval("let n = 2; \"n=${n}, twice=${n * 2}, ${\"in ${n}\"}\";"), "n=2, twice=4, in 2");
+    assert_eq!(eval("\"\\${n}\";"), "${n}");
+}
+
+#[test]
+fn comments_and_doc_comments() {
+    assert_eq!(eval("/* outer /* nested */ still */ 1 // trailing\n+ 1;"), "2");
+    assert_eq!(eval("/// Adds one.\nfn inc(x) { return x + 1; }\ndoc(inc);"), "Adds one.");
+    assert_eq!(eval("/** Squares. */\nconst sq = (x) -> x * x;\ndoc(sq);"), "Squares.");
+    assert_eq!(eval("/// Not a function.\nconst K = 1;\ndoc(K);"), "null");
+}
+
+#[test]
+fn numeric_literals() {
+    assert_eq!(eval("[0xFF, 0o755, 0b1010, 1_000_000, 1e-3, .5];"), "[255, 493, 10, 1000000, 0.001, 0.5]");
+    assert_error("0b102;", ErrorKind::Syntax, "Invalid digit '2' in binary literal");
+    assert_error("99999999999999999999;", ErrorKind::Syntax, "out of range");
+}
+
+#[test]
+fn malformed_numeric_literals_are_rejected() {
+    for (source, message) in [
+        ("1e;", "Missing digits in exponent of '1e'"),
+        ("1e+;", "Missing digits in exponent of '1e+'"),
+        ("2E-;", "Missing digits in exponent of '2E-'"),
+        ("1__0;", "Invalid '_' in numeric literal '1__0'"),
+        ("1_;", "Invalid '_' in numeric literal '1_'"),
+        ("0x_FF;", "Invalid '_' in numeric literal '0x_FF'"),
+    ] {
+        let e = error(source);
+        assert_eq!(e.kind, ErrorKind::Syntax);
+        assert_eq!(e.message, message);
+        assert_eq!(location(&e, source), (1, 1));
+    }
+}
+
+#[test]
+fn string_escapes_and_raw_strings() {
+    assert_eq!(eval("\"caf\\u{E9} \\x41\\t|\";"), "café A\t|");
+    assert_eq!(eval("r\"C:\\new\";"), "C:\\new");
+    assert_eq!(eval("\"\"\"\n    a\n      b\n    \"\"\";"), "a\n  b");
+    assert_error("\"\\q\";", ErrorKind::Syntax, "Unknown escape sequence '\\q'");
+}
+
+#[test]
+fn bitwise_operators_use_c_precedence() {
+    assert_eq!(eval("[6 & 3, 6 | 3, 6 ^ 3, ~5, 1 << 4, 0 - 16 >> 2];"), "[2, 7, 5, -6, 16, -4]");
+    assert_eq!(eval("[1 + 2 << 1, (1 | 2) == 3];"), "[6, true]");
+    assert_error("1 | 2 == 3;", ErrorKind::Type, "incompatible types");
+    assert_error("1 << (0 - 1);", ErrorKind::Runtime, "Negative shift amount");
+}
+
+#[test]
+fn compound_assignment_and_postfix_operators() {
+    assert_eq!(eval("let x = 6; x += 1; x -= 2; x *= 3; x /= 5; x &= 7; x |= 8; x ^= 1; x <<= 2; x >>= 1; x;"), "20");
+    assert_eq!(eval("let f = 1.5; f *= 2; f -= 0.5; let s = \"a\"; s += \"b\"; [f, s];"), "[2.5, ab]");
+    assert_eq!(eval("let i = 1; let before = i++; i++; i--; [before, i];"), "[1, 2]");
+    assert_eq!(eval("struct C { n } let c = C(1); c.n += 4; c.n;"), "5");
+    assert_error("let s = \"a\"; s -= 1;", ErrorKind::Type, "Unsupported assignment operator");
+}
+
+#[test]
+fn logical_operators_short_circuit() {
+    assert_eq!(eval("let n = 0; fn bump() { n += 1; return true; } false && bump(); true || bump(); n;"), "0");
+    assert_eq!(eval("[0 || \"x\", \"\" && 1, null ?: 5, [] ?: \"empty\"];"), "[true, false, 5, empty]");
+    assert_eq!(eval("!0 && !\"\";"), "true");
+}
+
+#[test]
+fn index_assignment_reaches_outer_and_captured_variables() {
+    assert_eq!(eval("let a = [1, 2, 3]; if (true) { a[0] = 9; } a;"), "[9, 2, 3]");
+    assert_eq!(eval("let m = {}; fn put(k, v) { m[k] = v; } put(\"a\", 1); put(\"b\", 2); m;"), "{a: 1, b: 2}");
+    assert_eq!(eval("let m = {\"n\": 0}; let bump = () -> { m[\"n\"] = m[\"n\"] + 1; }; bump(); bump(); m[\"n\"];"), "2");
+    assert_eq!(eval("fn make() { let seen = []; return (x) -> { seen = seen.push(x); seen[0] = x; return seen; }; } let f = make(); f(1); f(2);"), "[2, 2]");
+    assert_eq!(eval("let grid = [[0, 0], [0, 0]]; for i in 0..2 { grid[i][i] = 1; } grid;"), "[[1, 0], [0, 1]]");
+    assert_eq!(eval("let a = [0]; a[0] = 5;"), "5");
+}
+
+#[test]
+fn index_assignment_errors() {
+    assert_error("let a = [1]; a[3] = 0;", ErrorKind::Index, "Array index out of bounds");
+    assert_error("let a = [1]; a[\"x\"] = 0;", ErrorKind::Type, "arrays with integer indices");
+    assert_error("let s = \"abc\"; s[0] = \"x\";", ErrorKind::Type, "Cannot assign to an index of a String");
+    assert_error("const a = [1]; a[0] = 2;", ErrorKind::Syntax, "Cannot assign to constant 'a'");
+    assert_error("fn f() { missing[0] = 1; } f();", ErrorKind::Name, "missing");
+    assert_error("struct P { x } fn f() { const p = P(1); p.x = 2; } f();", ErrorKind::Runtime, "Cannot assign to constant 'p'");
+}
+
+#[test]
+fn resolved_slots_match_runtime_scopes() {
+    assert_eq!(eval("let x = \"outer\"; if (true) let x = \"inner\"; x;"), "outer");
+    assert_eq!(eval("fn f(c) { if (c) let a = 1; let b = 2; return b; } [f(true), f(false)];"), "[2, 2]");
+    assert_eq!(eval("fn f(n) { let a = n; if (n > 0) { let b = a * 2; if (true) { a = b + 1; } return a; } return a; } [f(0), f(3)];"), "[0, 7]");
+    assert_eq!(eval("fn f() { let g = () -> later; let later = 5; return g(); } f();"), "5");
+    assert_eq!(eval("let fs = []; for i in 0..3 { fs = fs.push(() -> i); } fs.map((f) -> f());"), "[0, 1, 2]");
+    assert_eq!(eval("fn f() { let t = 0; let i = 0; while (i < 4) { if (i % 2 == 0) { t += i; } i++; } return t; } f();"), "2");
+    assert_eq!(eval("fn even(n) { return ? n == 0 -> true : odd(n - 1); } fn odd(n) { return ? n == 0 -> false : even(n - 1); } even(10);"), "true");
+}
+
+#[test]
+fn or_pattern_bindings_follow_the_matching_alternative() {
+    assert_eq!(eval("fn f(v) { return match v { [a, b, 0] | [b, a] -> \"${a}${b}\", _ -> \"none\" }; } [f([1, 2, 0]), f([1, 2])];"), "[12, 21]");
+    assert_eq!(eval("match 5 { [x] | x -> x + 1 };"), "6");
+    assert_eq!(eval("match [[1, 2]] { [[a, 9] | [9, a]] -> 0, [[p, q] | [q, p, 0]] -> p * 10 + q };"), "12");
+    assert_error("match 5 { [y] | x -> y };", ErrorKind::Name, "'y'");
+}
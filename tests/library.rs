@@ -0,0 +1,124 @@
+mod common;
+
+use common::{assert_error, error, eval, location};
+use pluto_lang::ErrorKind;
+
+#[test]
+fn string_methods() {
+    assert_eq!(eval("\"a,b,,c\".split(\",\");"), "[a, b, , c]");
+    assert_eq!(eval("let parts = \"\".split(\",\"); [parts.len(), parts[0] == \"\"];"), "[1, true]");
+    assert_eq!(eval("\"\".split().len();"), "0");
+    assert_eq!(eval("\"  hi  \".trim() + \"|\" + \"  hi\".trim_start() + \"|\" + \"hi  \".trim_end();"), "hi|hi|hi");
+    assert_eq!(eval("\"banana\".replace(\"an\", \"AN\");"), "bANANa");
+    assert_eq!(eval("[\"abc\".contains(\"b\"), \"abc\".starts_with(\"ab\"), \"abc\".ends_with(\"bc\")];"), "[true, true, true]");
+    assert_eq!(eval("[\"hello\".index_of(\"l\"), \"hello\".index_of(\"z\")];"), "[2, -1]");
+    assert_eq!(eval("\"hello\".substring(1, 3) + \"ab\".repeat(2);"), "elabab");
+    assert_eq!(eval("\"a\\nb\".lines().len() + \"abc\".chars().len();"), "5");
+    assert_eq!(eval("\"7\".pad_start(3, \"0\") + \"|\" + \"7\".pad_end(3);"), "007|7  ");
+    assert_eq!(eval("[\"abc\".reverse(), \"\".is_empty(), \"banana\".count(\"a\")];"), "[cba, true, 3]");
+    assert_eq!(eval("[\"42\".to_int() + 1, \"Hi\".to_upper(), \"Hi\".to_lower()];"), "[43, HI, hi]");
+    assert_error("\"abc\".split(1);", ErrorKind::Argument, "split expects a String separator");
+}
+
+#[test]
+fn strings_index_and_slice_by_character() {
+    assert_eq!(eval("\"héllo\"[1];"), "é");
+    assert_eq!(eval("\"héllo\"[1..3];"), "él");
+    assert_eq!(eval("\"日本語\".len();"), "3");
+    assert_eq!(eval("\"日本語\".bytes().len();"), "9");
+    assert_eq!(eval("\"e\\u{301}!\".graphemes().len();"), "2");
+    assert_error("\"abc\"[5];", ErrorKind::Index, "out of bounds");
+}
+
+#[test]
+fn array_methods() {
+    assert_eq!(eval("[3, 1, 2].sort();"), "[1, 2, 3]");
+    assert_eq!(eval("[1, 2, 3, 4].filter((x) -> x % 2 == 0).map((x) -> x * x);"), "[4, 16]");
+    assert_eq!(eval("[1, 2, 3].reduce((a, b) -> a + b);"), "6");
+    assert_eq!(eval("[1, 2, 3].fold(10, (a, b) -> a + b);"), "16");
+    assert_eq!(eval("[[1, 2], [3]].flat_map((x) -> x);"), "[1, 2, 3]");
+    assert_eq!(eval("[1, 2].zip([\"a\", \"b\"]);"), "[[1, a], [2, b]]");
+    assert_eq!(eval("[1, 1, 2, 1].unique();"), "[1, 2]");
+    assert_eq!(eval("[1, 2, 3, 4, 5].chunk(2);"), "[[1, 2], [3, 4], [5]]");
+    assert_eq!(eval("[[5, 2, 9].min(), [5, 2, 9].max(), [5, 2, 9].first(), [5, 2, 9].last()];"), "[2, 9, 5, 9]");
+    assert_eq!(eval("[\"bb\", \"a\", \"ccc\"].sort_by((s) -> s.len()).join(\"-\");"), "a-bb-ccc");
+    assert_eq!(eval("[1, 2, 3, 4][1..3];"), "[2, 3]");
+    assert_eq!(eval("let a = [1]; let b = a.push(2).push(3); [a, b];"), "[[1], [1, 2, 3]]");
+    assert_error("[1, 2].map(5);", ErrorKind::Type, "Cannot invoke a non-function value");
+}
+
+#[test]
+fn hashmaps_keep_insertion_order() {
+    let source = "
+        let m = {\"z\": 1, \"a\": 2};
+        m[\"m\"] = 3;
+        m[\"z\"] = 4;
+        [m.keys(), m.values(), m.len()];";
+    assert_eq!(eval(source), "[[z, a, m], [4, 2, 3], 3]");
+    assert_eq!(eval("let m = {\"a\": 1, \"b\": 2}.delete(\"a\"); m[\"a\"] = 5; m.keys();"), "[b, a]");
+}
+
+#[test]
+fn hashmap_methods() {
+    assert_eq!(eval("{\"a\": 1}.has(\"a\");"), "true");
+    assert_eq!(eval("{\"a\": 1}.get_or(\"b\", 0);"), "0");
+    assert_eq!(eval("{\"a\": 1}.merge({\"b\": 2, \"a\": 3});"), "{a: 3, b: 2}");
+    assert_eq!(eval("{\"a\": 1, \"b\": 2}.map_values((v) -> v * 10);"), "{a: 10, b: 20}");
+    assert_eq!(eval("{\"a\": 1, \"b\": 2}.filter((k, v) -> v > 1);"), "{b: 2}");
+    assert_eq!(eval("{\"a\": 1}.entries();"), "[[a, 1]]");
+    assert_error("{\"a\": 1}[\"b\"];", ErrorKind::Key, "b");
+}
+
+#[test]
+fn range_methods() {
+    assert_eq!(eval("(0..5).rev().to_array();"), "[4, 3, 2, 1, 0]");
+    assert_eq!(eval("[(0..5).contains(4), (0..5).contains(5), (0..=5).contains(5)];"), "[true, false, true]");
+    assert_eq!(eval("(0..10).step(4).to_array();"), "[0, 4, 8]");
+    assert_eq!(eval("(0..=9223372036854775807).len();"), "9223372036854775808");
+    assert_eq!(eval("(0..=9223372036854775807).rev()[0];"), "9223372036854775807");
+}
+
+#[test]
+fn ranges_slice_into_ranges() {
+    assert_eq!(eval("(0..5)[1..3].to_array();"), "[1, 2]");
+    assert_eq!(eval("(0..20).step(3)[1..=3].to_array();"), "[3, 6, 9]");
+    assert_eq!(eval("(0..5)[(1..4).rev()].to_array();"), "[3, 2, 1]");
+    assert_eq!(eval("(0..5)[2..2].len();"), "0");
+    assert_error("(0..5)[3..7];", ErrorKind::Index, "Range index out of bounds");
+}
+
+#[test]
+fn methods_are_looked_up_by_receiver_type() {
+    assert_eq!(eval("[(1..4).sum(), (1..4).map((x) -> x * 2).len(), 5.to_float().to_int(), 2.5.to_string().len()];"), "[6, 3, 5, 3]");
+    assert_error("\"abc\".push(1);", ErrorKind::Attribute, "No such method 'push' for String");
+    assert_error("(0..3).nope();", ErrorKind::Attribute, "No such method 'nope' for Range");
+    assert_error("{}.len2();", ErrorKind::Attribute, "No such method 'len2' for HashMap");
+}
+
+#[test]
+fn global_functions() {
+    assert_eq!(eval("[type(1), type(1.5), type(\"s\"), type([]), type({}), type(null)];"), "[Number, Float, String, Array, HashMap, Null]");
+    assert_eq!(eval("format(\"{} + {} = {}\", 1, 2, 3);"), "1 + 2 = 3");
+    assert_eq!(eval("[Math.pow(2, 10), Math.sqrt(16.0)];"), "[1024, 4]");
+    assert_eq!(eval("Math.pow(2, 64);"), "18446744073709551616");
+}
+
+#[test]
+fn module_functions_check_their_arguments() {
+    assert_error("Time.sleep(\"x\");", ErrorKind::Type, "Time.sleep expects a Number");
+    assert_error("Time.sleep(0 - 1);", ErrorKind::Value, "non-negative");
+    assert_error("Math.sqrt(0 - 1);", ErrorKind::Value, "Math.sqrt of a negative number");
+    assert_error("Math.sqrt(0.0 - 4);", ErrorKind::Value, "Math.sqrt of a negative number");
+    assert_error("Random.int(\"a\", 5);", ErrorKind::Type, "Random.int expects Number bounds");
+    assert_error("Random.int(5, 1);", ErrorKind::Value, "reversed");
+    assert_error("Random.bool(\"x\");", ErrorKind::Type, "Random.bool expects a Number");
+    assert_error("Random.bool(2);", ErrorKind::Value, "between 0 and 1");
+    assert_error("Random.choice(\"abc\");", ErrorKind::Type, "Random.choice expects an Array");
+    assert_error("Random.choice([]);", ErrorKind::Value, "empty array");
+    assert_error("Random.shuffle(5);", ErrorKind::Type, "Random.shuffle expects an Array");
+    assert_eq!(eval("[Random.int(3, 3), Random.choice([7]), Random.shuffle([]), Random.bool(1)];"), "[3, 7, [], true]");
+    assert_eq!(eval("let n = Random.int(0 - 9223372036854775807 - 1, 9223372036854775807); type(n);"), "Number");
+
+    let source = "let x = 1;\nlet y = Math.sqrt(0 - x);";
+    assert_eq!(location(&error(source), source), (2, 9));
+}